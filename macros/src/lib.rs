@@ -0,0 +1,101 @@
+//! Attribute macro of test-patience, see [`test_patience::test`](https://docs.rs/test-patience/*/test_patience/attr.test.html)
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, Error, ExprArray, ItemFn, LitStr, Result};
+
+/// Start an application before the test and kill it afterwards
+///
+/// Arguments:
+///
+/// - `cmd`: path of the application, which is spawned using `Server::spawn_and_wait`
+/// - `args`: arguments passed to the application, e.g. `args = ["--verbose"]`
+/// - `timeout`: period within which the application has to signal its start, e.g. `"500ms"`, `"10s"` or `"2m"` (5 seconds by default)
+///
+/// The test function can take the `&mut ChildGuard` of the application and its `Notification` as parameters.
+#[proc_macro_attribute]
+pub fn test(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut options = Options::default();
+    let parser = syn::meta::parser(|meta| options.parse(meta));
+    parse_macro_input!(attr with parser);
+    let function = parse_macro_input!(item as ItemFn);
+    match expand(options, function) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+#[derive(Default)]
+struct Options {
+    cmd: Option<LitStr>,
+    args: Option<ExprArray>,
+    timeout_ms: Option<u64>,
+}
+
+impl Options {
+    fn parse(&mut self, meta: syn::meta::ParseNestedMeta) -> Result<()> {
+        if meta.path.is_ident("cmd") {
+            self.cmd = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("args") {
+            self.args = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("timeout") {
+            let timeout: LitStr = meta.value()?.parse()?;
+            let ms = parse_duration_ms(&timeout.value()).ok_or_else(|| Error::new(timeout.span(), "invalid timeout, expected e.g. \"500ms\", \"10s\" or \"2m\""))?;
+            self.timeout_ms = Some(ms);
+        } else {
+            return Err(meta.error("unsupported argument, expected cmd, args or timeout"));
+        }
+        Ok(())
+    }
+}
+
+fn expand(options: Options, function: ItemFn) -> Result<proc_macro2::TokenStream> {
+    let cmd = options.cmd.ok_or_else(|| Error::new(Span::call_site(), "missing cmd argument"))?;
+    let args = options.args.map(|args| quote!(command.args(#args);));
+    let timeout_ms = options.timeout_ms.unwrap_or(5000);
+    let parameters = match function.sig.inputs.len() {
+        0 => quote!(),
+        1 => quote!(&mut child),
+        2 => quote!(&mut child, notification),
+        _ => return Err(Error::new_spanned(&function.sig.inputs, "expected at most the child and the notification as parameters")),
+    };
+
+    let ItemFn { attrs, vis, sig, block } = function;
+    let name = &sig.ident;
+    let output = &sig.output;
+    let mut inner = sig.clone();
+    inner.ident = syn::Ident::new("test_patience_inner", Span::call_site());
+    Ok(quote! {
+        #[test]
+        #(#attrs)*
+        #vis fn #name() #output {
+            #inner #block
+
+            let server = ::test_patience::Server::new().expect("failed to create test-patience server");
+            let mut command = ::std::process::Command::new(#cmd);
+            #args
+            let (child, notification) = server.spawn_and_wait(&mut command, ::std::time::Duration::from_millis(#timeout_ms))
+                .expect("application failed to start");
+            #[allow(unused_mut)]
+            let mut child = ::test_patience::ChildGuard::new(child);
+            let _ = &notification;
+            test_patience_inner(#parameters)
+        }
+    })
+}
+
+/// Parse a duration like `500ms`, `10s` or `2m` into milliseconds
+fn parse_duration_ms(value: &str) -> Option<u64> {
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number = number.parse::<u64>().ok()?;
+    match unit {
+        "ms" => Some(number),
+        "s" => number.checked_mul(1000),
+        "m" => number.checked_mul(60_000),
+        _ => None,
+    }
+}
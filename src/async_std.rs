@@ -0,0 +1,29 @@
+//! Asynchronous server and client for applications running on [async-std](https://async.rs)
+//!
+//! This module re-exports the runtime-agnostic [`asynchronous`](../asynchronous/index.html) implementation,
+//! which can be awaited from async-std tasks without blocking an executor thread.
+//! Applications can signal their successful start from inside their async startup path using [`Client::notify_async`](../struct.Client.html#method.notify_async).
+//!
+//! # Examples
+//!
+//! Test
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use std::process;
+//!
+//! # async fn run() {
+//! let server = test_patience::async_std::Server::new().await.unwrap();
+//! let port = server.port().unwrap();
+//!
+//! # #[allow(unused_variables)]
+//! let process = process::Command::new("path/to/application")
+//!     .env("TEST_PATIENCE_PORT", format!("{}", port))
+//!     .spawn()
+//!     .unwrap();
+//!
+//! server.wait(Duration::from_secs(5)).await.unwrap();
+//! # }
+//! ```
+
+pub use crate::asynchronous::Server;
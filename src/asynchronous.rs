@@ -0,0 +1,432 @@
+//! Runtime-agnostic asynchronous server and client
+//!
+//! The `Server` in this module behaves like the blocking [`Server`](../struct.Server.html),
+//! except that `wait` returns a future which can be awaited without blocking an executor thread.
+//! Applications can signal their successful start from inside their async startup path using [`Client::notify_async`](../struct.Client.html#method.notify_async).
+//!
+//! The implementation is based on [async-io](https://docs.rs/async-io) and works on any executor,
+//! including tokio, async-std and `futures_lite::future::block_on`.
+//!
+//! On Unix platforms, the server can also listen on a Unix domain socket instead of a TCP port (see [`Server::new_unix`](struct.Server.html#method.new_unix)),
+//! so readiness can be signaled via a socket path (e.g. mounted into a container) without exposing any port.
+//!
+//! Applications using [`Client::heartbeat`](../struct.Client.html#method.heartbeat) are supported as well, and a heartbeat connection closed without a startup notification is reported right away.
+//! The heartbeats themselves are not monitored though, an application that hangs runs into the timeout of `wait`
+//! (see [`Server::heartbeat_timeout`](../struct.Server.html#method.heartbeat_timeout) of the blocking server).
+//!
+//! # Examples
+//!
+//! Test
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use std::process;
+//!
+//! # async fn run() {
+//! let server = test_patience::asynchronous::Server::new().await.unwrap();
+//! let port = server.port().unwrap();
+//!
+//! # #[allow(unused_variables)]
+//! let process = process::Command::new("path/to/application")
+//!     .env("TEST_PATIENCE_PORT", format!("{}", port))
+//!     .spawn()
+//!     .unwrap();
+//!
+//! server.wait(Duration::from_secs(5)).await.unwrap();
+//! # }
+//! ```
+//!
+//! Application
+//!
+//! ```no_run
+//! # async fn run() {
+//! test_patience::Client::notify_async(12345).await.unwrap();
+//! # }
+//! ```
+
+use std::future::Future;
+use std::io::{Result, Error, ErrorKind};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use std::fs;
+#[cfg(target_os = "linux")]
+use std::os::linux::net::SocketAddrExt;
+#[cfg(target_os = "linux")]
+use std::os::unix::net::SocketAddr as UnixSocketAddr;
+use std::time::{Instant, Duration};
+
+use async_io::{Async, Timer};
+use futures_lite::{future, ready, stream, AsyncWriteExt, FutureExt, Stream};
+
+use crate::{Client, Notification, Registry, WaitTimeout};
+use crate::client::{authenticate, PORT_VAR, RUN_VAR, TOKEN_VAR};
+use crate::protocol::{self, Kind, Message, HEARTBEAT_LINE, RUN_HEADER, TOKEN_HEADER};
+use crate::transport::{self, heartbeat};
+use self::connection::Connection;
+
+mod connection;
+pub mod probe;
+
+impl Client {
+    /// Notify the server that the client has started successfully, without blocking the executor
+    pub async fn notify_async(port: u16) -> Result<()> {
+        let mut stream = connect_loopback(port).await?;
+        stream.write_all(&authenticate(Message::new(Kind::Ready)).encode()).await?;
+        Ok(())
+    }
+
+    /// Notify the server listening on the Unix domain socket at `path` that the client has started successfully, without blocking the executor
+    #[cfg(unix)]
+    pub async fn notify_unix_async<P: AsRef<Path>>(path: P) -> Result<()> {
+        let mut stream = Async::<UnixStream>::connect(path).await?;
+        stream.write_all(&authenticate(Message::new(Kind::Ready)).encode()).await?;
+        Ok(())
+    }
+
+    /// Notify the server listening on the abstract Unix domain socket `name` that the client has started successfully, without blocking the executor
+    #[cfg(target_os = "linux")]
+    pub async fn notify_abstract_async<N: AsRef<[u8]>>(name: N) -> Result<()> {
+        let addr = UnixSocketAddr::from_abstract_name(name)?;
+        let stream = UnixStream::connect_addr(&addr)?;
+        let mut stream = Async::new(stream)?;
+        stream.write_all(&authenticate(Message::new(Kind::Ready)).encode()).await?;
+        Ok(())
+    }
+}
+
+enum Listener {
+    Tcp(Async<TcpListener>),
+    /// Unix domain socket, with the path of its socket file unless it's bound in the abstract namespace
+    #[cfg(unix)]
+    Unix(Async<UnixListener>, Option<PathBuf>),
+}
+
+impl Listener {
+    /// Poll for the next incoming connection
+    fn poll_accept(&self, cx: &mut Context<'_>) -> Poll<Result<Connection>> {
+        match *self {
+            Listener::Tcp(ref listener) => {
+                poll_accept(listener, cx, |listener| Ok(Connection::new(Async::new(listener.accept()?.0)?)))
+            }
+            #[cfg(unix)]
+            Listener::Unix(ref listener, _) => {
+                poll_accept(listener, cx, |listener| Ok(Connection::new(Async::new(listener.accept()?.0)?)))
+            }
+        }
+    }
+}
+
+/// Entry point for the test, waiting for the application to start
+pub struct Server {
+    listener: Listener,
+    /// Accepted connections which haven't been closed by the application yet
+    connections: Mutex<Vec<Connection>>,
+    /// Number of connections accepted since the server was created
+    accepted: AtomicUsize,
+    registry: Registry,
+    token: String,
+    require_token: bool,
+    run_id: String,
+}
+
+impl Server {
+    fn with_listener(listener: Listener) -> Server {
+        Server {
+            listener,
+            connections: Mutex::new(Vec::new()),
+            accepted: AtomicUsize::new(0),
+            registry: Registry::default(),
+            token: protocol::generate_token(),
+            require_token: false,
+            run_id: protocol::generate_token(),
+        }
+    }
+
+    /// Start new TCP server, waiting for the application's startup notification
+    pub async fn new() -> Result<Server> {
+        Ok(Server::with_listener(Listener::Tcp(Async::new(transport::bind_loopback(0, TcpListener::bind)?)?)))
+    }
+
+    /// Start new server listening on the Unix domain socket at `path`, waiting for the application's startup notification
+    ///
+    /// The socket file is removed when the server is dropped.
+    #[cfg(unix)]
+    pub async fn new_unix<P: AsRef<Path>>(path: P) -> Result<Server> {
+        let path = path.as_ref().to_path_buf();
+        Ok(Server::with_listener(Listener::Unix(Async::<UnixListener>::bind(&path)?, Some(path))))
+    }
+
+    /// Start new server listening on the abstract Unix domain socket `name`, waiting for the application's startup notification
+    ///
+    /// See [`Server::new_abstract`](../struct.Server.html#method.new_abstract) of the blocking server.
+    #[cfg(target_os = "linux")]
+    pub async fn new_abstract<N: AsRef<[u8]>>(name: N) -> Result<Server> {
+        let addr = UnixSocketAddr::from_abstract_name(name)?;
+        Ok(Server::with_listener(Listener::Unix(Async::new(UnixListener::bind_addr(&addr)?)?, None)))
+    }
+
+    /// Get the port number of the TCP Server
+    ///
+    /// This port number has to sent to the application.
+    /// Returns an error if the server listens on a Unix domain socket.
+    pub fn port(&self) -> Result<u16> {
+        match self.listener {
+            Listener::Tcp(ref listener) => Ok(listener.get_ref().local_addr()?.port()),
+            #[cfg(unix)]
+            Listener::Unix(..) => Err(Error::new(ErrorKind::InvalidInput, "server does not listen on a TCP port")),
+        }
+    }
+
+    /// Get the address of the TCP server, or `None` if it listens on a Unix domain socket
+    fn local_addr(&self) -> Option<SocketAddr> {
+        match self.listener {
+            Listener::Tcp(ref listener) => listener.get_ref().local_addr().ok(),
+            #[cfg(unix)]
+            Listener::Unix(..) => None,
+        }
+    }
+
+    /// Get the random token generated for this server
+    ///
+    /// See [`Server::token`](../struct.Server.html#method.token) of the blocking server.
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// Only accept notifications carrying the token of this server
+    ///
+    /// See [`Server::require_token`](../struct.Server.html#method.require_token) of the blocking server.
+    pub fn require_token(mut self, require: bool) -> Server {
+        self.require_token = require;
+        self
+    }
+
+    /// Get the random run ID of this server
+    ///
+    /// See [`Server::run_id`](../struct.Server.html#method.run_id) of the blocking server.
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// Get the environment variables telling the application how to notify this server
+    ///
+    /// These are the `TEST_PATIENCE_PORT` variable of TCP servers and the `TEST_PATIENCE_TOKEN` and `TEST_PATIENCE_RUN` variables,
+    /// see [`Server::env`](../struct.Server.html#method.env) of the blocking server.
+    pub fn env(&self) -> Vec<(String, String)> {
+        let mut env = Vec::new();
+        if let Ok(port) = self.port() {
+            env.push((PORT_VAR.to_string(), format!("{}", port)));
+        }
+        env.push((TOKEN_VAR.to_string(), self.token.clone()));
+        env.push((RUN_VAR.to_string(), self.run_id.clone()));
+        env
+    }
+
+    /// Get the names of the services which have signaled their start so far, in the order their notifications were received
+    ///
+    /// Only notifications sent using [`Client::notify_named`](../struct.Client.html#method.notify_named) carry a name.
+    /// This is mostly useful together with [`incoming`](#method.incoming), when several applications report to the same server.
+    pub fn reported(&self) -> Vec<String> {
+        self.registry.ready_services()
+    }
+
+    /// Get a handle to the notifications received by this server
+    ///
+    /// See [`Server::registry`](../struct.Server.html#method.registry) of the blocking server.
+    pub fn registry(&self) -> Registry {
+        self.registry.clone()
+    }
+
+    /// Wait until the starting application has signaled its successful start, without any timeout
+    ///
+    /// The returned future resolves to the notification, carrying the duration for which was waited (measured from the call of this method), or an error in case of an invalid startup notification.
+    /// It is a plain future, so it composes with `tokio::time::timeout`, `tokio::select!` or any other combinator.
+    ///
+    /// ```no_run
+    /// # async fn run() {
+    /// # let server = test_patience::asynchronous::Server::new().await.unwrap();
+    /// # async fn log_watcher() {}
+    /// tokio::select! {
+    ///     result = server.notified() => println!("ready after {:?}", result.unwrap().elapsed()),
+    ///     _ = log_watcher() => panic!("application logged an error"),
+    /// }
+    /// # }
+    /// ```
+    pub fn notified(self) -> impl Future<Output = Result<Notification>> {
+        let start = Instant::now();
+        async move { self.receive_notification(start).await }
+    }
+
+    /// Receive the startup notifications of several applications reporting to the same server
+    ///
+    /// Every accepted connection yields either a notification, carrying the duration since the call of this method, or an error in case of an invalid startup notification.
+    /// Connections are read concurrently, so a slow application doesn't hold back the notifications of the others.
+    /// The stream never ends by itself.
+    ///
+    /// ```no_run
+    /// use futures_lite::StreamExt;
+    ///
+    /// # async fn run() {
+    /// # let server = test_patience::asynchronous::Server::new().await.unwrap();
+    /// let mut incoming = server.incoming();
+    /// while let Some(notification) = incoming.next().await {
+    ///     println!("application ready after {:?}", notification.unwrap().elapsed());
+    /// }
+    /// # }
+    /// ```
+    pub fn incoming(&self) -> impl Stream<Item = Result<Notification>> + Unpin + '_ {
+        let start = Instant::now();
+        Box::pin(stream::unfold(self, move |server| async move {
+            let notification = server.receive_notification(start).await;
+            Some((notification, server))
+        }))
+    }
+
+    /// Wait until either the starting application has signaled its successful start or the `timeout` period has expired
+    ///
+    /// Returns the received notification or an error in case of a timeout or invalid startup notification.
+    /// In case of a timeout, the error wraps a [`WaitTimeout`](../struct.WaitTimeout.html), like the one of the blocking server.
+    /// Progress updates sent using [`Client::progress`](../struct.Client.html#method.progress) are skipped and don't extend the timeout.
+    pub async fn wait(self, timeout: Duration) -> Result<Notification> {
+        let start = Instant::now();
+        let timed_out = async {
+            Timer::after(timeout).await;
+            let timeout = WaitTimeout::new(timeout, start.elapsed(), self.local_addr(), self.accepted.load(Ordering::Relaxed), None);
+            Err(Error::new(ErrorKind::TimedOut, timeout))
+        };
+        self.receive_notification(start).or(timed_out).await
+    }
+
+    /// Like [`wait`](#method.wait), but abort with an error of kind `Interrupted` as soon as the `cancel` future completes
+    ///
+    /// Any future can be used as cancellation signal, e.g. `CancellationToken::cancelled` of tokio-util
+    /// or the receiving end of a channel, which is useful when another service in the test already failed and there is no point waiting further.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// # async fn run() {
+    /// # let server = test_patience::asynchronous::Server::new().await.unwrap();
+    /// let (cancel, cancelled) = tokio::sync::oneshot::channel::<()>();
+    /// # drop(cancel);
+    /// server.wait_cancellable(Duration::from_secs(5), async { let _ = cancelled.await; }).await.unwrap();
+    /// # }
+    /// ```
+    pub async fn wait_cancellable<F>(self, timeout: Duration, cancel: F) -> Result<Notification>
+        where F: Future<Output = ()>
+    {
+        let cancel = async {
+            cancel.await;
+            Err(Error::new(ErrorKind::Interrupted, "waiting for startup notification was cancelled"))
+        };
+        self.wait(timeout).or(cancel).await
+    }
+
+    /// Accept connections and read from all of them concurrently, until the first message is complete
+    ///
+    /// Framed messages are complete as soon as all of their data has arrived, unframed ones once their connection is closed.
+    async fn receive(&self) -> Result<Vec<u8>> {
+        future::poll_fn(|cx| {
+            let mut connections = self.connections.lock().unwrap_or_else(|e| e.into_inner());
+            while let Poll::Ready(connection) = self.listener.poll_accept(cx) {
+                connections.push(connection?);
+                self.accepted.fetch_add(1, Ordering::Relaxed);
+            }
+            let mut i = 0;
+            while i < connections.len() {
+                let closed = match connections[i].poll_read(cx) {
+                    Poll::Ready(Ok(())) => true,
+                    Poll::Ready(Err(e)) => {
+                        connections.swap_remove(i);
+                        return Poll::Ready(Err(e));
+                    }
+                    Poll::Pending => false,
+                };
+                let message = connections[i].take_message(closed);
+                if closed && connections[i].is_empty() {
+                    connections.swap_remove(i);
+                } else {
+                    i += 1;
+                }
+                if let Some(message) = message {
+                    return Poll::Ready(Ok(message));
+                }
+            }
+            Poll::Pending
+        }).await
+    }
+
+    /// Receive the next notification, skipping progress updates, messages of another run and, if required, messages without the correct token
+    ///
+    /// Heartbeat connections are handled once they are closed, the heartbeats themselves are not monitored.
+    async fn receive_notification(&self, start: Instant) -> Result<Notification> {
+        loop {
+            let mut buf = self.receive().await?;
+            if buf.starts_with(HEARTBEAT_LINE) {
+                let (headers, message) = heartbeat::split(buf);
+                if !self.is_authentic(protocol::header(&headers, TOKEN_HEADER), protocol::header(&headers, RUN_HEADER)) {
+                    continue;
+                }
+                if message.is_empty() {
+                    return Err(heartbeat::closed_early());
+                }
+                buf = message;
+            }
+            let message = match Message::decode(&buf) {
+                Ok(ref message) if !self.is_authentic(message.get(TOKEN_HEADER), message.get(RUN_HEADER)) => continue,
+                Ok(message) => message,
+                Err(_) if self.require_token => continue,
+                Err(e) => return Err(e),
+            };
+            if message.kind != Kind::Progress {
+                let notification = Notification::from_message(message, start.elapsed())?;
+                self.registry.record(&notification);
+                return Ok(notification);
+            }
+        }
+    }
+
+    /// Check the `token` of a message, if required, and that its `run` ID, if any, is the one of this server
+    fn is_authentic(&self, token: Option<&str>, run: Option<&str>) -> bool {
+        (!self.require_token || token == Some(self.token.as_str())) && run.is_none_or(|run| run == self.run_id)
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Server {
+    fn drop(&mut self) {
+        if let Listener::Unix(_, Some(ref path)) = self.listener {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Connect to `port` on the loopback interface, trying IPv4 first like the blocking client
+async fn connect_loopback(port: u16) -> Result<Async<TcpStream>> {
+    let [ipv4, ipv6] = transport::loopback(port);
+    match Async::<TcpStream>::connect(ipv4).await {
+        Ok(stream) => Ok(stream),
+        Err(_) => Async::<TcpStream>::connect(ipv6).await,
+    }
+}
+
+/// Poll the non-blocking `listener` until `accept` returns a connection
+fn poll_accept<L, F>(listener: &Async<L>, cx: &mut Context<'_>, accept: F) -> Poll<Result<Connection>>
+    where F: Fn(&L) -> Result<Connection>
+{
+    loop {
+        ready!(listener.poll_readable(cx))?;
+        match accept(listener.get_ref()) {
+            Ok(connection) => return Poll::Ready(Ok(connection)),
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(e) => return Poll::Ready(Err(e)),
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! Accepted connections, which are read concurrently
+
+use std::io::{Result, ErrorKind};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_lite::AsyncRead;
+
+use crate::protocol;
+
+/// Stream of an accepted connection, buffering the data sent by the application until it closes the connection
+pub struct Connection {
+    stream: Box<dyn AsyncRead + Unpin + Send>,
+    buf: Vec<u8>,
+    framed: bool,
+}
+
+impl Connection {
+    pub fn new<S: AsyncRead + Unpin + Send + 'static>(stream: S) -> Connection {
+        Connection { stream: Box::new(stream), buf: Vec::new(), framed: false }
+    }
+
+    /// Read the data available without blocking
+    ///
+    /// Resolves once the application has closed the connection,
+    /// or with an error of kind `InvalidData` as soon as the message being received exceeds `protocol::MAX_MESSAGE_SIZE`.
+    pub fn poll_read(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let mut chunk = [0; 1024];
+        loop {
+            match Pin::new(&mut *self.stream).poll_read(cx, &mut chunk) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Ok(())),
+                Poll::Ready(Ok(len)) => {
+                    self.buf.extend_from_slice(&chunk[..len]);
+                    if let Err(e) = protocol::check_size(&self.buf, protocol::MAX_MESSAGE_SIZE) {
+                        return Poll::Ready(Err(e));
+                    }
+                }
+                Poll::Ready(Err(ref e)) if e.kind() == ErrorKind::Interrupted => {}
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    /// Check whether all data received so far has been taken
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Take the next complete message out of the data received so far, see `protocol::take_message`
+    pub fn take_message(&mut self, closed: bool) -> Option<Vec<u8>> {
+        protocol::take_message(&mut self.buf, &mut self.framed, closed)
+    }
+}
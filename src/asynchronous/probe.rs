@@ -0,0 +1,150 @@
+//! Asynchronous readiness probes for applications that can't send a startup notification
+//!
+//! A probe repeatedly checks a condition (e.g. whether a port accepts connections) until it holds or a timeout has expired.
+//! Probes don't block the executor, so several of them can run concurrently inside one test without spawning a thread per probe.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use futures_lite::future;
+//! use test_patience::asynchronous::probe::{self, Probe};
+//!
+//! # async fn run() {
+//! let (db, cache) = future::zip(
+//!     probe::tcp("127.0.0.1:5432").wait(Duration::from_secs(30)),
+//!     probe::tcp("127.0.0.1:6379").wait(Duration::from_secs(5)),
+//! ).await;
+//! db.unwrap();
+//! cache.unwrap();
+//! # }
+//! ```
+
+use std::future::Future;
+use std::io::{Result, Error, ErrorKind};
+use std::net::{TcpStream, SocketAddr, ToSocketAddrs};
+use std::pin::Pin;
+use std::time::{Instant, Duration};
+
+use async_io::{Async, Timer};
+use futures_lite::FutureExt;
+
+pub use crate::probe::Backoff;
+
+/// Future returned by [`Probe::check`](trait.Probe.html#tymethod.check)
+pub type Check<'a> = Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>;
+
+/// Condition that is checked repeatedly until it holds
+pub trait Probe {
+    /// Check once whether the condition holds
+    ///
+    /// An error means that the condition does not hold (yet). The last error is reported if the probe times out.
+    fn check(&mut self) -> Check<'_>;
+
+    /// Get the duration to sleep between two checks
+    fn check_interval(&self) -> Duration {
+        Duration::from_millis(10)
+    }
+
+    /// Get the policy for the delays between the checks, a fixed `check_interval` by default
+    fn backoff(&self) -> Backoff {
+        Backoff::fixed(self.check_interval())
+    }
+
+    /// Check the condition until it holds or the `timeout` period has expired
+    ///
+    /// Returns the duration for which was waited or an error in case of a timeout.
+    fn wait<'a>(mut self, timeout: Duration) -> Pin<Box<dyn Future<Output = Result<Duration>> + Send + 'a>>
+        where Self: Sized + Send + 'a
+    {
+        Box::pin(async move {
+            let start = Instant::now();
+            let mut delays = self.backoff().delays();
+            loop {
+                let remaining = timeout.checked_sub(start.elapsed()).unwrap_or_default();
+                let deadline = async {
+                    Timer::after(remaining).await;
+                    Err(Error::new(ErrorKind::TimedOut, "check did not finish in time"))
+                };
+                let error = match self.check().or(deadline).await {
+                    Ok(()) => return Ok(start.elapsed()),
+                    Err(e) => e,
+                };
+                if start.elapsed() >= timeout {
+                    return Err(Error::new(ErrorKind::TimedOut, format!("probe did not succeed ({})", error)));
+                }
+                Timer::after(delays.next().unwrap_or_default()).await;
+            }
+        })
+    }
+
+    /// Replace the delays between the checks by the ones of the `backoff` policy
+    fn with_backoff(self, backoff: Backoff) -> WithBackoff<Self>
+        where Self: Sized
+    {
+        WithBackoff { probe: self, backoff }
+    }
+}
+
+/// Probe created by [`Probe::with_backoff`](trait.Probe.html#method.with_backoff)
+#[derive(Debug)]
+pub struct WithBackoff<P> {
+    probe: P,
+    backoff: Backoff,
+}
+
+impl<P: Probe> Probe for WithBackoff<P> {
+    fn check(&mut self) -> Check<'_> {
+        self.probe.check()
+    }
+
+    fn backoff(&self) -> Backoff {
+        self.backoff
+    }
+}
+
+/// Probe created by [`tcp`](fn.tcp.html)
+#[derive(Debug)]
+pub struct Tcp {
+    addrs: Result<Vec<SocketAddr>>,
+    interval: Duration,
+}
+
+/// Create a probe which succeeds as soon as the address accepts TCP connections
+pub fn tcp<A: ToSocketAddrs>(addr: A) -> Tcp {
+    Tcp {
+        addrs: addr.to_socket_addrs().map(Iterator::collect),
+        interval: Duration::from_millis(10),
+    }
+}
+
+impl Tcp {
+    /// Set the duration to sleep between two connection attempts
+    pub fn interval(mut self, interval: Duration) -> Tcp {
+        self.interval = interval;
+        self
+    }
+}
+
+impl Probe for Tcp {
+    fn check(&mut self) -> Check<'_> {
+        Box::pin(async move {
+            let addrs = match self.addrs {
+                Ok(ref addrs) => addrs,
+                Err(ref e) => return Err(Error::new(e.kind(), e.to_string())),
+            };
+            let mut error = Error::new(ErrorKind::InvalidInput, "could not resolve to any address");
+            for &addr in addrs {
+                match Async::<TcpStream>::connect(addr).await {
+                    Ok(_) => return Ok(()),
+                    Err(e) => error = e,
+                }
+            }
+            Err(error)
+        })
+    }
+
+    fn check_interval(&self) -> Duration {
+        self.interval
+    }
+}
@@ -0,0 +1,258 @@
+//! Command line interface of test-patience, for applications and test suites which aren't written in Rust
+//!
+//! Exits with status 0 on success, 1 if the command failed and 2 if it was used incorrectly.
+
+use std::env;
+use std::fs;
+use std::io::{self, Result, Error, ErrorKind, Write};
+use std::process::{self, Command};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use test_patience::{Client, PatienceCommandExt, Server};
+use test_patience::probe::{self, Probe};
+
+const USAGE: &str = "\
+usage: test-patience <command> [options]
+
+commands:
+  notify [--port PORT] [--name NAME | --payload DATA | --failure REASON]
+      Signal the start of the application to the test waiting on PORT, which defaults to $TEST_PATIENCE_PORT.
+      The token passed in $TEST_PATIENCE_TOKEN is sent along.
+  wait [--timeout DURATION] [--export] [--port-file PATH]
+      Start a server, print its port and wait until an application signals its start, 5s by default.
+      With --export the port is printed as `export TEST_PATIENCE_PORT=PORT`, with --port-file it's written to PATH instead.
+  wait-for TARGET... [--timeout DURATION] [--interval DURATION]
+      Wait until all targets are ready, 5s by default, checking every 100ms. Targets are given as
+      tcp://HOST:PORT (accepts connections), http://HOST[:PORT]/PATH (returns status 200),
+      file://PATH (exists) or unix://PATH (Unix domain socket accepts connections).
+  exec [--timeout DURATION] [--detach] -- COMMAND [ARGUMENT...]
+      Run COMMAND with $TEST_PATIENCE_PORT set and wait until it signals its start, 5s by default.
+      Afterwards keep running until COMMAND exits and exit with its status, or exit right away with --detach.
+      COMMAND is killed if it doesn't signal its start in time.
+
+durations are given like 500ms, 30s or 2m";
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let result = match args.next().as_deref() {
+        Some("notify") => Options::parse(args).and_then(notify),
+        Some("wait") => Options::parse(args).and_then(wait),
+        Some("wait-for") => Options::parse(args).and_then(wait_for),
+        Some("exec") => Options::parse(args).and_then(exec),
+        Some("-h") | Some("--help") => {
+            println!("{}", USAGE);
+            return;
+        }
+        Some(command) => Err(usage(format!("unknown command {}", command))),
+        None => Err(usage("missing command")),
+    };
+    if let Err(e) = result {
+        eprintln!("test-patience: {}", e);
+        if e.kind() == ErrorKind::InvalidInput {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+        process::exit(1);
+    }
+}
+
+/// Options which don't take a value
+const FLAGS: &[&str] = &["export", "detach"];
+
+/// Options of a command, given as `--name value` or `--name=value`, and its other arguments, which include everything after `--`
+struct Options {
+    values: Vec<(String, String)>,
+    arguments: Vec<String>,
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Options> {
+        let mut values = Vec::new();
+        let mut arguments = Vec::new();
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                arguments.extend(args.by_ref());
+                break;
+            }
+            let name = match arg.strip_prefix("--") {
+                Some(name) => name,
+                None => {
+                    arguments.push(arg);
+                    continue;
+                }
+            };
+            let (name, value) = match name.find('=') {
+                Some(i) => (name[..i].to_string(), name[i + 1..].to_string()),
+                None if FLAGS.contains(&name) => (name.to_string(), String::new()),
+                None => {
+                    let value = args.next().ok_or_else(|| usage(format!("missing value of --{}", name)))?;
+                    (name.to_string(), value)
+                }
+            };
+            values.push((name, value));
+        }
+        Ok(Options { values, arguments })
+    }
+
+    /// Remove the option `name` and return its value
+    fn take(&mut self, name: &str) -> Option<String> {
+        let i = self.values.iter().position(|(other, _)| other == name)?;
+        Some(self.values.remove(i).1)
+    }
+
+    /// Remove the flag `name` and return whether it was given
+    fn flag(&mut self, name: &str) -> bool {
+        self.take(name).is_some()
+    }
+
+    /// Remove the option `name` and parse its value as a duration
+    fn duration(&mut self, name: &str) -> Result<Option<Duration>> {
+        self.take(name).map(|value| parse_duration(&value)).transpose()
+    }
+
+    /// Fail if there are options which haven't been taken, otherwise return the other arguments
+    fn finish(self) -> Result<Vec<String>> {
+        match self.values.first() {
+            Some((name, _)) => Err(usage(format!("unknown option --{}", name))),
+            None => Ok(self.arguments),
+        }
+    }
+}
+
+/// Send the startup notification to the waiting test
+fn notify(mut options: Options) -> Result<()> {
+    let port = match options.take("port") {
+        Some(port) => port,
+        None => env::var("TEST_PATIENCE_PORT").map_err(|_| usage("missing --port and TEST_PATIENCE_PORT is not set"))?,
+    };
+    let port = port.parse::<u16>().map_err(|_| usage(format!("invalid port {}", port)))?;
+    let name = options.take("name");
+    let payload = options.take("payload");
+    let failure = options.take("failure");
+    no_arguments(options.finish()?)?;
+    match (name, payload, failure) {
+        (None, None, None) => Client::notify(port),
+        (Some(name), None, None) => Client::notify_named(port, &name),
+        (None, Some(payload), None) => Client::notify_with_payload(port, payload.as_bytes()),
+        (None, None, Some(reason)) => Client::notify_failure(port, &reason),
+        _ => Err(usage("only one of --name, --payload and --failure can be given")),
+    }
+}
+
+/// Wait for the startup notification of an application, which is told about the port by the calling script
+fn wait(mut options: Options) -> Result<()> {
+    let timeout = options.duration("timeout")?.unwrap_or(Duration::from_secs(5));
+    let export = options.flag("export");
+    let port_file = options.take("port-file");
+    no_arguments(options.finish()?)?;
+
+    let server = Server::new()?;
+    let port = server.port()?;
+    match port_file {
+        // the port is written to a temporary file first, so scripts polling for the file never read it half-written
+        Some(path) => {
+            let tmp = format!("{}.tmp", path);
+            fs::write(&tmp, format!("{}\n", port))?;
+            fs::rename(&tmp, &path)?;
+        }
+        None if export => println!("export TEST_PATIENCE_PORT={}", port),
+        None => println!("{}", port),
+    }
+    io::stdout().flush()?;
+    server.wait(timeout).map(drop)
+}
+
+/// Wait until all targets given as arguments are ready, replacing scripts like wait-for-it.sh
+fn wait_for(mut options: Options) -> Result<()> {
+    let timeout = options.duration("timeout")?.unwrap_or(Duration::from_secs(5));
+    let interval = options.duration("interval")?.unwrap_or(Duration::from_millis(100));
+    let targets = options.finish()?;
+    if targets.is_empty() {
+        return Err(usage("missing target"));
+    }
+    let probes = targets.iter().map(|target| target_probe(target)).collect::<Result<Vec<_>>>()?;
+    probe::all(probes).interval(interval).wait(timeout).map(drop)
+}
+
+/// Create the probe for a target of `wait-for`
+fn target_probe(target: &str) -> Result<Box<dyn Probe>> {
+    let (scheme, rest) = match target.find("://") {
+        Some(i) => (&target[..i], &target[i + 3..]),
+        None => return Err(usage(format!("target {} has no scheme", target))),
+    };
+    match scheme {
+        "tcp" => Ok(Box::new(probe::tcp(rest))),
+        "http" => Ok(Box::new(probe::http(target))),
+        "file" => Ok(Box::new(probe::file(rest))),
+        #[cfg(unix)]
+        "unix" => Ok(Box::new(probe::unix(rest))),
+        _ => Err(usage(format!("unsupported target {}", target))),
+    }
+}
+
+/// Run the command given as arguments and wait for its start, so process supervisors can gate follow-up steps on it
+fn exec(mut options: Options) -> Result<()> {
+    let timeout = options.duration("timeout")?.unwrap_or(Duration::from_secs(5));
+    let detach = options.flag("detach");
+    let arguments = options.finish()?;
+    let (program, arguments) = arguments.split_first().ok_or_else(|| usage("missing command"))?;
+
+    let server = Server::new()?;
+    // unlike `Server::spawn`, this keeps the child in the process group of the tool, so it receives the signals sent to the tool by a terminal or supervisor
+    let mut child = Command::new(program).args(arguments).notify_via(&server)?.spawn()?;
+    let (sender, notified) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = sender.send(server.wait(timeout).map(drop));
+    });
+    let result = loop {
+        match notified.recv_timeout(Duration::from_millis(10)) {
+            Ok(result) => break result,
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break Err(Error::other("server stopped waiting")),
+        }
+        if let Some(status) = child.try_wait()? {
+            // the application may have notified right before exiting
+            break notified.recv_timeout(Duration::from_millis(100))
+                .unwrap_or_else(|_| Err(Error::other(format!("application exited before signaling its start ({})", status))));
+        }
+    };
+    if let Err(e) = result {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err(e);
+    }
+    if detach {
+        return Ok(());
+    }
+    let status = child.wait()?;
+    process::exit(status.code().unwrap_or(1));
+}
+
+/// Fail if a command which doesn't take any arguments was given some
+fn no_arguments(arguments: Vec<String>) -> Result<()> {
+    match arguments.first() {
+        Some(argument) => Err(usage(format!("unexpected argument {}", argument))),
+        None => Ok(()),
+    }
+}
+
+/// Parse a duration like `500ms`, `30s` or `2m`, where a plain number is taken as seconds
+fn parse_duration(value: &str) -> Result<Duration> {
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let invalid = || usage(format!("invalid duration {}", value));
+    let number = number.parse::<u64>().map_err(|_| invalid())?;
+    match unit {
+        "ms" => Ok(Duration::from_millis(number)),
+        "" | "s" => Ok(Duration::from_secs(number)),
+        "m" => Ok(Duration::from_secs(number.checked_mul(60).ok_or_else(invalid)?)),
+        _ => Err(invalid()),
+    }
+}
+
+/// Error for an incorrect use of the command line interface
+fn usage<E: Into<String>>(message: E) -> Error {
+    Error::new(ErrorKind::InvalidInput, message.into())
+}
@@ -0,0 +1,512 @@
+use std::env;
+use std::fs;
+use std::io::{Result, Error, ErrorKind};
+use std::io::prelude::*;
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+#[cfg(target_os = "linux")]
+use std::os::linux::net::SocketAddrExt;
+#[cfg(target_os = "linux")]
+use std::os::unix::net::SocketAddr as UnixSocketAddr;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::panic;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+#[cfg(feature = "serde")]
+use crate::{Handshake, Metadata};
+use crate::protocol::{self, Headers, Kind, Message, BARRIER_LINE, EXPECT_HEADER, HEARTBEAT_LINE, NAME_HEADER, PING, RELEASE, RUN_HEADER, SENT_HEADER, TOKEN_HEADER};
+use crate::transport;
+
+/// Environment variable used to pass the port number to the application
+pub(crate) const PORT_VAR: &str = "TEST_PATIENCE_PORT";
+/// Environment variable used to pass the token of the server to the application
+pub(crate) const TOKEN_VAR: &str = "TEST_PATIENCE_TOKEN";
+/// Environment variable used to pass the run ID of the server to the application
+pub(crate) const RUN_VAR: &str = "TEST_PATIENCE_RUN";
+/// Environment variable asking the application to timestamp its notifications, see `Server::measure_overhead`
+pub(crate) const TIMESTAMP_VAR: &str = "TEST_PATIENCE_TIMESTAMP";
+
+/// Whether the panic hook installed by `Client::install_panic_hook` still has to report panics
+static PANIC_HOOK_ARMED: AtomicBool = AtomicBool::new(false);
+
+/// Entry point for the application that needs to be synchronized
+pub struct Client;
+
+impl Client {
+    /// Notify the server that the client has started successfully
+    ///
+    /// If the `TEST_PATIENCE_TOKEN` environment variable is set, its value is sent along with the notification (see `Server::require_token`).
+    /// The same applies to all other notifications sent over sockets or pipes.
+    pub fn notify(port: u16) -> Result<()> {
+        Client::send(port, &authenticate(Message::new(Kind::Ready)))
+    }
+
+    /// Notify the server whose port is passed in the `TEST_PATIENCE_PORT` environment variable that the client has started successfully
+    ///
+    /// Does nothing if the variable isn't set, e.g. because the application wasn't started by a test.
+    /// Returns an error of kind `InvalidInput` if the variable doesn't contain a port number.
+    ///
+    /// ```no_run
+    /// // initialize application (eg. connect to database server)
+    /// test_patience::Client::notify_from_env().unwrap();
+    /// ```
+    pub fn notify_from_env() -> Result<()> {
+        Client::notify_from_env_var(PORT_VAR)
+    }
+
+    /// Notify the server whose port is passed in the environment variable `var` that the client has started successfully
+    ///
+    /// Like `notify_from_env`, this does nothing if the variable isn't set.
+    /// Servers pass the port number in a custom variable using `Server::port_var`, e.g. to keep nested layers of tests apart.
+    pub fn notify_from_env_var(var: &str) -> Result<()> {
+        let value = match env::var(var) {
+            Ok(value) => value,
+            Err(env::VarError::NotPresent) => return Ok(()),
+            Err(env::VarError::NotUnicode(_)) => return Err(Error::new(ErrorKind::InvalidInput, format!("{} is not a port number", var))),
+        };
+        let port = value.parse::<u16>()
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, format!("{} is not a port number: {}", var, value)))?;
+        Client::notify(port)
+    }
+
+    /// Notify the server that the client has started successfully, authenticating the notification using the `token` of the server
+    ///
+    /// This is only needed if the token isn't passed using the `TEST_PATIENCE_TOKEN` environment variable.
+    pub fn notify_with_token(port: u16, token: &str) -> Result<()> {
+        Client::send(port, &Message::new(Kind::Ready).header(TOKEN_HEADER, token))
+    }
+
+    /// Notify the server that the client has started successfully, identifying the service by its `name`
+    ///
+    /// This tells apart several applications reporting to the same server, see `Notification::name`.
+    /// The name must not contain line breaks.
+    pub fn notify_named(port: u16, name: &str) -> Result<()> {
+        if name.contains('\n') {
+            return Err(Error::new(ErrorKind::InvalidInput, "service name must not contain line breaks"));
+        }
+        Client::send(port, &authenticate(Message::new(Kind::Ready).header(NAME_HEADER, name)))
+    }
+
+    /// Notify the server that the client has started successfully, announcing that `more` applications will report to the same server
+    ///
+    /// This raises the number of notifications `Server::wait_for_n` waits for, e.g. for an application forking a number of workers only known at runtime.
+    pub fn notify_expecting(port: u16, more: usize) -> Result<()> {
+        Client::send(port, &authenticate(Message::new(Kind::Ready).header(EXPECT_HEADER, more.to_string())))
+    }
+
+    /// Notify the server that the client has started successfully, handing back an opaque `payload`
+    ///
+    /// `Server::wait` returns the payload as part of the `Notification` (see `Notification::payload`),
+    /// e.g. so an application which chooses its own listening port during startup can tell the test where to connect.
+    ///
+    /// ```no_run
+    /// let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    /// let addr = listener.local_addr().unwrap().to_string();
+    ///
+    /// # let port = 12345;
+    /// test_patience::Client::notify_with_payload(port, addr.as_bytes()).unwrap();
+    /// ```
+    pub fn notify_with_payload(port: u16, payload: &[u8]) -> Result<()> {
+        Client::send(port, &authenticate(Message::new(Kind::Ready).body(payload)))
+    }
+
+    /// Notify the server that the client has started successfully, sending structured `metadata` along with the notification
+    ///
+    /// The metadata is encoded as JSON and returned by `Server::wait` as part of the `Notification`.
+    ///
+    /// ```no_run
+    /// let mut metadata = test_patience::Metadata::default();
+    /// metadata.service = Some("db-proxy".to_string());
+    /// metadata.version = Some(env!("CARGO_PKG_VERSION").to_string());
+    /// metadata.pid = Some(std::process::id());
+    /// metadata.properties.insert("listen".to_string(), "127.0.0.1:8080".to_string());
+    ///
+    /// # let port = 12345;
+    /// test_patience::Client::notify_with_metadata(port, &metadata).unwrap();
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn notify_with_metadata(port: u16, metadata: &Metadata) -> Result<()> {
+        Client::send(port, &authenticate(Message::new(Kind::Ready).with_metadata(metadata)?))
+    }
+
+    /// Notify the server that the client has failed to start
+    ///
+    /// `Server::wait` returns immediately with a [`StartupFailure`](struct.StartupFailure.html) carrying the `reason`,
+    /// so the test fails fast with the application's actual error message instead of running into its timeout.
+    pub fn notify_failure(port: u16, reason: &str) -> Result<()> {
+        Client::send(port, &authenticate(Message::new(Kind::Failure).body(reason)))
+    }
+
+    /// Tell the server that the client is still starting, sending a progress update `message`
+    ///
+    /// If the server was configured using `Server::reset_timeout_on_progress`, every update restarts its timeout.
+    pub fn progress(port: u16, message: &str) -> Result<()> {
+        Client::send(port, &authenticate(Message::new(Kind::Progress).body(message)))
+    }
+
+    /// Open a heartbeat connection to the server, which pings it every `interval` until the client signals its start
+    ///
+    /// The returned `Heartbeat` sends the final notification over the same connection.
+    /// If it is dropped without notifying, e.g. because the application died, the server reports this right away.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// # let port = 12345;
+    /// let heartbeat = test_patience::Client::heartbeat(port, Duration::from_millis(100)).unwrap();
+    /// // long running initialization (eg. rebuild search index)
+    /// heartbeat.notify().unwrap();
+    /// ```
+    pub fn heartbeat(port: u16, interval: Duration) -> Result<Heartbeat> {
+        let mut stream = TcpStream::connect(&transport::loopback(port)[..])?;
+        let mut preamble = HEARTBEAT_LINE.to_vec();
+        let headers = credentials();
+        if !headers.is_empty() {
+            preamble.extend_from_slice(&protocol::encode_headers(&headers));
+        }
+        stream.write_all(&preamble)?;
+
+        let mut pinger = stream.try_clone()?;
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                if pinger.write_all(PING).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Heartbeat { stream, stop: Some(stop), thread: Some(thread) })
+    }
+
+    /// Arrive at the startup barrier of the server and block until the test releases all applications together
+    ///
+    /// See `Server::barrier`. Returns an error of kind `ConnectionAborted` if the server gives up waiting for the other applications.
+    pub fn wait_for_release(port: u16) -> Result<()> {
+        let mut stream = TcpStream::connect(&transport::loopback(port)[..])?;
+        let mut preamble = BARRIER_LINE.to_vec();
+        preamble.extend_from_slice(&protocol::encode_headers(&credentials()));
+        stream.write_all(&preamble)?;
+
+        let mut answer = [0; RELEASE.len()];
+        match stream.read_exact(&mut answer) {
+            Ok(()) if answer == RELEASE => Ok(()),
+            Ok(()) => Err(Error::new(ErrorKind::InvalidData, "invalid answer of the server at the startup barrier")),
+            Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => {
+                Err(Error::new(ErrorKind::ConnectionAborted, "server closed the startup barrier without releasing it"))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Create a guard, which notifies the server when it's dropped at the end of the initialization
+    ///
+    /// If the application panics while the guard is alive, a failure is reported instead (see `notify_failure`),
+    /// so the test doesn't wait for its timeout. This makes it hard to forget the notification on some code path.
+    ///
+    /// ```no_run
+    /// # let port = 12345;
+    /// let guard = test_patience::Client::ready_guard(port);
+    /// // initialize application (eg. connect to database server)
+    /// drop(guard);
+    /// ```
+    pub fn ready_guard(port: u16) -> ReadyGuard {
+        ReadyGuard { port: Some(port) }
+    }
+
+    /// Open a connection to the server, over which several messages can be sent
+    ///
+    /// The messages are framed, so the server handles each of them as soon as it's complete.
+    /// This is useful for applications sending progress updates, which don't want to reconnect for every update.
+    ///
+    /// ```no_run
+    /// # let port = 12345;
+    /// let mut session = test_patience::Client::connect(port).unwrap();
+    /// session.progress("running migrations").unwrap();
+    /// session.progress("warming up caches").unwrap();
+    /// session.notify().unwrap();
+    /// ```
+    pub fn connect(port: u16) -> Result<Session> {
+        Ok(Session { stream: TcpStream::connect(&transport::loopback(port)[..])? })
+    }
+
+    /// Notify the server listening on `addr` that the client has started successfully
+    ///
+    /// This is needed if the server doesn't listen on the loopback interface of the application,
+    /// e.g. for applications running inside a container (see `ServerBuilder::bind_addr`).
+    /// If `addr` resolves to several addresses, e.g. the IPv4 and IPv6 address of a host name, they are tried in order.
+    ///
+    /// ```no_run
+    /// test_patience::Client::notify_addr("[::1]:12345").unwrap();
+    /// ```
+    pub fn notify_addr<A: ToSocketAddrs>(addr: A) -> Result<()> {
+        Client::send_to(addr, &authenticate(Message::new(Kind::Ready)))
+    }
+
+    /// Notify the server described by the `handshake` that the client has started successfully, authenticating the notification using its token
+    ///
+    /// See `Handshake`.
+    #[cfg(feature = "serde")]
+    pub fn notify_handshake(handshake: &Handshake) -> Result<()> {
+        let mut message = Message::new(Kind::Ready).header(TOKEN_HEADER, &handshake.token);
+        if !handshake.run_id.is_empty() {
+            message = message.header(RUN_HEADER, &handshake.run_id);
+        }
+        if handshake.timestamp {
+            message = message.header(SENT_HEADER, protocol::timestamp());
+        }
+        Client::send_to(handshake.addr(), &message)
+    }
+
+    fn send(port: u16, message: &Message) -> Result<()> {
+        Client::send_to(&transport::loopback(port)[..], message)
+    }
+
+    fn send_to<A: ToSocketAddrs>(addr: A, message: &Message) -> Result<()> {
+        let mut stream = TcpStream::connect(addr)?;
+        stream.write_all(&message.encode())?;
+        if message.kind != Kind::Progress {
+            PANIC_HOOK_ARMED.store(false, Ordering::SeqCst);
+        }
+        Ok(())
+    }
+
+    /// Register a panic hook, which reports a failure to the server if the application panics before it has notified the server
+    ///
+    /// The failure carries the panic message and location, so the test fails right away with the actual error instead of running into its timeout.
+    /// The hook stops reporting once the application has sent a notification using `notify` or one of its variants.
+    /// The previously registered hook is still called, so the panic message is printed as usual.
+    ///
+    /// ```no_run
+    /// # let port = 12345;
+    /// test_patience::Client::install_panic_hook(port);
+    /// // initialize application (eg. connect to database server)
+    /// test_patience::Client::notify(port).unwrap();
+    /// ```
+    pub fn install_panic_hook(port: u16) {
+        PANIC_HOOK_ARMED.store(true, Ordering::SeqCst);
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if PANIC_HOOK_ARMED.swap(false, Ordering::SeqCst) {
+                let payload = info.payload();
+                let message = payload.downcast_ref::<&str>().copied()
+                    .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                    .unwrap_or("Box<dyn Any>");
+                let reason = match info.location() {
+                    Some(location) => format!("application panicked at {}: {}", location, message),
+                    None => format!("application panicked: {}", message),
+                };
+                let _ = Client::notify_failure(port, &reason);
+            }
+            previous(info);
+        }));
+    }
+
+    /// Notify the UDP server that the client has started successfully
+    ///
+    /// The notification is sent as a single datagram, so this doesn't fail if the server isn't listening.
+    pub fn notify_udp(port: u16) -> Result<()> {
+        let socket = transport::bind_loopback(0, UdpSocket::bind)?;
+        let addr = if socket.local_addr()?.is_ipv4() { transport::loopback(port)[0] } else { transport::loopback(port)[1] };
+        socket.send_to(&authenticate(Message::new(Kind::Ready)).encode(), addr)?;
+        Ok(())
+    }
+
+    /// Notify the server watching the sentinel file at `path` that the client has started successfully
+    ///
+    /// The file is created atomically by writing a temporary file next to it and renaming it.
+    /// Applications that can't be modified beyond a shell wrapper can simply `touch` the file instead.
+    pub fn notify_file<P: AsRef<Path>>(path: P) -> Result<()> {
+        Client::notify_file_with_payload(path, b"")
+    }
+
+    /// Notify the server watching the sentinel file at `path` that the client has started successfully, handing back `payload`
+    ///
+    /// The payload is written to the sentinel file and can be retrieved using `Notification::payload`.
+    pub fn notify_file_with_payload<P: AsRef<Path>>(path: P, payload: &[u8]) -> Result<()> {
+        let path = path.as_ref();
+        let mut tmp_name = path.file_name()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "sentinel path has no file name"))?
+            .to_os_string();
+        tmp_name.push(".tmp");
+        let tmp_path = path.with_file_name(tmp_name);
+        fs::write(&tmp_path, payload)?;
+        fs::rename(&tmp_path, path)
+    }
+
+    /// Notify the server of the test process `pid` that the client has started successfully by sending it `SIGUSR1`
+    ///
+    /// The process id can be obtained from `Server::pid` and has to be sent to the application.
+    #[cfg(unix)]
+    pub fn notify_signal(pid: u32) -> Result<()> {
+        transport::signal::notify(pid)
+    }
+
+    /// Notify the server that the client has started successfully by writing to the pipe inherited from the test
+    ///
+    /// The inherited file descriptor (handle on Windows) is read from the `TEST_PATIENCE_PIPE` environment variable, which is set by `Server::inherit_pipe`.
+    /// Shell wrappers can write to the file descriptor directly, e.g. `printf done >&$TEST_PATIENCE_PIPE`.
+    pub fn notify_pipe() -> Result<()> {
+        transport::pipe::notify(&authenticate(Message::new(Kind::Ready)).encode())
+    }
+
+    /// Notify the server listening on vsock `port` of the VM or host `cid` that the client has started successfully
+    ///
+    /// An application running inside a VM reaches its host using CID 2 (`VMADDR_CID_HOST`).
+    #[cfg(target_os = "linux")]
+    pub fn notify_vsock(cid: u32, port: u32) -> Result<()> {
+        let mut stream = transport::vsock::connect(cid, port)?;
+        stream.write_all(&authenticate(Message::new(Kind::Ready)).encode())?;
+        Ok(())
+    }
+
+    /// Notify the server listening on the Unix domain socket at `path` that the client has started successfully
+    #[cfg(unix)]
+    pub fn notify_unix<P: AsRef<Path>>(path: P) -> Result<()> {
+        let mut stream = UnixStream::connect(path)?;
+        stream.write_all(&authenticate(Message::new(Kind::Ready)).encode())?;
+        Ok(())
+    }
+
+    /// Notify the server listening on the abstract Unix domain socket `name` that the client has started successfully
+    #[cfg(target_os = "linux")]
+    pub fn notify_abstract<N: AsRef<[u8]>>(name: N) -> Result<()> {
+        let addr = UnixSocketAddr::from_abstract_name(name)?;
+        let mut stream = UnixStream::connect_addr(&addr)?;
+        stream.write_all(&authenticate(Message::new(Kind::Ready)).encode())?;
+        Ok(())
+    }
+}
+
+/// Heartbeat connection to the server, created by `Client::heartbeat`
+///
+/// The heartbeats stop when it's dropped.
+pub struct Heartbeat {
+    stream: TcpStream,
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Heartbeat {
+    /// Stop the heartbeats and notify the server that the client has started successfully
+    pub fn notify(mut self) -> Result<()> {
+        self.finish(Message::new(Kind::Ready))
+    }
+
+    /// Stop the heartbeats and notify the server that the client has failed to start
+    ///
+    /// See `Client::notify_failure`.
+    pub fn notify_failure(mut self, reason: &str) -> Result<()> {
+        self.finish(Message::new(Kind::Failure).body(reason))
+    }
+
+    fn finish(&mut self, message: Message) -> Result<()> {
+        self.stop();
+        self.stream.write_all(&authenticate(message).encode())
+    }
+
+    fn stop(&mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Guard created by `Client::ready_guard`, which notifies the server when it's dropped
+///
+/// Errors sending the notification from `Drop` are ignored. Use `notify` to handle them.
+#[derive(Debug)]
+pub struct ReadyGuard {
+    /// Port of the server, until the notification has been sent
+    port: Option<u16>,
+}
+
+impl ReadyGuard {
+    /// Notify the server that the client has started successfully right away, returning any error
+    pub fn notify(mut self) -> Result<()> {
+        Client::notify(self.port.take().expect("notification already sent"))
+    }
+
+    /// Notify the server that the client has failed to start, see `Client::notify_failure`
+    pub fn notify_failure(mut self, reason: &str) -> Result<()> {
+        Client::notify_failure(self.port.take().expect("notification already sent"), reason)
+    }
+}
+
+impl Drop for ReadyGuard {
+    fn drop(&mut self) {
+        if let Some(port) = self.port.take() {
+            let _ = if thread::panicking() {
+                Client::notify_failure(port, "application panicked during its initialization")
+            } else {
+                Client::notify(port)
+            };
+        }
+    }
+}
+
+/// Connection to the server carrying several framed messages, created by `Client::connect`
+pub struct Session {
+    stream: TcpStream,
+}
+
+impl Session {
+    /// Send a progress update `message`, see `Client::progress`
+    pub fn progress(&mut self, message: &str) -> Result<()> {
+        self.send(Message::new(Kind::Progress).body(message))
+    }
+
+    /// Notify the server that the client has started successfully and close the connection
+    pub fn notify(mut self) -> Result<()> {
+        self.send(Message::new(Kind::Ready))
+    }
+
+    /// Notify the server that the client has started successfully, sending structured `metadata` along with the notification, and close the connection
+    ///
+    /// See `Client::notify_with_metadata`.
+    #[cfg(feature = "serde")]
+    pub fn notify_with_metadata(mut self, metadata: &Metadata) -> Result<()> {
+        self.send(Message::new(Kind::Ready).with_metadata(metadata)?)
+    }
+
+    /// Notify the server that the client has failed to start and close the connection
+    ///
+    /// See `Client::notify_failure`.
+    pub fn notify_failure(mut self, reason: &str) -> Result<()> {
+        self.send(Message::new(Kind::Failure).body(reason))
+    }
+
+    fn send(&mut self, message: Message) -> Result<()> {
+        self.stream.write_all(&protocol::frame(&authenticate(message))?)
+    }
+}
+
+/// Get the headers carrying the token and run ID passed in the `TEST_PATIENCE_TOKEN` and `TEST_PATIENCE_RUN` environment variables, if they are set
+fn credentials() -> Headers {
+    [(TOKEN_HEADER, TOKEN_VAR), (RUN_HEADER, RUN_VAR)].iter()
+        .filter_map(|&(header, var)| env::var(var).ok().map(|value| (header.to_string(), value)))
+        .collect()
+}
+
+/// Add the token and run ID passed in the `TEST_PATIENCE_TOKEN` and `TEST_PATIENCE_RUN` environment variables to the `message`, if they are set
+///
+/// The message is timestamped as well if the `TEST_PATIENCE_TIMESTAMP` environment variable is set, see `Server::measure_overhead`.
+/// Otherwise a notification without token and run ID stays a plain `done`, which all versions of the server understand.
+pub(crate) fn authenticate(message: Message) -> Message {
+    let message = credentials().into_iter().fold(message, |message, (name, value)| message.header(&name, value));
+    if env::var_os(TIMESTAMP_VAR).is_some() {
+        return message.header(SENT_HEADER, protocol::timestamp());
+    }
+    message
+}
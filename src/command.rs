@@ -0,0 +1,40 @@
+//! Extension of `std::process::Command` for applications notifying a `Server`
+
+use std::io::Result;
+use std::process::Command;
+
+use crate::Server;
+
+/// Extension trait letting any `Command` builder chain pass the notification details of a `Server` to the application
+///
+/// ```no_run
+/// use std::process::Command;
+/// use std::time::Duration;
+///
+/// use test_patience::PatienceCommandExt;
+///
+/// let server = test_patience::Server::new().unwrap();
+/// # #[allow(unused_variables)]
+/// let child = Command::new("path/to/application")
+///     .arg("--verbose")
+///     .notify_via(&server).unwrap()
+///     .spawn()
+///     .unwrap();
+/// server.wait(Duration::from_secs(5)).unwrap();
+/// ```
+pub trait PatienceCommandExt {
+    /// Tell the application how to notify `server`, like `Server::spawn` does
+    ///
+    /// Sets the environment variables of `Server::env`, which are `TEST_PATIENCE_PORT`, `TEST_PATIENCE_TOKEN` and `TEST_PATIENCE_RUN`,
+    /// plus `TEST_PATIENCE_TIMESTAMP` if the server measures its overhead (see `Server::measure_overhead`).
+    /// Servers created using `new_pipe` let the application inherit their pipe as well.
+    /// Unlike `Server::spawn`, the application is not started in a process group of its own.
+    fn notify_via(&mut self, server: &Server) -> Result<&mut Self>;
+}
+
+impl PatienceCommandExt for Command {
+    fn notify_via(&mut self, server: &Server) -> Result<&mut Command> {
+        server.configure(self)?;
+        Ok(self)
+    }
+}
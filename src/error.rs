@@ -0,0 +1,190 @@
+//! Classification of the errors returned by servers, which tests can match on
+
+use std::error;
+use std::fmt;
+use std::io;
+
+use crate::{EarlyExit, StartupFailure, WaitTimeout};
+use crate::shutdown::Cancelled;
+
+/// Reason for which waiting for an application failed, converted from the `io::Error` returned by `Server::wait` and its variants
+///
+/// The methods of this crate keep returning `io::Error`, so they work with `?` in functions returning `io::Result`.
+/// Converting the error into this enum lets tests match on the reason instead of comparing error messages,
+/// without having to downcast the wrapped errors themselves.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use test_patience::Error;
+///
+/// let server = test_patience::Server::new().unwrap();
+/// match server.wait(Duration::from_secs(5)).map_err(Error::from) {
+///     Ok(notification) => println!("application started after {:?}", notification.elapsed()),
+///     Err(Error::Timeout(timeout)) => panic!("no notification within {:?}", timeout.timeout()),
+///     Err(Error::Failure(failure)) => panic!("application failed to start: {}", failure.reason()),
+///     Err(e) => panic!("{}", e),
+/// }
+/// ```
+#[derive(Debug)]
+pub enum Error {
+    /// No startup notification arrived within the timeout period
+    Timeout(WaitTimeout),
+    /// The application reported that it failed to start, see `Client::notify_failure`
+    Failure(StartupFailure),
+    /// The application exited before signaling its start, see `Server::spawn_and_wait`
+    EarlyExit(EarlyExit),
+    /// The wait was cancelled by shutting down the server, see `ShutdownHandle`
+    Cancelled,
+    /// A message which isn't a valid startup notification was received, see `Server::ignore_invalid`
+    InvalidNotification,
+    /// A service signaled its start repeatedly, see `Duplicates::Error`
+    Duplicate(String),
+    /// The application stopped sending heartbeats, see `Server::heartbeat_timeout`
+    HeartbeatStopped,
+    /// The application closed its heartbeat connection without signaling its start
+    HeartbeatClosed,
+    /// Any other error, e.g. a failure to bind or spawn
+    Io(io::Error),
+}
+
+impl Error {
+    /// Get the kind of the `io::Error` this error was converted from
+    pub fn kind(&self) -> io::ErrorKind {
+        match *self {
+            Error::Timeout(_) => io::ErrorKind::TimedOut,
+            Error::Failure(_) | Error::EarlyExit(_) | Error::InvalidNotification => io::ErrorKind::Other,
+            Error::Cancelled => io::ErrorKind::Interrupted,
+            Error::Duplicate(_) => io::ErrorKind::InvalidData,
+            Error::HeartbeatStopped | Error::HeartbeatClosed => io::ErrorKind::ConnectionAborted,
+            Error::Io(ref e) => e.kind(),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        let e = match downcast(e) {
+            Ok(timeout) => return Error::Timeout(timeout),
+            Err(e) => e,
+        };
+        let e = match downcast(e) {
+            Ok(failure) => return Error::Failure(failure),
+            Err(e) => e,
+        };
+        let e = match downcast(e) {
+            Ok(exit) => return Error::EarlyExit(exit),
+            Err(e) => e,
+        };
+        let e = match downcast::<Cancelled>(e) {
+            Ok(_) => return Error::Cancelled,
+            Err(e) => e,
+        };
+        let e = match downcast::<InvalidNotification>(e) {
+            Ok(_) => return Error::InvalidNotification,
+            Err(e) => e,
+        };
+        let e = match downcast::<Duplicate>(e) {
+            Ok(Duplicate(name)) => return Error::Duplicate(name),
+            Err(e) => e,
+        };
+        let e = match downcast::<HeartbeatStopped>(e) {
+            Ok(_) => return Error::HeartbeatStopped,
+            Err(e) => e,
+        };
+        match downcast::<HeartbeatClosed>(e) {
+            Ok(_) => Error::HeartbeatClosed,
+            Err(e) => Error::Io(e),
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> io::Error {
+        match e {
+            Error::Timeout(timeout) => io::Error::new(io::ErrorKind::TimedOut, timeout),
+            Error::Failure(failure) => io::Error::other(failure),
+            Error::EarlyExit(exit) => io::Error::other(exit),
+            Error::Cancelled => io::Error::new(io::ErrorKind::Interrupted, Cancelled),
+            Error::InvalidNotification => io::Error::other(InvalidNotification),
+            Error::Duplicate(name) => io::Error::new(io::ErrorKind::InvalidData, Duplicate(name)),
+            Error::HeartbeatStopped => io::Error::new(io::ErrorKind::ConnectionAborted, HeartbeatStopped),
+            Error::HeartbeatClosed => io::Error::new(io::ErrorKind::ConnectionAborted, HeartbeatClosed),
+            Error::Io(e) => e,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Error::Timeout(ref timeout) => timeout.fmt(f),
+            Error::Failure(ref failure) => failure.fmt(f),
+            Error::EarlyExit(ref exit) => exit.fmt(f),
+            Error::Cancelled => Cancelled.fmt(f),
+            Error::InvalidNotification => InvalidNotification.fmt(f),
+            Error::Duplicate(ref name) => write!(f, "received repeated startup notification of {}", name),
+            Error::HeartbeatStopped => HeartbeatStopped.fmt(f),
+            Error::HeartbeatClosed => HeartbeatClosed.fmt(f),
+            Error::Io(ref e) => e.fmt(f),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// Marker wrapped in the errors for messages which aren't valid startup notifications
+#[derive(Debug)]
+pub(crate) struct InvalidNotification;
+
+impl fmt::Display for InvalidNotification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "wrong startup notification received")
+    }
+}
+
+impl error::Error for InvalidNotification {}
+
+/// Name of a service wrapped in the error for its repeated startup notification
+#[derive(Debug)]
+pub(crate) struct Duplicate(pub(crate) String);
+
+impl fmt::Display for Duplicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "received repeated startup notification of {}", self.0)
+    }
+}
+
+impl error::Error for Duplicate {}
+
+/// Marker wrapped in the errors for heartbeats which have stopped before the startup notification
+#[derive(Debug)]
+pub(crate) struct HeartbeatStopped;
+
+impl fmt::Display for HeartbeatStopped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "application stopped sending heartbeats")
+    }
+}
+
+impl error::Error for HeartbeatStopped {}
+
+/// Marker wrapped in the errors for heartbeat connections closed before the startup notification
+#[derive(Debug)]
+pub(crate) struct HeartbeatClosed;
+
+impl fmt::Display for HeartbeatClosed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "application closed its heartbeat connection without signaling its start")
+    }
+}
+
+impl error::Error for HeartbeatClosed {}
+
+/// Take the error of type `T` out of `e`, or return `e` itself if it doesn't wrap one
+fn downcast<T: error::Error + Send + Sync + 'static>(e: io::Error) -> Result<T, io::Error> {
+    if e.get_ref().is_none_or(|inner| !inner.is::<T>()) {
+        return Err(e);
+    }
+    let inner = e.into_inner().expect("error has no inner error");
+    Ok(*inner.downcast::<T>().expect("inner error has the wrong type"))
+}
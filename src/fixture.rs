@@ -0,0 +1,219 @@
+//! Fixtures declared once per test module using the `fixture!` macro
+
+use std::fmt;
+use std::io::Result;
+use std::process::{Child, Command, ExitStatus};
+use std::time::Duration;
+
+use crate::probe::Probe;
+use crate::{ChildGuard, Notification, Server};
+use crate::process;
+
+mod rstest;
+
+pub use self::rstest::{PatienceFixture, RunningApp};
+
+/// Declare named fixtures, which are started and stopped by generated helper functions
+///
+/// Every fixture becomes a module containing the functions `start() -> Fixture` and `stop(Fixture)`, which panic if the application fails to start or stop.
+/// The fixture is described by the following fields in this order, of which only `cmd` is required:
+///
+/// - `cmd`: path of the application
+/// - `args`: arguments passed to the application
+/// - `env`: environment variables passed to the application
+/// - `ready`: how to tell that the application is ready, see `Readiness` (a startup notification within 5 seconds by default)
+/// - `teardown`: closure taking the `&mut Child`, which is called before the application is killed, e.g. to shut it down gracefully
+///
+/// The expressions are evaluated inside the generated module, which imports everything from the surrounding module.
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use test_patience::fixture;
+/// use test_patience::fixture::Readiness;
+/// use test_patience::probe;
+///
+/// fixture! {
+///     database {
+///         cmd: "path/to/db",
+///         args: ["--port", "5432"],
+///         env: { "DB_LOG" => "debug" },
+///         ready: Readiness::probe(probe::tcp("127.0.0.1:5432"), Duration::from_secs(10)),
+///     }
+///
+///     api {
+///         cmd: "path/to/api",
+///         teardown: |child| println!("stopping api process {}", child.id()),
+///     }
+/// }
+///
+/// # fn main() {
+/// let db = database::start();
+/// let api = api::start();
+/// // test using the database and the api
+/// api::stop(api);
+/// database::stop(db);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! fixture {
+    ($(
+        $(#[$attr:meta])*
+        $name:ident {
+            cmd: $cmd:expr
+            $(, args: [$($arg:expr),* $(,)?])?
+            $(, env: {$($key:expr => $value:expr),* $(,)?})?
+            $(, ready: $ready:expr)?
+            $(, teardown: $teardown:expr)?
+            $(,)?
+        }
+    )*) => {$(
+        $(#[$attr])*
+        #[allow(dead_code)]
+        mod $name {
+            #[allow(unused_imports)]
+            use super::*;
+
+            /// Start the fixture, panicking if it fails to get ready
+            pub fn start() -> $crate::fixture::Fixture {
+                let mut command = ::std::process::Command::new($cmd);
+                $($(command.arg($arg);)*)?
+                $($(command.env($key, $value);)*)?
+                #[allow(unused_variables)]
+                let ready = $crate::fixture::Readiness::default();
+                $(let ready = $ready;)?
+                let result = $crate::fixture::Fixture::start(&mut command, ready);
+                #[allow(unused_mut)]
+                let mut fixture = result.unwrap_or_else(|e| panic!("failed to start fixture {}: {}", stringify!($name), e));
+                $(fixture = fixture.teardown($teardown);)?
+                fixture
+            }
+
+            /// Stop the fixture, panicking if it can't be stopped
+            pub fn stop(fixture: $crate::fixture::Fixture) {
+                if let Err(e) = fixture.stop() {
+                    panic!("failed to stop fixture {}: {}", stringify!($name), e);
+                }
+            }
+        }
+    )*};
+}
+
+/// Way of telling that the application of a `Fixture` is ready
+pub enum Readiness {
+    /// Wait for a startup notification within the timeout, like `Server::spawn_and_wait`
+    Notify(Duration),
+    /// Wait for the probe to succeed within the timeout
+    Probe(Box<dyn Probe>, Duration),
+}
+
+impl Readiness {
+    /// Wait for a startup notification within `timeout`
+    pub fn notify(timeout: Duration) -> Readiness {
+        Readiness::Notify(timeout)
+    }
+
+    /// Wait for `probe` to succeed within `timeout`
+    pub fn probe<P: Probe + 'static>(probe: P, timeout: Duration) -> Readiness {
+        Readiness::Probe(Box::new(probe), timeout)
+    }
+}
+
+impl Default for Readiness {
+    /// Wait for a startup notification within 5 seconds
+    fn default() -> Readiness {
+        Readiness::Notify(Duration::from_secs(5))
+    }
+}
+
+impl fmt::Debug for Readiness {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Readiness::Notify(timeout) => f.debug_tuple("Notify").field(&timeout).finish(),
+            Readiness::Probe(ref probe, timeout) => f.debug_tuple("Probe").field(&probe.describe()).field(&timeout).finish(),
+        }
+    }
+}
+
+type Teardown = Box<dyn FnOnce(&mut Child) + Send>;
+
+/// Running application of a fixture, which is torn down when it's stopped or dropped
+pub struct Fixture {
+    child: Option<ChildGuard>,
+    notification: Option<Notification>,
+    teardown: Option<Teardown>,
+}
+
+impl Fixture {
+    /// Spawn the application using `command` and wait until it's ready
+    ///
+    /// If it doesn't get ready, the application is killed before the error is returned.
+    pub fn start(command: &mut Command, ready: Readiness) -> Result<Fixture> {
+        let (child, notification) = match ready {
+            Readiness::Notify(timeout) => {
+                let (child, notification) = Server::new()?.spawn_and_wait(command, timeout)?;
+                (ChildGuard::new(child), Some(notification))
+            }
+            Readiness::Probe(probe, timeout) => {
+                process::new_group(command);
+                let child = ChildGuard::new(command.spawn()?);
+                process::join_group(&child)?;
+                probe.wait(timeout)?;
+                (child, None)
+            }
+        };
+        Ok(Fixture { child: Some(child), notification, teardown: None })
+    }
+
+    /// Call `teardown` before the application is killed
+    pub fn teardown<F: FnOnce(&mut Child) + Send + 'static>(mut self, teardown: F) -> Fixture {
+        self.teardown = Some(Box::new(teardown));
+        self
+    }
+
+    /// Get the child process of the application
+    pub fn child(&mut self) -> &mut Child {
+        self.child.as_mut().expect("fixture already stopped")
+    }
+
+    /// Get the startup notification, if the fixture waited for one
+    pub fn notification(&self) -> Option<&Notification> {
+        self.notification.as_ref()
+    }
+
+    /// Tear the application down and return its exit status
+    pub fn stop(mut self) -> Result<ExitStatus> {
+        self.shutdown().expect("fixture already stopped")
+    }
+
+    fn shutdown(&mut self) -> Option<Result<ExitStatus>> {
+        let mut child = self.child.take()?.into_inner();
+        if let Some(teardown) = self.teardown.take() {
+            teardown(&mut child);
+        }
+        // the teardown may have reaped the child already, after which its group id may have been reused
+        match child.try_wait() {
+            Ok(Some(status)) => Some(Ok(status)),
+            _ => {
+                process::kill(&mut child);
+                Some(child.wait())
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Fixture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Fixture")
+            .field("child", &self.child)
+            .field("notification", &self.notification)
+            .field("teardown", &self.teardown.is_some())
+            .finish()
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = self.shutdown();
+    }
+}
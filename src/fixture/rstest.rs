@@ -0,0 +1,132 @@
+//! Integration with `rstest` style fixtures
+
+use std::ffi::OsStr;
+use std::io::Result;
+use std::ops::{Deref, DerefMut};
+use std::process::{Child, Command, ExitStatus};
+
+use crate::Notification;
+use super::{Fixture, Readiness, Teardown};
+
+/// Description of an application, which is started by an `rstest` fixture function
+///
+/// The `RunningApp` returned by `start` is `Send` and implements `Debug`, so it can be passed to tests traced using `#[trace]` and moved into other threads.
+/// It's torn down when the test drops it, even if the test panics.
+///
+/// ```no_run
+/// use rstest::{fixture, rstest};
+/// use test_patience::fixture::{PatienceFixture, RunningApp};
+///
+/// #[fixture]
+/// fn app() -> RunningApp {
+///     PatienceFixture::new("path/to/app").arg("--verbose").start()
+/// }
+///
+/// #[rstest]
+/// fn responds(app: RunningApp) {
+///     println!("application running as process {}", app.id());
+/// }
+/// # fn main() {}
+/// ```
+pub struct PatienceFixture {
+    command: Command,
+    ready: Readiness,
+    teardown: Option<Teardown>,
+}
+
+impl PatienceFixture {
+    /// Describe the application `program`, which has to send a startup notification within 5 seconds
+    pub fn new<S: AsRef<OsStr>>(program: S) -> PatienceFixture {
+        PatienceFixture::from_command(Command::new(program))
+    }
+
+    /// Describe the application spawned using `command`
+    pub fn from_command(command: Command) -> PatienceFixture {
+        PatienceFixture { command, ready: Readiness::default(), teardown: None }
+    }
+
+    /// Pass the argument `arg` to the application
+    pub fn arg<S: AsRef<OsStr>>(mut self, arg: S) -> PatienceFixture {
+        self.command.arg(arg);
+        self
+    }
+
+    /// Pass the arguments `args` to the application
+    pub fn args<I, S>(mut self, args: I) -> PatienceFixture
+        where I: IntoIterator<Item = S>, S: AsRef<OsStr>
+    {
+        self.command.args(args);
+        self
+    }
+
+    /// Set the environment variable `key` of the application to `value`
+    pub fn env<K: AsRef<OsStr>, V: AsRef<OsStr>>(mut self, key: K, value: V) -> PatienceFixture {
+        self.command.env(key, value);
+        self
+    }
+
+    /// Set how to tell that the application is ready
+    pub fn ready(mut self, ready: Readiness) -> PatienceFixture {
+        self.ready = ready;
+        self
+    }
+
+    /// Call `teardown` before the application is killed, see `Fixture::teardown`
+    pub fn teardown<F: FnOnce(&mut Child) + Send + 'static>(mut self, teardown: F) -> PatienceFixture {
+        self.teardown = Some(Box::new(teardown));
+        self
+    }
+
+    /// Spawn the application and wait until it's ready, panicking if it fails to get ready
+    pub fn start(self) -> RunningApp {
+        let program = self.command.get_program().to_string_lossy().into_owned();
+        self.try_start().unwrap_or_else(|e| panic!("failed to start {}: {}", program, e))
+    }
+
+    /// Spawn the application and wait until it's ready
+    pub fn try_start(mut self) -> Result<RunningApp> {
+        let mut fixture = Fixture::start(&mut self.command, self.ready)?;
+        fixture.teardown = self.teardown;
+        Ok(RunningApp { fixture })
+    }
+}
+
+/// Application started by a `PatienceFixture`, which is torn down when it's dropped
+///
+/// It dereferences to the `Child`.
+#[derive(Debug)]
+pub struct RunningApp {
+    fixture: Fixture,
+}
+
+impl RunningApp {
+    /// Get the startup notification, if the application was waited for using one
+    pub fn notification(&self) -> Option<&Notification> {
+        self.fixture.notification()
+    }
+
+    /// Tear the application down and return its exit status, see `Fixture::stop`
+    pub fn stop(self) -> Result<ExitStatus> {
+        self.fixture.stop()
+    }
+}
+
+impl From<RunningApp> for Fixture {
+    fn from(app: RunningApp) -> Fixture {
+        app.fixture
+    }
+}
+
+impl Deref for RunningApp {
+    type Target = Child;
+
+    fn deref(&self) -> &Child {
+        self.fixture.child.as_ref().expect("fixture already stopped")
+    }
+}
+
+impl DerefMut for RunningApp {
+    fn deref_mut(&mut self) -> &mut Child {
+        self.fixture.child()
+    }
+}
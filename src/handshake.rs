@@ -0,0 +1,78 @@
+//! Everything the application needs to notify the server, serialized as a single value
+
+use std::io::{Result, Error, ErrorKind};
+use std::net::{IpAddr, SocketAddr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::VERSION;
+
+/// Port, address, token, run ID, timestamp flag and protocol version of a server, created by `Server::handshake`
+///
+/// It's serialized as JSON, so it can be passed to the application as a single environment variable or configuration file entry.
+/// The application parses it using `from_json` and notifies the server using `Client::notify_handshake`.
+///
+/// ```no_run
+/// use std::process::Command;
+/// use std::time::Duration;
+///
+/// let server = test_patience::Server::new().unwrap();
+/// let handshake = server.handshake().unwrap();
+/// # #[allow(unused_variables)]
+/// let child = Command::new("path/to/application").env("APP_HANDSHAKE", handshake.to_json()).spawn().unwrap();
+/// server.wait(Duration::from_secs(5)).unwrap();
+/// ```
+///
+/// Application:
+///
+/// ```no_run
+/// let handshake = test_patience::Handshake::from_json(&std::env::var("APP_HANDSHAKE").unwrap()).unwrap();
+/// test_patience::Client::notify_handshake(&handshake).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Handshake {
+    /// Port the server is listening on
+    pub port: u16,
+    /// Address the server is listening on
+    pub address: IpAddr,
+    /// Token authenticating the notifications, see `Server::require_token`
+    pub token: String,
+    /// Run ID telling the notifications for this server apart from those of applications started for earlier servers, see `Server::run_id`
+    ///
+    /// It's empty for handshakes without a run ID.
+    #[serde(default)]
+    pub run_id: String,
+    /// Whether the notification is timestamped, so the server can measure its overhead, see `Server::measure_overhead`
+    #[serde(default)]
+    pub timestamp: bool,
+    /// Version of the protocol spoken by the server
+    pub version: u32,
+}
+
+impl Handshake {
+    pub(crate) fn new(addr: SocketAddr, token: &str, run_id: &str, timestamp: bool) -> Handshake {
+        Handshake { port: addr.port(), address: addr.ip(), token: token.to_string(), run_id: run_id.to_string(), timestamp, version: VERSION }
+    }
+
+    /// Parse a handshake encoded as JSON
+    ///
+    /// Returns an error of kind `InvalidData` if the JSON is invalid, or if the handshake was created by a newer version of this crate speaking a protocol this one doesn't know.
+    pub fn from_json(json: &str) -> Result<Handshake> {
+        let handshake: Handshake = serde_json::from_str(json)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, format!("invalid handshake: {}", e)))?;
+        if handshake.version > VERSION {
+            return Err(Error::new(ErrorKind::InvalidData, format!("unsupported handshake version {}", handshake.version)));
+        }
+        Ok(handshake)
+    }
+
+    /// Encode the handshake as JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("failed to encode handshake")
+    }
+
+    /// Get the address of the server
+    pub fn addr(&self) -> SocketAddr {
+        SocketAddr::new(self.address, self.port)
+    }
+}
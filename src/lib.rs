@@ -14,27 +14,107 @@
 //! It blocks the currently running thread until either the starting application has signaled its successful start or the `timeout` period has passed.
 //!
 //! When the application is ready, it has to create an instance of the `Client` struct and call the `notify` method with the correct port number.
+//! `Client::notify_from_env` reads the port number from the `TEST_PATIENCE_PORT` environment variable and does nothing if it isn't set.
 //! After that the thread of the test continues executing.
 //!
 //! In order to disable startup notifications in release builds, use `cfg!(debug_assertions)` (see [conditional compilation](https://doc.rust-lang.org/reference.html#conditional-compilation)).
 //!
+//! ## Spawning applications
+//!
+//! `Server::spawn` spawns the application with the port number passed in the `TEST_PATIENCE_PORT` environment variable.
+//! Existing `Command` builder chains can pass the port number using `PatienceCommandExt::notify_via` or `Command::envs(server.env())` instead.
+//! Nested layers of tests can pass it in a variable of their own using `Server::port_var` and `Client::notify_from_env_var`.
+//! A `ChildGuard` kills and reaps the application when it's dropped, even if the test panics.
+//! The `Orchestrator` starts several applications in the order of their dependencies, each of them once the services it depends on are ready.
+//! Started as a `ProcessSet`, they are killed in reverse order when the set is dropped.
+//! Applications shared by several tests can be declared once per test module using the `fixture!` macro, which generates functions starting and stopping them.
+//! In `rstest` fixture functions, `fixture::PatienceFixture` spawns an application, which is torn down once the test drops it.
+//!
+//! ## Waiting
+//!
+//! `Server::wait_ref` waits without consuming the server, so it can be waited for again, e.g. after restarting the application, and `Server::try_wait` checks for a notification without blocking.
+//! `Server::wait_deadline` waits until a point in time instead, so several waits can share an overall time budget, and `Server::wait_forever` doesn't time out at all.
+//! `Server::wait_with` invokes a callback every second while waiting, e.g. to report that a long running wait is still alive.
+//! A `ShutdownHandle` obtained using `Server::shutdown_handle` cancels a wait running in another thread right away, e.g. when the test bails out early, which `Error::from` turns into `Error::Cancelled`.
+//! On Unix, servers wait for their sockets to become readable instead of polling them, so notifications are noticed right away without burning CPU time while slow applications start. On Windows, they still poll their sockets in the poll interval of 1 millisecond, so waits there use more CPU time and notice notifications up to one interval late.
+//! Listeners which have to be polled, like sentinel files, can slow their checks down exponentially while slow applications start using `Server::poll_backoff`.
+//! After `Server::measure_overhead` asked the application to timestamp its notifications, `Notification::overhead` reports how long it took from the application sending a notification until the server handed it to the test, which shows that the tooling doesn't skew the measured durations.
+//!
+//! ## Errors
+//!
+//! If no notification arrives in time, `wait` returns a `WaitTimeout` telling how long it waited on which address and how many connections the server accepted, and `Server::drain_late` collects notifications arriving after the timeout.
+//! Converting the returned `io::Error` into a `test_patience::Error` lets tests match on the reason, e.g. a timeout, a reported failure or a repeated notification, instead of comparing error messages.
+//! Messages larger than 16 MiB are rejected with an error instead of being buffered, see `Server::max_message_size`, and `Server::ignore_invalid` skips invalid messages, e.g. sent by port scanners, instead of ending the wait.
+//!
+//! ## Notifying the test
+//!
+//! If the application fails to start, it can call `Client::notify_failure` instead of `notify`, so `wait` returns a `StartupFailure` carrying the reason right away.
+//! A `ReadyGuard` created using `Client::ready_guard` sends the notification when it's dropped at the end of the initialization, or a failure if the application panics first.
+//! `Client::install_panic_hook` reports panics happening before the notification to the server, including the panic message.
+//! Applications with a long warmup can send updates using `Client::progress`, and `Server::reset_timeout_on_progress` measures the timeout from the last update.
+//! `Client::connect` opens a connection over which several updates and the final notification can be sent.
+//! Alternatively, applications can keep a connection open using `Client::heartbeat`, so `wait` fails early if the application dies or stops sending heartbeats (see `Server::heartbeat_timeout`).
+//! Applications can hand data back to the test, e.g. the port they chose during startup, using `Client::notify_with_payload`, see `Notification::payload`.
+//!
+//! ## Several applications
+//!
+//! `Server::wait_for_n` waits for several applications reporting to the same server, which can identify themselves using `Client::notify_named` (see `Notification::name`), and `Server::wait_all` waits for every service declared using `Server::expect`.
+//! `Server::duplicates` sets whether repeated notifications of the same service, e.g. sent by an application retrying, count again, are ignored or end the wait with an error.
+//! Services declared as a group using `Server::group` can be waited for as a unit using `Server::wait_group`.
+//! A `Registry` obtained using `Server::registry` tells which services have come up, even while the server waits in another thread.
+//! A `ServerHandle` obtained using `Server::handle` additionally tells the port and declares further services for a running `wait_all`.
+//! `Server::barrier` releases several applications waiting in `Client::wait_for_release` at the same instant.
+//!
+//! ## Shared machines
+//!
+//! On shared machines, `Server::require_token` makes the server ignore all notifications which don't carry its random token, passed to the application in the `TEST_PATIENCE_TOKEN` environment variable.
+//! Notifications carrying the run ID of another server, e.g. sent by an application leaked by an earlier test to a reused port, are always ignored (see `Server::run_id`).
+//!
+//! ## Addresses and ports
+//!
+//! `Server::builder` configures the address the server listens on (e.g. `0.0.0.0` for applications running inside a container, see `Server::local_addr`), the peers it accepts connections from besides loopback addresses, a fixed token and the interval in which it checks for notifications, and `Server::bind` listens on a fixed port for applications which can only read it from their configuration file.
+//! On CI machines whose firewall only allows certain ports, `Server::bind_in_range` picks the first free port of a range.
+//! On IPv6-only hosts, servers listen on `::1` instead of `127.0.0.1`, which clients try as well. `Client::notify_addr` notifies a server at any IPv4 or IPv6 address or host name, e.g. the host running the test from inside a container or VM.
+//!
+//! ## Other transports
+//!
+//! Applications that cannot open sockets can print a marker line to their stdout instead, which is watched by `Server::watch_stdout`.
+//! In network-less sandboxes, `Server::new_pipe` passes the write end of an anonymous pipe to the application, which signals readiness using `Client::notify_pipe`.
+//! Legacy applications which can only be wrapped in a shell script can create a sentinel file, which is watched by `Server::watch_file`. Its content is handed back as the payload of the notification.
+//! For tiny helper tools, `Server::new_udp` and `Client::notify_udp` signal readiness with a single UDP datagram instead of a TCP connection.
+//! On Unix platforms, `Server::new_unix` and `Client::notify_unix` can be used to communicate via a Unix domain socket instead of a TCP port,
+//! and `Server::new_signal` and `Client::notify_signal` let the application signal readiness by sending `SIGUSR1` to the test process.
+//! On Linux, `Server::new_abstract` and `Client::notify_abstract` use a socket in the abstract namespace, which needs no filesystem cleanup,
+//! and `Server::new_vsock` and `Client::notify_vsock` let an application running inside a VM notify the host without any port forwarding.
+//!
+//! ## Probes
+//!
+//! Off-the-shelf services which can't send a startup notification can be waited for using a probe, e.g. `probe::tcp` polling until a port accepts connections (see the [`probe`](probe/index.html) module).
+//!
+//! # Cargo features
+//!
+//! - `async`: runtime-agnostic asynchronous `Server` and `Client::notify_async`, working on any executor (see the [`asynchronous`](asynchronous/index.html) module)
+//! - `tokio`: the asynchronous API for tests and applications running on a tokio runtime, plus a `tokio::process` spawn helper (see the [`tokio`](tokio/index.html) module)
+//! - `async-std`: the asynchronous API for async-std based tests and applications (see the [`async_std`](async_std/index.html) module)
+//! - `serde`: structured `Metadata` sent along with the startup notification (see `Client::notify_with_metadata`), and the `Handshake` passing everything the application needs to notify the server as a single JSON value
+//! - `regex`: `probe::log`, waiting for a line of the output of the application to match a regular expression
+//! - `docker`: `probe::docker`, waiting for a Docker container to report itself as healthy
+//! - `cli`: the `test-patience` command line tool, with `test-patience notify --port $TEST_PATIENCE_PORT` for applications and `test-patience wait`, `wait-for` or `exec` for test suites which aren't written in Rust
+//! - `macros`: the `#[test_patience::test]` attribute, starting an application before a test and killing it afterwards
+//! - `mio`: `Poller`, waiting for many servers from a single thread by registering all of their sockets with one `mio` poller (Unix only)
+//!
 //! # Examples
 //!
 //! Application
 //!
 //! ```no_run
-//! use std::env;
-//!
 //! // initialize application (eg. connect to database server)
 //! # fn get_db_connection() {}
 //! # #[allow(unused_variables)]
 //! let db_connection = get_db_connection();
 //!
 //! // notify test in case the environment variable TEST_PATIENCE_PORT is set
-//! if let Some(port) = env::var("TEST_PATIENCE_PORT").ok()
-//!                     .and_then(|s| s.parse::<u16>().ok()) {
-//!     test_patience::Client::notify(port).unwrap();
-//! }
+//! test_patience::Client::notify_from_env().unwrap();
 //! ```
 //!
 //! Test
@@ -56,67 +136,42 @@
 //! ```
 #![warn(missing_docs)]
 
-use std::net::{TcpListener, TcpStream};
-use std::time::{Instant, Duration};
-use std::io::{Result, Error, ErrorKind};
-use std::io::prelude::*;
-use std::thread;
-
-/// Entry point for the application that needs to be synchronized
-pub struct Client;
-
-impl Client {
-    /// Notify the server that the client has started successfully
-    pub fn notify(port: u16) -> Result<()> {
-        let mut stream = TcpStream::connect(("127.0.0.1", port))?;
-        stream.write_all(b"done")?;
-        Ok(())
-    }
-}
-
-/// Entry point for the test, waiting for the application to start
-pub struct Server {
-    listener: TcpListener,
-}
-
-impl Server {
-    /// Start new TCP server, waiting for the application's startup notification
-    pub fn new() -> Result<Server> {
-        Ok(Server {
-            listener: TcpListener::bind(("127.0.0.1", 0))?
-        })
-    }
-
-    /// Get the port number of the TCP Server
-    ///
-    /// This port number has to sent to the application.
-    pub fn port(&self) -> Result<u16> {
-        Ok(self.listener.local_addr()?.port())
-    }
-
-    /// Block the currently running thread until either the starting application has signaled its successful start or the `timeout` period has expired
-    ///
-    /// Returns the duration for which was waited or an error in case of a timeout or invalid startup notification.
-    pub fn wait(self, timeout: Duration) -> Result<Duration> {
-        self.listener.set_nonblocking(true)?;
+#[cfg(feature = "async")]
+pub mod asynchronous;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+#[cfg(feature = "async-std")]
+pub mod async_std;
+mod client;
+mod command;
+mod error;
+pub mod fixture;
+#[cfg(feature = "serde")]
+mod handshake;
+mod notification;
+mod orchestrator;
+pub mod probe;
+mod process;
+mod protocol;
+mod registry;
+mod server;
+mod shutdown;
+mod transport;
 
-        let start = Instant::now();
-        while start.elapsed() < timeout {
-            match self.listener.accept() {
-                Ok((mut stream, _)) => {
-                    let mut buf = Vec::new();
-                    stream.read_to_end(&mut buf)?;
-                    if buf == b"done" {
-                        return Ok(start.elapsed());
-                    } else {
-                        return Err(Error::new(ErrorKind::Other, "wrong startup notification received"));
-                    }
-                }
-                Err(ref e) if e.kind() == ErrorKind::WouldBlock => {}
-                Err(e) => return Err(e)
-            }
-            thread::sleep(Duration::from_millis(1));
-        }
-        Err(Error::new(ErrorKind::TimedOut, "did not receive startup notification"))
-    }
-}
+pub use crate::client::{Client, Heartbeat, ReadyGuard, Session};
+pub use crate::command::PatienceCommandExt;
+pub use crate::error::Error;
+#[cfg(feature = "serde")]
+pub use crate::handshake::Handshake;
+#[cfg(feature = "serde")]
+pub use crate::notification::Metadata;
+pub use crate::notification::{Notification, StartupFailure, WaitTimeout};
+pub use crate::orchestrator::{Orchestrator, ProcessSet, Service};
+pub use crate::process::{ChildGuard, EarlyExit};
+pub use crate::registry::Registry;
+pub use crate::server::{Duplicates, Notifications, Server, ServerBuilder, ServerHandle};
+#[cfg(all(feature = "mio", unix))]
+pub use crate::server::Poller;
+pub use crate::shutdown::ShutdownHandle;
+#[cfg(feature = "macros")]
+pub use test_patience_macros::test;
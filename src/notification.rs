@@ -0,0 +1,375 @@
+#[cfg(feature = "serde")]
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+use std::io::{Result, Error};
+#[cfg(feature = "serde")]
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::{self, Kind, Message, EXPECT_HEADER, NAME_HEADER, SENT_HEADER};
+
+/// Header carrying the JSON encoded metadata
+#[cfg(feature = "serde")]
+const METADATA_HEADER: &str = "metadata";
+
+/// Startup notification received from an application
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    elapsed: Duration,
+    overhead: Option<Duration>,
+    name: Option<String>,
+    expecting: usize,
+    payload: Vec<u8>,
+    #[cfg(feature = "serde")]
+    metadata: Option<Metadata>,
+}
+
+impl Notification {
+    /// Interpret a received `message`, which has to be either a startup notification or a failure
+    pub(crate) fn from_message(message: Message, elapsed: Duration) -> Result<Notification> {
+        match message.kind {
+            Kind::Ready => {}
+            Kind::Failure => {
+                let reason = String::from_utf8_lossy(&message.body).into_owned();
+                return Err(Error::other(StartupFailure { reason }));
+            }
+            Kind::Progress => return Err(protocol::invalid()),
+        }
+        let expecting = match message.get(EXPECT_HEADER) {
+            Some(expecting) => expecting.parse().map_err(|_| protocol::invalid())?,
+            None => 0,
+        };
+        let overhead = message.get(SENT_HEADER)
+            .and_then(protocol::parse_timestamp)
+            .and_then(|sent| SystemTime::now().duration_since(sent).ok());
+        Ok(Notification {
+            elapsed,
+            overhead,
+            name: message.get(NAME_HEADER).map(str::to_string),
+            expecting,
+            #[cfg(feature = "serde")]
+            metadata: message.metadata()?,
+            payload: message.body,
+        })
+    }
+
+    /// Get the duration for which was waited until the notification arrived
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Get the time between the application sending the notification and the server handing it to the test, i.e. the overhead of the synchronization itself
+    ///
+    /// This includes connecting to the server, the time the notification spent in the socket until the server woke up, and decoding it.
+    /// It tells by how much the tooling delays the measured `elapsed` duration, e.g. to show that it can be neglected compared to the startup of the application.
+    /// The overhead is measured using the system clocks of the application and the test, which have to agree,
+    /// so it's only meaningful for applications running on the same host or VMs with a synchronized clock.
+    /// Notifications only carry the point in time at which they were sent if the server asked the application for it using `Server::measure_overhead`.
+    /// Returns `None` for notifications without it, like those of older versions of this crate, sentinel files, marker lines and signals,
+    /// or if the clock of the application is ahead.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// let server = test_patience::Server::new().unwrap().measure_overhead(true);
+    /// // start the application, passing it `server.env()`
+    /// let notification = server.wait(Duration::from_secs(5)).unwrap();
+    /// println!("application started after {:?}, measured with an overhead of {:?}", notification.elapsed(), notification.overhead());
+    /// ```
+    pub fn overhead(&self) -> Option<Duration> {
+        self.overhead
+    }
+
+    /// Get the name of the service sent by the application using `Client::notify_named`
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Get the number of further startup notifications announced by the application using `Client::notify_expecting`
+    pub fn expecting(&self) -> usize {
+        self.expecting
+    }
+
+    /// Get the payload sent by the application using `Client::notify_with_payload` or `Client::notify_file_with_payload`
+    ///
+    /// The payload is empty if the application didn't send any.
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    /// Get the metadata sent by the application using `Client::notify_with_metadata`
+    #[cfg(feature = "serde")]
+    pub fn metadata(&self) -> Option<&Metadata> {
+        self.metadata.as_ref()
+    }
+}
+
+/// Error reported by an application which failed to start using `Client::notify_failure`
+///
+/// `Server::wait` returns it wrapped in an `io::Error`, from which it can be retrieved using `get_ref` and `downcast_ref`.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use test_patience::StartupFailure;
+///
+/// # let server = test_patience::Server::new().unwrap();
+/// if let Err(e) = server.wait(Duration::from_secs(5)) {
+///     if let Some(failure) = e.get_ref().and_then(|e| e.downcast_ref::<StartupFailure>()) {
+///         panic!("application failed to start: {}", failure.reason());
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StartupFailure {
+    reason: String,
+}
+
+impl StartupFailure {
+    /// Get the reason sent by the application
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+impl fmt::Display for StartupFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "application failed to start: {}", self.reason)
+    }
+}
+
+impl error::Error for StartupFailure {}
+
+/// Error returned by `Server::wait` and its variants if no startup notification arrived within the timeout period
+///
+/// It's wrapped in an `io::Error` of kind `TimedOut`, from which it can be retrieved using `get_ref` and `downcast_ref`.
+/// Waits for several notifications, services or applications at the startup barrier tell how many of them were received and which are missing.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use test_patience::WaitTimeout;
+///
+/// let server = test_patience::Server::new().unwrap();
+/// if let Err(e) = server.wait(Duration::from_secs(5)) {
+///     if let Some(timeout) = e.get_ref().and_then(|e| e.downcast_ref::<WaitTimeout>()) {
+///         panic!("no notification after {:?}, {} connections accepted", timeout.elapsed(), timeout.connections());
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WaitTimeout {
+    timeout: Duration,
+    elapsed: Duration,
+    local_addr: Option<SocketAddr>,
+    connections: usize,
+    progress: Option<String>,
+    output: Vec<String>,
+    received: usize,
+    missing: usize,
+    missing_services: Vec<String>,
+    group: Option<String>,
+    subject: Subject,
+}
+
+/// What a timed out wait was waiting for, which determines the message of its `WaitTimeout`
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Subject {
+    /// A single startup notification
+    Notification,
+    /// A number of startup notifications, see `Server::wait_for_n`
+    Notifications,
+    /// Named services, with those which have exceeded their own timeout, see `Server::expect_within`
+    Services(Vec<(String, Duration)>),
+    /// Applications arriving at the startup barrier, see `Server::barrier`
+    Barrier,
+}
+
+impl WaitTimeout {
+    pub(crate) fn new(timeout: Duration, elapsed: Duration, local_addr: Option<SocketAddr>, connections: usize, progress: Option<String>) -> WaitTimeout {
+        WaitTimeout {
+            timeout,
+            elapsed,
+            local_addr,
+            connections,
+            progress,
+            output: Vec::new(),
+            received: 0,
+            missing: 1,
+            missing_services: Vec::new(),
+            group: None,
+            subject: Subject::Notification,
+        }
+    }
+
+    /// Turn this into the timeout of a wait for several notifications, of which `received` arrived and `missing` didn't
+    pub(crate) fn notifications(mut self, received: usize, missing: usize) -> WaitTimeout {
+        self.received = received;
+        self.missing = missing;
+        self.subject = Subject::Notifications;
+        self
+    }
+
+    /// Turn this into the timeout of a wait for the named services, of which `received` arrived and the `missing` ones didn't
+    ///
+    /// The `overdue` services have exceeded their own timeout. If there are any, the message only names them.
+    pub(crate) fn services(mut self, received: usize, missing: Vec<String>, overdue: Vec<(String, Duration)>) -> WaitTimeout {
+        self.received = received;
+        self.missing = missing.len();
+        self.missing_services = missing;
+        self.subject = Subject::Services(overdue);
+        self
+    }
+
+    /// Turn this into the timeout of a startup barrier, at which `arrived` applications arrived and `missing` didn't
+    pub(crate) fn barrier(mut self, arrived: usize, missing: usize) -> WaitTimeout {
+        self.received = arrived;
+        self.missing = missing;
+        self.subject = Subject::Barrier;
+        self
+    }
+
+    /// Get the configured timeout
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Get the duration since the call of `wait`
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Get the address the server was listening on, or `None` if it doesn't listen on a TCP or UDP port
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// Get the number of connections accepted (or datagrams received) by the server since it was created
+    ///
+    /// If this is 0, the application most likely never tried to notify the test, e.g. because it was passed the wrong port.
+    pub fn connections(&self) -> usize {
+        self.connections
+    }
+
+    /// Get the last progress update sent by the application, if any
+    pub fn progress(&self) -> Option<&str> {
+        self.progress.as_deref()
+    }
+
+    /// Get the last lines printed by the application, if its output was captured using `Server::capture_output`
+    pub fn output(&self) -> &[String] {
+        &self.output
+    }
+
+    /// Get the number of startup notifications received by the wait, or of applications which arrived at the startup barrier
+    pub fn received(&self) -> usize {
+        self.received
+    }
+
+    /// Get the number of startup notifications, services or applications at the startup barrier which were still missing
+    ///
+    /// This is 1 for waits for a single notification.
+    pub fn missing(&self) -> usize {
+        self.missing
+    }
+
+    /// Get the names of the services which haven't signaled their start, if named services were waited for using `wait_all` or `wait_group`
+    pub fn missing_services(&self) -> &[String] {
+        &self.missing_services
+    }
+
+    /// Get the name of the group waited for, if the wait was started using `wait_group`
+    pub fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    pub(crate) fn set_output(&mut self, output: Vec<String>) {
+        self.output = output;
+    }
+
+    pub(crate) fn set_group(&mut self, group: &str) {
+        self.group = Some(group.to_string());
+    }
+
+    /// Write the duration waited, address, connections and last progress update
+    fn fmt_details(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, " within {:?} (waited {:?}", self.timeout, self.elapsed)?;
+        if let Some(addr) = self.local_addr {
+            write!(f, " on {}", addr)?;
+        }
+        write!(f, ", {} connections accepted", self.connections)?;
+        if let Some(ref progress) = self.progress {
+            write!(f, ", last progress: {}", progress)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl fmt::Display for WaitTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.subject {
+            Subject::Notification => {
+                write!(f, "did not receive startup notification")?;
+                self.fmt_details(f)?;
+            }
+            Subject::Notifications => {
+                write!(f, "received {} of {} startup notifications", self.received, self.received + self.missing)?;
+                self.fmt_details(f)?;
+            }
+            Subject::Services(ref overdue) if overdue.is_empty() => {
+                write!(f, "did not receive startup notification of {}", self.missing_services.join(", "))?;
+            }
+            Subject::Services(ref overdue) => {
+                let overdue: Vec<_> = overdue.iter().map(|(name, timeout)| format!("{} within {:?}", name, timeout)).collect();
+                write!(f, "did not receive startup notification of {}", overdue.join(", "))?;
+            }
+            Subject::Barrier => {
+                write!(f, "{} of {} applications arrived at the startup barrier", self.received, self.received + self.missing)?;
+                self.fmt_details(f)?;
+            }
+        }
+        if let Some(ref group) = self.group {
+            write!(f, " in group {}", group)?;
+        }
+        if !self.output.is_empty() {
+            write!(f, "\nlast output of the application:\n{}", self.output.join("\n"))?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for WaitTimeout {}
+
+/// Structured information about the application, sent along with its startup notification
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Metadata {
+    /// Name of the service
+    pub service: Option<String>,
+    /// Version of the service
+    pub version: Option<String>,
+    /// Process id of the application
+    pub pid: Option<u32>,
+    /// Arbitrary key/value pairs
+    pub properties: BTreeMap<String, String>,
+}
+
+#[cfg(feature = "serde")]
+impl Message {
+    pub(crate) fn with_metadata(self, metadata: &Metadata) -> Result<Message> {
+        let json = serde_json::to_string(metadata)
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+        Ok(self.header(METADATA_HEADER, json))
+    }
+
+    fn metadata(&self) -> Result<Option<Metadata>> {
+        self.get(METADATA_HEADER)
+            .map(|json| serde_json::from_str(json)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, format!("invalid metadata in startup notification: {}", e))))
+            .transpose()
+    }
+}
@@ -0,0 +1,184 @@
+//! Startup of several applications in the order of their dependencies
+
+use std::io::{Result, Error, ErrorKind};
+use std::process::{Child, Command};
+use std::time::Duration;
+
+use crate::{ChildGuard, Notification, Server};
+use crate::process;
+
+/// Application started by an `Orchestrator`, once the applications it depends on are ready
+pub struct Service {
+    name: String,
+    command: Command,
+    timeout: Duration,
+    after: Vec<String>,
+}
+
+impl Service {
+    /// Describe the application `name`, which is spawned using `command` and has to signal its start within 5 seconds
+    pub fn new(name: &str, command: Command) -> Service {
+        Service { name: name.to_string(), command, timeout: Duration::from_secs(5), after: Vec::new() }
+    }
+
+    /// Set the `timeout` period within which the application has to signal its start
+    pub fn timeout(mut self, timeout: Duration) -> Service {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Don't start the application until the service `name` is ready
+    pub fn after(mut self, name: &str) -> Service {
+        self.after.push(name.to_string());
+        self
+    }
+}
+
+/// Starts several applications in the order of their dependencies, waiting for each of them to signal its start
+///
+/// Every application gets its own server, which it's told about by `Server::spawn`.
+/// An application is only spawned once all services it depends on (see `Service::after`) are ready.
+///
+/// ```no_run
+/// use std::process::Command;
+/// use std::time::Duration;
+///
+/// use test_patience::{Orchestrator, Service};
+///
+/// let services = Orchestrator::new()
+///     .service(Service::new("api", Command::new("path/to/api")).after("db"))
+///     .service(Service::new("db", Command::new("path/to/db")).timeout(Duration::from_secs(30)))
+///     .start()
+///     .unwrap();
+/// ```
+#[derive(Default)]
+pub struct Orchestrator {
+    services: Vec<Service>,
+}
+
+impl Orchestrator {
+    /// Create an orchestrator without any services
+    pub fn new() -> Orchestrator {
+        Orchestrator::default()
+    }
+
+    /// Add a `service` to be started
+    pub fn service(mut self, service: Service) -> Orchestrator {
+        self.services.push(service);
+        self
+    }
+
+    /// Start all services in the order of their dependencies
+    ///
+    /// Returns the name, child process and notification of every service, in the order they were started.
+    /// The children keep running until they are killed by the caller.
+    /// If a service fails to start, the services started before are killed and an error naming the service is returned.
+    /// An error of kind `InvalidInput` is returned if a service depends on an unknown service or the dependencies contain a cycle.
+    pub fn start(self) -> Result<Vec<(String, Child, Notification)>> {
+        let order = self.order()?;
+        let mut services: Vec<_> = self.services.into_iter().map(Some).collect();
+        let mut started = Vec::with_capacity(order.len());
+        for i in order {
+            let service = services[i].take().expect("service started twice");
+            match start(service) {
+                Ok(result) => started.push(result),
+                Err(e) => {
+                    for (_, mut child, _) in started {
+                        process::kill(&mut child);
+                        let _ = child.wait();
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(started)
+    }
+
+    /// Start all services like `start` and hand them to a `ProcessSet`, which kills them when it's dropped
+    pub fn start_set(self) -> Result<ProcessSet> {
+        let services = self.start()?.into_iter()
+            .map(|(name, child, notification)| (name, ChildGuard::new(child), notification))
+            .collect();
+        Ok(ProcessSet { services })
+    }
+
+    /// Sort the services topologically, keeping the order in which they were added where possible
+    fn order(&self) -> Result<Vec<usize>> {
+        for service in &self.services {
+            if let Some(unknown) = service.after.iter().find(|name| !self.services.iter().any(|other| other.name == **name)) {
+                let message = format!("service {} depends on unknown service {}", service.name, unknown);
+                return Err(Error::new(ErrorKind::InvalidInput, message));
+            }
+        }
+        let mut order: Vec<usize> = Vec::with_capacity(self.services.len());
+        while order.len() < self.services.len() {
+            let next = (0..self.services.len()).find(|&i| {
+                !order.contains(&i) && self.services[i].after.iter()
+                    .all(|name| order.iter().any(|&j| self.services[j].name == *name))
+            });
+            match next {
+                Some(i) => order.push(i),
+                None => return Err(Error::new(ErrorKind::InvalidInput, "dependencies of the services contain a cycle")),
+            }
+        }
+        Ok(order)
+    }
+}
+
+/// Services started by `Orchestrator::start_set`, which are torn down in reverse order of their start when the set is dropped
+///
+/// Services are killed before the services they depend on, so they don't log spurious errors about their dependencies going away.
+///
+/// ```no_run
+/// use std::process::Command;
+///
+/// use test_patience::{Orchestrator, Service};
+///
+/// let services = Orchestrator::new()
+///     .service(Service::new("db", Command::new("path/to/db")))
+///     .service(Service::new("api", Command::new("path/to/api")).after("db"))
+///     .start_set()
+///     .unwrap();
+/// println!("api running as process {}", services.get("api").unwrap().id());
+/// ```
+pub struct ProcessSet {
+    services: Vec<(String, ChildGuard, Notification)>,
+}
+
+impl ProcessSet {
+    /// Get the child process of the service `name`
+    pub fn get(&self, name: &str) -> Option<&ChildGuard> {
+        self.services.iter().find(|(other, _, _)| other == name).map(|(_, child, _)| child)
+    }
+
+    /// Get mutable access to the child process of the service `name`
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut ChildGuard> {
+        self.services.iter_mut().find(|(other, _, _)| other == name).map(|(_, child, _)| child)
+    }
+
+    /// Get the startup notification of the service `name`
+    pub fn notification(&self, name: &str) -> Option<&Notification> {
+        self.services.iter().find(|(other, _, _)| other == name).map(|(_, _, notification)| notification)
+    }
+
+    /// Get the names of the services, in the order they were started
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.services.iter().map(|(name, _, _)| name.as_str())
+    }
+}
+
+impl Drop for ProcessSet {
+    fn drop(&mut self) {
+        while let Some(service) = self.services.pop() {
+            drop(service);
+        }
+    }
+}
+
+/// Spawn the application of `service` and wait for its startup notification
+fn start(service: Service) -> Result<(String, Child, Notification)> {
+    let Service { name, mut command, timeout, .. } = service;
+    let context = |e: Error| Error::new(e.kind(), format!("service {} failed to start: {}", name, e));
+    let (child, notification) = Server::new()?.spawn_and_wait(&mut command, timeout).map_err(context)?;
+    Ok((name, child, notification))
+}
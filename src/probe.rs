@@ -0,0 +1,559 @@
+//! Readiness probes for applications that can't send a startup notification
+//!
+//! Off-the-shelf services like nginx or redis can't be changed to call [`Client::notify`](../struct.Client.html#method.notify).
+//! Instead of creating a `Server`, the test can use a probe, which repeatedly checks a condition (e.g. whether a port accepts connections)
+//! until it holds or a timeout has expired.
+//! The probes in this module block the current thread, see [`asynchronous::probe`](../asynchronous/probe/index.html) for probes which don't block an executor.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use test_patience::probe::{self, Probe};
+//!
+//! # #[allow(unused_variables)]
+//! let elapsed = probe::tcp("127.0.0.1:6379").wait(Duration::from_secs(5)).unwrap();
+//! ```
+//!
+//! Applications exposing a health endpoint can be probed using `http`, which waits for the expected status and optionally a text in the response body.
+//! Tools writing a pid file or creating a socket on startup can be waited for using `file`.
+//! On Unix platforms, daemons exposing a control socket can be waited for using `unix`, which also makes sure the socket accepts connections.
+//! Services shipping a readiness tool like `pg_isready` can be waited for using `command`.
+//! Any other condition can be waited for using `custom`.
+//! Several probes can be combined into a single one using `all` and `any`.
+//! The delays between the checks of a probe can be changed to an exponential backoff with jitter using `Probe::with_backoff`.
+//! With the `docker` feature, `docker` waits for a container started by the test to pass its healthcheck.
+//! With the `regex` feature, `log` waits for a line printed by the application to match a regular expression, e.g. `Listening on .*:\d+`.
+
+use std::fs;
+use std::io::{Result, Error, ErrorKind};
+use std::net::{TcpStream, SocketAddr, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::{Instant, Duration};
+
+pub(crate) mod backoff;
+mod http;
+#[cfg(feature = "regex")]
+mod log;
+
+pub use self::backoff::Backoff;
+pub use self::http::{http, Http};
+#[cfg(feature = "regex")]
+pub use self::log::{log, Log, LogMatch};
+
+/// Time after which a single connection attempt (or read of an HTTP response) is given up
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Condition that is checked repeatedly until it holds
+pub trait Probe {
+    /// Check once whether the condition holds
+    ///
+    /// An error means that the condition does not hold (yet). The last error is reported if the probe times out.
+    fn check(&mut self) -> Result<()>;
+
+    /// Get the duration to sleep between two checks
+    fn check_interval(&self) -> Duration {
+        Duration::from_millis(10)
+    }
+
+    /// Get the policy for the delays between the checks, a fixed `check_interval` by default
+    fn backoff(&self) -> Backoff {
+        Backoff::fixed(self.check_interval())
+    }
+
+    /// Get a description of the probe, which names it in the errors of `all` and `any`
+    fn describe(&self) -> String {
+        "probe".to_string()
+    }
+
+    /// Check the condition until it holds or the `timeout` period has expired
+    ///
+    /// Returns the duration for which was waited or an error in case of a timeout.
+    fn wait(mut self, timeout: Duration) -> Result<Duration>
+        where Self: Sized
+    {
+        let start = Instant::now();
+        let mut delays = self.backoff().delays();
+        loop {
+            let error = match self.check() {
+                Ok(()) => return Ok(start.elapsed()),
+                Err(e) => e,
+            };
+            let elapsed = start.elapsed();
+            if elapsed >= timeout {
+                return Err(Error::new(ErrorKind::TimedOut, format!("probe did not succeed ({})", error)));
+            }
+            let delay = delays.next().unwrap_or_default();
+            thread::sleep(delay.min(timeout - elapsed));
+        }
+    }
+
+    /// Replace the delays between the checks by the ones of the `backoff` policy
+    fn with_backoff(self, backoff: Backoff) -> WithBackoff<Self>
+        where Self: Sized
+    {
+        WithBackoff { probe: self, backoff }
+    }
+}
+
+/// Probe created by [`Probe::with_backoff`](trait.Probe.html#method.with_backoff)
+#[derive(Debug)]
+pub struct WithBackoff<P> {
+    probe: P,
+    backoff: Backoff,
+}
+
+impl<P: Probe> Probe for WithBackoff<P> {
+    fn check(&mut self) -> Result<()> {
+        self.probe.check()
+    }
+
+    fn backoff(&self) -> Backoff {
+        self.backoff
+    }
+
+    fn describe(&self) -> String {
+        self.probe.describe()
+    }
+}
+
+/// Probe created by [`tcp`](fn.tcp.html)
+#[derive(Debug)]
+pub struct Tcp {
+    addrs: Result<Vec<SocketAddr>>,
+    interval: Duration,
+}
+
+/// Create a probe which succeeds as soon as the address accepts TCP connections
+pub fn tcp<A: ToSocketAddrs>(addr: A) -> Tcp {
+    Tcp {
+        addrs: addr.to_socket_addrs().map(Iterator::collect),
+        interval: Duration::from_millis(10),
+    }
+}
+
+impl Tcp {
+    /// Set the duration to sleep between two connection attempts
+    pub fn interval(mut self, interval: Duration) -> Tcp {
+        self.interval = interval;
+        self
+    }
+}
+
+impl Probe for Tcp {
+    fn check(&mut self) -> Result<()> {
+        let addrs = match self.addrs {
+            Ok(ref addrs) => addrs,
+            Err(ref e) => return Err(Error::new(e.kind(), e.to_string())),
+        };
+        let mut error = Error::new(ErrorKind::InvalidInput, "could not resolve to any address");
+        for addr in addrs {
+            match TcpStream::connect_timeout(addr, CONNECT_TIMEOUT) {
+                Ok(_) => return Ok(()),
+                Err(e) => error = e,
+            }
+        }
+        Err(error)
+    }
+
+    fn check_interval(&self) -> Duration {
+        self.interval
+    }
+
+    fn describe(&self) -> String {
+        match self.addrs {
+            Ok(ref addrs) if !addrs.is_empty() => format!("TCP probe of {}", addrs[0]),
+            _ => "TCP probe".to_string(),
+        }
+    }
+}
+
+/// Probe created by [`unix`](fn.unix.html)
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct Unix {
+    path: PathBuf,
+    interval: Duration,
+}
+
+/// Create a probe which succeeds as soon as the Unix domain socket at `path` accepts connections
+///
+/// Unlike `file`, this doesn't succeed between the creation of the socket and the application starting to listen on it.
+#[cfg(unix)]
+pub fn unix<P: AsRef<Path>>(path: P) -> Unix {
+    Unix {
+        path: path.as_ref().to_path_buf(),
+        interval: Duration::from_millis(10),
+    }
+}
+
+#[cfg(unix)]
+impl Unix {
+    /// Set the duration to sleep between two connection attempts
+    pub fn interval(mut self, interval: Duration) -> Unix {
+        self.interval = interval;
+        self
+    }
+}
+
+#[cfg(unix)]
+impl Probe for Unix {
+    fn check(&mut self) -> Result<()> {
+        UnixStream::connect(&self.path).map(drop)
+    }
+
+    fn check_interval(&self) -> Duration {
+        self.interval
+    }
+
+    fn describe(&self) -> String {
+        format!("probe of {}", self.path.display())
+    }
+}
+
+/// Probe created by [`file`](fn.file.html)
+#[derive(Debug)]
+pub struct File {
+    path: PathBuf,
+    non_empty: bool,
+    interval: Duration,
+}
+
+/// Create a probe which succeeds as soon as `path` exists, e.g. a pid file or a Unix domain socket created by the application on startup
+pub fn file<P: AsRef<Path>>(path: P) -> File {
+    File {
+        path: path.as_ref().to_path_buf(),
+        non_empty: false,
+        interval: Duration::from_millis(10),
+    }
+}
+
+impl File {
+    /// Also wait until the file isn't empty anymore
+    ///
+    /// This keeps the test from reading a file which the application has created, but not written yet.
+    pub fn non_empty(mut self) -> File {
+        self.non_empty = true;
+        self
+    }
+
+    /// Set the duration to sleep between two checks
+    pub fn interval(mut self, interval: Duration) -> File {
+        self.interval = interval;
+        self
+    }
+}
+
+impl Probe for File {
+    fn check(&mut self) -> Result<()> {
+        let metadata = fs::metadata(&self.path)?;
+        if self.non_empty && metadata.len() == 0 {
+            return Err(Error::other(format!("{} is empty", self.path.display())));
+        }
+        Ok(())
+    }
+
+    fn check_interval(&self) -> Duration {
+        self.interval
+    }
+
+    fn describe(&self) -> String {
+        format!("probe of {}", self.path.display())
+    }
+}
+
+/// Probe created by [`command`](fn.command.html)
+#[derive(Debug)]
+pub struct Command {
+    command: process::Command,
+    capture_output: bool,
+    interval: Duration,
+}
+
+/// Create a probe which runs `command` until it exits successfully
+///
+/// The output of the command is captured, so it doesn't clutter the output of the test.
+///
+/// ```no_run
+/// use std::process::Command;
+/// use std::time::Duration;
+/// use test_patience::probe::{self, Probe};
+///
+/// let mut command = Command::new("pg_isready");
+/// command.args(&["-h", "127.0.0.1"]);
+/// probe::command(command).wait(Duration::from_secs(30)).unwrap();
+/// ```
+pub fn command(command: process::Command) -> Command {
+    Command {
+        command,
+        capture_output: false,
+        interval: Duration::from_millis(100),
+    }
+}
+
+impl Command {
+    /// Include the output of the last failed run in the timeout error
+    pub fn capture_output(mut self) -> Command {
+        self.capture_output = true;
+        self
+    }
+
+    /// Set the duration to sleep between two runs of the command, 100 milliseconds by default
+    pub fn interval(mut self, interval: Duration) -> Command {
+        self.interval = interval;
+        self
+    }
+}
+
+impl Probe for Command {
+    fn check(&mut self) -> Result<()> {
+        let output = self.command.output()?;
+        if output.status.success() {
+            return Ok(());
+        }
+        let mut message = format!("command failed with {}", output.status);
+        if self.capture_output {
+            for text in [&output.stdout, &output.stderr].iter() {
+                let text = String::from_utf8_lossy(text);
+                if !text.trim_end().is_empty() {
+                    message.push('\n');
+                    message.push_str(text.trim_end());
+                }
+            }
+        }
+        Err(Error::other(message))
+    }
+
+    fn check_interval(&self) -> Duration {
+        self.interval
+    }
+
+    fn describe(&self) -> String {
+        format!("probe running {:?}", self.command.get_program())
+    }
+}
+
+/// Probe created by [`docker`](fn.docker.html)
+#[cfg(feature = "docker")]
+#[derive(Debug)]
+pub struct Docker {
+    container: String,
+    interval: Duration,
+}
+
+/// Create a probe which succeeds as soon as the Docker container `container` reports its state as `healthy`
+///
+/// The health status is queried using `docker inspect`, so the `docker` command has to be available.
+/// The container needs a healthcheck, either from its image or passed to `docker run` using `--health-cmd`.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use test_patience::probe::{self, Probe};
+///
+/// probe::docker("test-postgres").wait(Duration::from_secs(60)).unwrap();
+/// ```
+#[cfg(feature = "docker")]
+pub fn docker(container: &str) -> Docker {
+    Docker {
+        container: container.to_string(),
+        interval: Duration::from_millis(500),
+    }
+}
+
+#[cfg(feature = "docker")]
+impl Docker {
+    /// Set the duration to sleep between two queries of the health status, 500 milliseconds by default
+    pub fn interval(mut self, interval: Duration) -> Docker {
+        self.interval = interval;
+        self
+    }
+}
+
+#[cfg(feature = "docker")]
+impl Probe for Docker {
+    fn check(&mut self) -> Result<()> {
+        let output = process::Command::new("docker")
+            .args(["inspect", "--format", "{{if .State.Health}}{{.State.Health.Status}}{{else}}without healthcheck{{end}}"])
+            .arg(&self.container)
+            .output()
+            .map_err(|e| Error::new(e.kind(), format!("failed to run docker: {}", e)))?;
+        if !output.status.success() {
+            return Err(Error::other(String::from_utf8_lossy(&output.stderr).trim_end().to_string()));
+        }
+        match String::from_utf8_lossy(&output.stdout).trim() {
+            "healthy" => Ok(()),
+            status => Err(Error::other(format!("container is {}", status))),
+        }
+    }
+
+    fn check_interval(&self) -> Duration {
+        self.interval
+    }
+
+    fn describe(&self) -> String {
+        format!("probe of container {}", self.container)
+    }
+}
+
+/// Probe created by [`custom`](fn.custom.html)
+pub struct Custom<F> {
+    check: F,
+    interval: Duration,
+}
+
+/// Create a probe which succeeds as soon as `check` returns `true`, checking every `interval`
+///
+/// Arbitrary conditions can be waited for this way, e.g. a row showing up in a database, without writing another sleep loop.
+///
+/// ```no_run
+/// use std::path::Path;
+/// use std::time::Duration;
+/// use test_patience::probe::{self, Probe};
+///
+/// probe::custom(|| Path::new("/tmp/application.log").exists(), Duration::from_millis(10))
+///     .wait(Duration::from_secs(5))
+///     .unwrap();
+/// ```
+pub fn custom<F: FnMut() -> bool>(check: F, interval: Duration) -> Custom<F> {
+    Custom { check, interval }
+}
+
+impl<F: FnMut() -> bool> Probe for Custom<F> {
+    fn check(&mut self) -> Result<()> {
+        if (self.check)() {
+            Ok(())
+        } else {
+            Err(Error::other("condition does not hold"))
+        }
+    }
+
+    fn check_interval(&self) -> Duration {
+        self.interval
+    }
+
+    fn describe(&self) -> String {
+        "custom probe".to_string()
+    }
+}
+
+impl<P: Probe + ?Sized> Probe for Box<P> {
+    fn check(&mut self) -> Result<()> {
+        (**self).check()
+    }
+
+    fn check_interval(&self) -> Duration {
+        (**self).check_interval()
+    }
+
+    fn backoff(&self) -> Backoff {
+        (**self).backoff()
+    }
+
+    fn describe(&self) -> String {
+        (**self).describe()
+    }
+}
+
+/// Probe created by [`all`](fn.all.html)
+pub struct All {
+    probes: Vec<Option<Box<dyn Probe>>>,
+    interval: Duration,
+}
+
+/// Create a probe which succeeds as soon as all of the `probes` have succeeded
+///
+/// Probes which have succeeded once aren't checked again.
+/// The check intervals and backoff policies of the probes are replaced by the ones of the combined probe.
+/// Its errors name the first probe which didn't succeed.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use test_patience::probe::{self, Probe};
+///
+/// probe::all(vec![Box::new(probe::http("http://127.0.0.1:8080/healthz")), Box::new(probe::file("/tmp/migrations.done"))])
+///     .wait(Duration::from_secs(5))
+///     .unwrap();
+/// ```
+pub fn all(probes: Vec<Box<dyn Probe>>) -> All {
+    All {
+        probes: probes.into_iter().map(Some).collect(),
+        interval: Duration::from_millis(10),
+    }
+}
+
+impl All {
+    /// Set the duration to sleep between two checks
+    pub fn interval(mut self, interval: Duration) -> All {
+        self.interval = interval;
+        self
+    }
+}
+
+impl Probe for All {
+    fn check(&mut self) -> Result<()> {
+        for slot in self.probes.iter_mut() {
+            if let Some(ref mut probe) = *slot {
+                if let Err(e) = probe.check() {
+                    return Err(Error::new(e.kind(), format!("{}: {}", probe.describe(), e)));
+                }
+                *slot = None;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_interval(&self) -> Duration {
+        self.interval
+    }
+
+    fn describe(&self) -> String {
+        "combined probe".to_string()
+    }
+}
+
+/// Probe created by [`any`](fn.any.html)
+pub struct Any {
+    probes: Vec<Box<dyn Probe>>,
+    interval: Duration,
+}
+
+/// Create a probe which succeeds as soon as any of the `probes` succeeds, e.g. one of several replicas being reachable
+///
+/// The check intervals and backoff policies of the probes are replaced by the ones of the combined probe.
+/// Its errors list why each of the probes didn't succeed.
+pub fn any(probes: Vec<Box<dyn Probe>>) -> Any {
+    Any { probes, interval: Duration::from_millis(10) }
+}
+
+impl Any {
+    /// Set the duration to sleep between two checks
+    pub fn interval(mut self, interval: Duration) -> Any {
+        self.interval = interval;
+        self
+    }
+}
+
+impl Probe for Any {
+    fn check(&mut self) -> Result<()> {
+        let mut errors = Vec::with_capacity(self.probes.len());
+        for probe in self.probes.iter_mut() {
+            match probe.check() {
+                Ok(()) => return Ok(()),
+                Err(e) => errors.push(format!("{}: {}", probe.describe(), e)),
+            }
+        }
+        Err(Error::other(errors.join("; ")))
+    }
+
+    fn check_interval(&self) -> Duration {
+        self.interval
+    }
+
+    fn describe(&self) -> String {
+        "combined probe".to_string()
+    }
+}
@@ -0,0 +1,76 @@
+//! Backoff policy, determining the delays between the checks of a probe
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// Policy for the delays between two checks of a probe
+///
+/// Polling at a high frequency makes tests fast on a developer machine, but it can put a considerable load on slow CI machines.
+/// An exponential backoff starts checking often and slows down during a long startup, and jitter keeps several probes from checking in lockstep.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use test_patience::probe::{self, Backoff, Probe};
+///
+/// probe::tcp("127.0.0.1:5432")
+///     .with_backoff(Backoff::exponential(Duration::from_millis(10), Duration::from_secs(1)).jitter())
+///     .wait(Duration::from_secs(30))
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    interval: Duration,
+    max_interval: Option<Duration>,
+    jitter: bool,
+}
+
+impl Backoff {
+    /// Sleep for the same `interval` after every check
+    pub fn fixed(interval: Duration) -> Backoff {
+        Backoff { interval, max_interval: None, jitter: false }
+    }
+
+    /// Sleep for `initial` after the first check, doubling the duration after every check up to `max_interval`
+    pub fn exponential(initial: Duration, max_interval: Duration) -> Backoff {
+        Backoff { interval: initial, max_interval: Some(max_interval), jitter: false }
+    }
+
+    /// Shorten every delay by a random amount of up to half of it
+    pub fn jitter(mut self) -> Backoff {
+        self.jitter = true;
+        self
+    }
+
+    /// Get the delays after the checks, starting with the delay after the first check
+    pub(crate) fn delays(self) -> Delays {
+        Delays { backoff: self, interval: self.interval, random: RandomState::new(), i: 0 }
+    }
+}
+
+/// Delays of a `Backoff` policy, which never end
+pub(crate) struct Delays {
+    backoff: Backoff,
+    /// Delay after the next check, before applying jitter
+    interval: Duration,
+    random: RandomState,
+    i: u64,
+}
+
+impl Iterator for Delays {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let mut delay = self.interval;
+        if let Some(max_interval) = self.backoff.max_interval {
+            self.interval = (self.interval * 2).min(max_interval);
+        }
+        if self.backoff.jitter {
+            let mut hasher = self.random.build_hasher();
+            hasher.write_u64(self.i);
+            delay -= (delay / 2).mul_f64(hasher.finish() as f64 / u64::MAX as f64);
+        }
+        self.i += 1;
+        Some(delay)
+    }
+}
@@ -0,0 +1,130 @@
+//! HTTP probe, polling a health endpoint using plain HTTP/1.1 requests
+
+use std::io::{Result, Error, ErrorKind};
+use std::io::prelude::*;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use super::{Probe, CONNECT_TIMEOUT};
+
+/// Probe created by [`http`](fn.http.html)
+#[derive(Debug)]
+pub struct Http {
+    url: String,
+    target: Result<(String, String)>,
+    status: u16,
+    body: Option<String>,
+    interval: Duration,
+}
+
+/// Create a probe which succeeds as soon as `url` returns the status 200
+///
+/// Only plain `http://` URLs are supported, which is what health endpoints of local test services usually use.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use test_patience::probe::{self, Probe};
+///
+/// probe::http("http://127.0.0.1:8080/healthz")
+///     .body_contains("ok")
+///     .wait(Duration::from_secs(5))
+///     .unwrap();
+/// ```
+pub fn http(url: &str) -> Http {
+    Http {
+        url: url.to_string(),
+        target: split_url(url),
+        status: 200,
+        body: None,
+        interval: Duration::from_millis(10),
+    }
+}
+
+impl Http {
+    /// Set the `status` code to wait for
+    pub fn status(mut self, status: u16) -> Http {
+        self.status = status;
+        self
+    }
+
+    /// Also wait until the response body contains `text`
+    pub fn body_contains(mut self, text: &str) -> Http {
+        self.body = Some(text.to_string());
+        self
+    }
+
+    /// Set the duration to sleep between two requests
+    pub fn interval(mut self, interval: Duration) -> Http {
+        self.interval = interval;
+        self
+    }
+}
+
+impl Probe for Http {
+    fn check(&mut self) -> Result<()> {
+        let (host, path) = match self.target {
+            Ok((ref host, ref path)) => (host, path),
+            Err(ref e) => return Err(Error::new(e.kind(), e.to_string())),
+        };
+        let response = get(host, path)?;
+        let status = response.split(|&b| b == b' ').nth(1)
+            .and_then(|code| std::str::from_utf8(code).ok())
+            .and_then(|code| code.parse::<u16>().ok())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "invalid HTTP response"))?;
+        if status != self.status {
+            return Err(Error::other(format!("received status {}", status)));
+        }
+        if let Some(ref body) = self.body {
+            let start = response.windows(4).position(|w| w == b"\r\n\r\n").map_or(response.len(), |i| i + 4);
+            if !String::from_utf8_lossy(&response[start..]).contains(body.as_str()) {
+                return Err(Error::other(format!("response body does not contain {:?}", body)));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_interval(&self) -> Duration {
+        self.interval
+    }
+
+    fn describe(&self) -> String {
+        format!("probe of {}", self.url)
+    }
+}
+
+/// Split `url` into the host including the port and the path
+fn split_url(url: &str) -> Result<(String, String)> {
+    let rest = url.strip_prefix("http://")
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, format!("unsupported URL {}, only http:// URLs can be probed", url)))?;
+    let (host, path) = match rest.find('/') {
+        Some(i) => rest.split_at(i),
+        None => (rest, "/"),
+    };
+    if host.is_empty() {
+        return Err(Error::new(ErrorKind::InvalidInput, format!("URL {} has no host", url)));
+    }
+    let host = if host.contains(':') { host.to_string() } else { format!("{}:80", host) };
+    Ok((host, path.to_string()))
+}
+
+/// Send a GET request for `path` to `host` and read the whole response
+fn get(host: &str, path: &str) -> Result<Vec<u8>> {
+    let mut error = Error::new(ErrorKind::InvalidInput, "could not resolve to any address");
+    for addr in host.to_socket_addrs()? {
+        let mut stream = match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(stream) => stream,
+            Err(e) => {
+                error = e;
+                continue;
+            }
+        };
+        stream.set_read_timeout(Some(CONNECT_TIMEOUT))?;
+        // a single write, so servers reading the request only once get all of it
+        let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", path, host);
+        stream.write_all(request.as_bytes())?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response)?;
+        return Ok(response);
+    }
+    Err(error)
+}
@@ -0,0 +1,139 @@
+//! Log-line probe, scanning the output of a child process for a regular expression
+
+use std::io::{BufRead, BufReader, Read, Result, Error, ErrorKind};
+use std::process::Child;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Instant, Duration};
+
+use regex::Regex;
+
+/// Probe created by [`log`](fn.log.html)
+///
+/// Unlike the other probes it doesn't poll, but waits for the lines printed by the child.
+pub struct Log {
+    regex: Regex,
+    lines: Receiver<String>,
+}
+
+impl Log {
+    /// Read the output of the child until a line matches or the `timeout` period has passed
+    ///
+    /// Returns the matching line together with its capture groups.
+    /// If the `timeout` period has passed, an error of kind `TimedOut` including the last line is returned.
+    /// If the child closes its stdout and stderr (usually because it has exited) without printing a matching line, an error of kind `UnexpectedEof` is returned right away.
+    /// A `timeout` too long to be represented, like `Duration::MAX`, waits without a deadline.
+    pub fn wait(self, timeout: Duration) -> Result<LogMatch> {
+        let start = Instant::now();
+        let deadline = start.checked_add(timeout);
+        let mut last = None;
+        loop {
+            let line = match deadline {
+                Some(deadline) => self.lines.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+                None => self.lines.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            let line = match line {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => {
+                    let message = format!("no line of output matched {} within {:?}", self.regex, timeout);
+                    return Err(Error::new(ErrorKind::TimedOut, with_last_line(message, last)));
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    let message = format!("output of the application ended without a line matching {}", self.regex);
+                    return Err(Error::new(ErrorKind::UnexpectedEof, with_last_line(message, last)));
+                }
+            };
+            if let Some(captures) = self.regex.captures(&line) {
+                let groups = captures.iter().map(|group| group.map(|group| group.as_str().to_string())).collect();
+                let names = self.regex.capture_names()
+                    .enumerate()
+                    .filter_map(|(i, name)| name.map(|name| (name.to_string(), i)))
+                    .collect();
+                return Ok(LogMatch { elapsed: start.elapsed(), line, groups, names });
+            }
+            last = Some(line);
+        }
+    }
+}
+
+/// Line of output matched by a `Log` probe
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogMatch {
+    elapsed: Duration,
+    line: String,
+    groups: Vec<Option<String>>,
+    names: Vec<(String, usize)>,
+}
+
+impl LogMatch {
+    /// Get the time it took until the line was printed
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Get the whole matching line
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+
+    /// Get the text matched by the capture group with index `i`, where 0 is the whole match
+    pub fn get(&self, i: usize) -> Option<&str> {
+        self.groups.get(i).and_then(|group| group.as_deref())
+    }
+
+    /// Get the text matched by the capture group `name`
+    pub fn name(&self, name: &str) -> Option<&str> {
+        self.names.iter().find(|(other, _)| other == name).and_then(|&(_, i)| self.get(i))
+    }
+}
+
+/// Create a probe which takes the piped stdout and stderr of `child` and waits for a line to match the regular expression `pattern`
+///
+/// The output is drained in background threads even after a line has matched, so the child doesn't block on a full pipe.
+/// An error of kind `InvalidInput` is returned if `pattern` is invalid or neither stdout nor stderr of the child is piped.
+///
+/// ```no_run
+/// use std::process::{Command, Stdio};
+/// use std::time::Duration;
+///
+/// let mut child = Command::new("path/to/application").stdout(Stdio::piped()).spawn().unwrap();
+/// let found = test_patience::probe::log(&mut child, r"Listening on .*:(?P<port>\d+)").unwrap()
+///     .wait(Duration::from_secs(5))
+///     .unwrap();
+/// let port: u16 = found.name("port").unwrap().parse().unwrap();
+/// ```
+pub fn log(child: &mut Child, pattern: &str) -> Result<Log> {
+    let regex = Regex::new(pattern).map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+    if child.stdout.is_none() && child.stderr.is_none() {
+        return Err(Error::new(ErrorKind::InvalidInput, "neither stdout nor stderr of the child process is piped"));
+    }
+    let (sender, lines) = mpsc::channel();
+    if let Some(stdout) = child.stdout.take() {
+        forward(stdout, sender.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        forward(stderr, sender);
+    }
+    Ok(Log { regex, lines })
+}
+
+/// Send the lines read from `output` to `sender` in a background thread, draining the output after the receiver is gone
+fn forward<R: Read + Send + 'static>(output: R, sender: Sender<String>) {
+    thread::spawn(move || {
+        for line in BufReader::new(output).lines() {
+            match line {
+                Ok(line) => {
+                    let _ = sender.send(line);
+                }
+                Err(_) => break,
+            }
+        }
+    });
+}
+
+fn with_last_line(message: String, last: Option<String>) -> String {
+    match last {
+        Some(last) => format!("{} (last line: {})", message, last),
+        None => message,
+    }
+}
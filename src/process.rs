@@ -0,0 +1,267 @@
+//! Management of the child processes spawned by the test
+
+use std::collections::VecDeque;
+use std::error;
+use std::fmt;
+use std::io::{BufRead, BufReader, Read, Result};
+use std::ops::{Deref, DerefMut};
+use std::process::{Child, Command, ExitStatus};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Owner of a spawned child process, which kills and reaps it when dropped
+///
+/// This makes sure that tests which panic (or return early) don't leave orphaned applications behind, which keep ports bound on the CI machine.
+/// For children spawned by the `Server`, the processes started by the child are killed as well, see `Server::spawn`.
+/// The guard dereferences to the `Child`.
+///
+/// ```no_run
+/// use std::process::Command;
+/// use std::time::Duration;
+///
+/// use test_patience::ChildGuard;
+///
+/// let server = test_patience::Server::new().unwrap();
+/// let (child, _) = server.spawn_and_wait(&mut Command::new("path/to/application"), Duration::from_secs(5)).unwrap();
+/// let child = ChildGuard::new(child);
+/// println!("application running as process {}", child.id());
+/// ```
+#[derive(Debug)]
+pub struct ChildGuard {
+    child: Option<Child>,
+}
+
+impl ChildGuard {
+    /// Take ownership of `child`
+    pub fn new(child: Child) -> ChildGuard {
+        ChildGuard { child: Some(child) }
+    }
+
+    /// Release the child without killing it
+    pub fn into_inner(mut self) -> Child {
+        self.child.take().expect("child process already released")
+    }
+}
+
+impl From<Child> for ChildGuard {
+    fn from(child: Child) -> ChildGuard {
+        ChildGuard::new(child)
+    }
+}
+
+impl Deref for ChildGuard {
+    type Target = Child;
+
+    fn deref(&self) -> &Child {
+        self.child.as_ref().expect("child process already released")
+    }
+}
+
+impl DerefMut for ChildGuard {
+    fn deref_mut(&mut self) -> &mut Child {
+        self.child.as_mut().expect("child process already released")
+    }
+}
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            kill(&mut child);
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Error returned by `Server::spawn_and_wait` if the application exits before signaling its start
+///
+/// It's wrapped in an `io::Error`, from which it can be retrieved using `get_ref` and `downcast_ref`.
+/// The application has already been reaped, so it doesn't linger as a zombie process.
+///
+/// ```no_run
+/// use std::process::Command;
+/// use std::time::Duration;
+/// use test_patience::EarlyExit;
+///
+/// let server = test_patience::Server::new().unwrap();
+/// if let Err(e) = server.spawn_and_wait(&mut Command::new("path/to/application"), Duration::from_secs(5)) {
+///     if let Some(exit) = e.get_ref().and_then(|e| e.downcast_ref::<EarlyExit>()) {
+///         panic!("application exited with code {:?}", exit.code());
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EarlyExit {
+    status: ExitStatus,
+    output: Vec<String>,
+}
+
+impl EarlyExit {
+    pub(crate) fn new(status: ExitStatus) -> EarlyExit {
+        EarlyExit { status, output: Vec::new() }
+    }
+
+    /// Get the exit status of the application
+    pub fn status(&self) -> ExitStatus {
+        self.status
+    }
+
+    /// Get the exit code of the application, or `None` if it was terminated by a signal
+    pub fn code(&self) -> Option<i32> {
+        self.status.code()
+    }
+
+    /// Get the number of the signal which terminated the application, if any
+    #[cfg(unix)]
+    pub fn signal(&self) -> Option<i32> {
+        use std::os::unix::process::ExitStatusExt;
+        self.status.signal()
+    }
+
+    /// Get the last lines printed by the application, if its output was captured using `Server::capture_output`
+    pub fn output(&self) -> &[String] {
+        &self.output
+    }
+
+    pub(crate) fn set_output(&mut self, output: Vec<String>) {
+        self.output = output;
+    }
+}
+
+impl fmt::Display for EarlyExit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "application exited before signaling its start ({})", self.status)?;
+        if !self.output.is_empty() {
+            write!(f, "\nlast output of the application:\n{}", self.output.join("\n"))?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for EarlyExit {}
+
+/// Let `command` start the child in a new process group, so `kill` terminates the processes started by the child along with it
+pub fn new_group(command: &mut Command) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    #[cfg(not(unix))]
+    let _ = command;
+}
+
+/// Put the freshly spawned `child` into its own group, which has already been created by `new_group` on unix and is a job object on Windows
+///
+/// The job object is inherited by processes the child starts from now on.
+pub fn join_group(child: &Child) -> Result<()> {
+    #[cfg(windows)]
+    return job::assign(child);
+    #[cfg(not(windows))]
+    {
+        let _ = child;
+        Ok(())
+    }
+}
+
+/// Kill `child` together with the other processes of its group
+///
+/// Children which don't lead a group of their own are killed alone.
+pub fn kill(child: &mut Child) {
+    #[cfg(unix)]
+    unsafe {
+        // a group with the id of the child exists only if the child leads it, since its pid has not been reaped yet
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+    #[cfg(windows)]
+    job::terminate(child);
+    // fails if the child has already exited, which is fine
+    let _ = child.kill();
+}
+
+#[cfg(windows)]
+mod job {
+    use std::io::{Error, Result};
+    use std::os::windows::io::AsRawHandle;
+    use std::process::Child;
+    use std::ptr;
+    use std::sync::Mutex;
+
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows_sys::Win32::System::JobObjects::{AssignProcessToJobObject, CreateJobObjectW, TerminateJobObject};
+
+    /// Job objects of the spawned children by process id, with the handles stored as integers since they aren't `Send`
+    static JOBS: Mutex<Vec<(u32, usize)>> = Mutex::new(Vec::new());
+
+    pub fn assign(child: &Child) -> Result<()> {
+        unsafe {
+            let job = CreateJobObjectW(ptr::null(), ptr::null());
+            if job.is_null() {
+                return Err(Error::last_os_error());
+            }
+            if AssignProcessToJobObject(job, child.as_raw_handle() as HANDLE) == 0 {
+                let e = Error::last_os_error();
+                CloseHandle(job);
+                return Err(e);
+            }
+            let mut jobs = JOBS.lock().unwrap_or_else(|e| e.into_inner());
+            // the process id may have been reused since an earlier child exited
+            jobs.retain(|&(id, job)| id != child.id() || CloseHandle(job as HANDLE) == 0);
+            jobs.push((child.id(), job as usize));
+        }
+        Ok(())
+    }
+
+    pub fn terminate(child: &Child) {
+        let mut jobs = JOBS.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(i) = jobs.iter().position(|&(id, _)| id == child.id()) {
+            let (_, job) = jobs.swap_remove(i);
+            unsafe {
+                TerminateJobObject(job as HANDLE, 1);
+                CloseHandle(job as HANDLE);
+            }
+        }
+    }
+}
+
+/// Last lines printed by a child process to its piped stdout and stderr, which are drained in background threads
+pub struct Capture {
+    lines: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl Capture {
+    /// Take the piped stdout and stderr of `child` and keep the last `max_lines` lines printed to either of them
+    pub fn start(child: &mut Child, max_lines: usize) -> Capture {
+        let lines = Arc::new(Mutex::new(VecDeque::with_capacity(max_lines)));
+        if let Some(stdout) = child.stdout.take() {
+            drain(stdout, lines.clone(), max_lines);
+        }
+        if let Some(stderr) = child.stderr.take() {
+            drain(stderr, lines.clone(), max_lines);
+        }
+        Capture { lines }
+    }
+
+    /// Get the lines captured so far
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect()
+    }
+}
+
+/// Read lines from `output` until it's closed, keeping the last `max_lines` of them
+///
+/// The output is drained even after the child has signaled its start, so it doesn't block on a full pipe.
+fn drain<R: Read + Send + 'static>(output: R, lines: Arc<Mutex<VecDeque<String>>>, max_lines: usize) {
+    thread::spawn(move || {
+        for line in BufReader::new(output).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            let mut lines = lines.lock().unwrap_or_else(|e| e.into_inner());
+            if lines.len() == max_lines {
+                lines.pop_front();
+            }
+            lines.push_back(line);
+        }
+    });
+}
@@ -0,0 +1,303 @@
+//! Wire format of the messages sent from the application to the test
+//!
+//! A message consists of its kind, optionally followed by header lines and a body:
+//!
+//! ```text
+//! kind [ "\n" *( name ": " value "\n" ) "\n" body ]
+//! ```
+//!
+//! A plain `done` is a startup notification without any headers, as sent by all versions of this crate.
+//!
+//! A connection carrying a single message is delimited by closing it.
+//! To send several messages over one connection, each of them is prefixed by its length, encoded as 32 bit big-endian integer:
+//!
+//! ```text
+//! *( length message )
+//! ```
+//!
+//! The length has to be less than 16 MiB, so a framed connection always starts with a zero byte, which tells it apart from an unframed message.
+//! Framed messages are handled as soon as they are complete, without waiting for the connection to be closed.
+//!
+//! A heartbeat connection starts with a preamble, followed by a `PING` per heartbeat and finally a regular message:
+//!
+//! ```text
+//! "heartbeat\n" [ *( name ": " value "\n" ) "\n" ] *"ping\n" message
+//! ```
+//!
+//! The header block of the preamble is optional, so the first ping can follow the first line directly.
+//!
+//! An application arriving at a startup barrier sends a preamble with a header block and keeps the connection open
+//! until the test releases it by answering with `RELEASE`:
+//!
+//! ```text
+//! "barrier\n" *( name ": " value "\n" ) "\n"
+//! ```
+//!
+//! Messages and heartbeat connections can be authenticated using a `token` header, see `Server::require_token`,
+//! and carry the `run` ID of the server the application was started for, see `Server::run_id`.
+//! Startup notifications can identify the service which sent them using a `name` header.
+//! If the server asks for it, messages carry the point in time at which they were sent in a `sent` header, in microseconds since the Unix epoch, see `Server::measure_overhead`.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{Result, Error, ErrorKind};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::InvalidNotification;
+
+const READY: &str = "done";
+const FAILURE: &str = "failed";
+const PROGRESS: &str = "progress";
+
+/// First line of the preamble of a heartbeat connection
+pub const HEARTBEAT_LINE: &[u8] = b"heartbeat\n";
+/// Header carrying the token of the server
+pub const TOKEN_HEADER: &str = "token";
+/// Header carrying the run ID of the server the application was started for
+pub const RUN_HEADER: &str = "run";
+/// Header carrying the name of the service which sent the message
+pub const NAME_HEADER: &str = "name";
+/// Header carrying the point in time at which the message was sent, in microseconds since the Unix epoch
+pub const SENT_HEADER: &str = "sent";
+/// Header carrying the number of further startup notifications announced by the application
+pub const EXPECT_HEADER: &str = "expect";
+/// Single heartbeat sent over a heartbeat connection
+pub const PING: &[u8] = b"ping\n";
+/// First line of the preamble of an application arriving at a startup barrier
+pub const BARRIER_LINE: &[u8] = b"barrier\n";
+/// Answer of the test releasing the applications waiting at a startup barrier
+pub const RELEASE: &[u8] = b"go\n";
+/// Version of the protocol, announced in `Handshake`s
+#[cfg(feature = "serde")]
+pub const VERSION: u32 = 1;
+
+/// Length of the prefix of a framed message
+const LENGTH_LEN: usize = 4;
+/// Upper bound of the length of a framed message, which keeps the first byte of its prefix zero
+const MAX_FRAME_LEN: usize = 1 << 24;
+/// Maximum size of the messages received by a server by default
+pub const MAX_MESSAGE_SIZE: usize = MAX_FRAME_LEN;
+
+/// Header lines of a message, as pairs of name and value
+pub type Headers = Vec<(String, String)>;
+
+/// Kind of a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// The application has started successfully
+    Ready,
+    /// The application has failed to start, with the reason in the body
+    Failure,
+    /// The application is still starting, with a progress update in the body
+    Progress,
+}
+
+impl Kind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Kind::Ready => READY,
+            Kind::Failure => FAILURE,
+            Kind::Progress => PROGRESS,
+        }
+    }
+
+    fn parse(kind: &str) -> Option<Kind> {
+        match kind {
+            READY => Some(Kind::Ready),
+            FAILURE => Some(Kind::Failure),
+            PROGRESS => Some(Kind::Progress),
+            _ => None,
+        }
+    }
+}
+
+/// Message sent from the application to the test
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub kind: Kind,
+    pub headers: Headers,
+    pub body: Vec<u8>,
+}
+
+impl Message {
+    pub fn new(kind: Kind) -> Message {
+        Message { kind, headers: Vec::new(), body: Vec::new() }
+    }
+
+    pub fn body<B: Into<Vec<u8>>>(mut self, body: B) -> Message {
+        self.body = body.into();
+        self
+    }
+
+    /// Add a header, whose name and value must not contain line breaks
+    pub fn header<V: Into<String>>(mut self, name: &str, value: V) -> Message {
+        self.headers.push((name.to_string(), value.into()));
+        self
+    }
+
+    /// Get the value of the first header named `name`
+    pub fn get(&self, name: &str) -> Option<&str> {
+        header(&self.headers, name)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = self.kind.as_str().as_bytes().to_vec();
+        if !self.headers.is_empty() || !self.body.is_empty() {
+            buf.push(b'\n');
+            buf.extend_from_slice(&encode_headers(&self.headers));
+            buf.extend_from_slice(&self.body);
+        }
+        buf
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<Message> {
+        let (kind, rest) = match buf.iter().position(|&b| b == b'\n') {
+            Some(end) => (&buf[..end], Some(&buf[end + 1..])),
+            None => (buf, None),
+        };
+        let kind = std::str::from_utf8(kind).ok().and_then(Kind::parse).ok_or_else(invalid)?;
+        let mut message = Message::new(kind);
+
+        if let Some(rest) = rest {
+            let (headers, len) = decode_headers(rest)?.ok_or_else(invalid)?;
+            message.headers = headers;
+            message.body = rest[len..].to_vec();
+        }
+
+        Ok(message)
+    }
+}
+
+/// Prefix the encoded `message` with its length
+pub fn frame(message: &Message) -> Result<Vec<u8>> {
+    let message = message.encode();
+    if message.len() >= MAX_FRAME_LEN {
+        return Err(Error::new(ErrorKind::InvalidInput, "message is too long to be framed"));
+    }
+    let mut buf = (message.len() as u32).to_be_bytes().to_vec();
+    buf.extend_from_slice(&message);
+    Ok(buf)
+}
+
+/// Check that the next message in the data `buf` received over a connection doesn't exceed `max` bytes
+///
+/// The length of a framed message is checked as soon as its prefix is complete, before the message itself has arrived.
+/// Unframed data is checked as a whole.
+pub fn check_size(buf: &[u8], max: usize) -> Result<()> {
+    let len = match buf.first() {
+        Some(0) if buf.len() >= LENGTH_LEN => {
+            let mut length = [0; LENGTH_LEN];
+            length.copy_from_slice(&buf[..LENGTH_LEN]);
+            u32::from_be_bytes(length) as usize
+        }
+        Some(0) => return Ok(()),
+        _ => buf.len(),
+    };
+    if len > max {
+        return Err(too_large(max));
+    }
+    Ok(())
+}
+
+/// Error for a message exceeding the maximum size of `max` bytes
+pub fn too_large(max: usize) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("message exceeds the maximum size of {} bytes", max))
+}
+
+/// Take the next complete message out of the data `buf` received over a connection
+///
+/// Whether the connection is `framed` is determined by its first byte. Framed messages are taken as soon as they are complete,
+/// unframed data is a single message, which is complete once the connection is `closed`.
+/// The incomplete rest of a closed framed connection is returned as well, so it's reported as invalid.
+pub fn take_message(buf: &mut Vec<u8>, framed: &mut bool, closed: bool) -> Option<Vec<u8>> {
+    if !*framed {
+        *framed = buf.first() == Some(&0);
+    }
+    if *framed && buf.len() >= LENGTH_LEN {
+        let mut length = [0; LENGTH_LEN];
+        length.copy_from_slice(&buf[..LENGTH_LEN]);
+        let end = LENGTH_LEN + u32::from_be_bytes(length) as usize;
+        if buf.len() >= end {
+            let message = buf[LENGTH_LEN..end].to_vec();
+            buf.drain(..end);
+            return Some(message);
+        }
+    }
+    if closed && (!*framed || !buf.is_empty()) {
+        Some(buf.split_off(0))
+    } else {
+        None
+    }
+}
+
+/// Get the value of the first header named `name`
+pub fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers.iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, value)| value.as_str())
+}
+
+/// Encode header lines, followed by the empty line ending them
+pub fn encode_headers(headers: &[(String, String)]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for (name, value) in headers {
+        buf.extend_from_slice(name.as_bytes());
+        buf.extend_from_slice(b": ");
+        buf.extend_from_slice(value.as_bytes());
+        buf.push(b'\n');
+    }
+    buf.push(b'\n');
+    buf
+}
+
+/// Decode header lines up to the empty line ending them
+///
+/// Returns the headers together with the length of the header block including the empty line, or `None` if the block is incomplete.
+pub fn decode_headers(buf: &[u8]) -> Result<Option<(Headers, usize)>> {
+    let mut headers = Vec::new();
+    let mut start = 0;
+    while let Some(end) = buf[start..].iter().position(|&b| b == b'\n') {
+        let line = &buf[start..start + end];
+        start += end + 1;
+        if line.is_empty() {
+            return Ok(Some((headers, start)));
+        }
+        let line = std::str::from_utf8(line).map_err(|_| invalid())?;
+        let mut parts = line.splitn(2, ": ");
+        let name = parts.next().ok_or_else(invalid)?;
+        let value = parts.next().ok_or_else(invalid)?;
+        headers.push((name.to_string(), value.to_string()));
+    }
+    Ok(None)
+}
+
+/// Encode the current point in time for the `SENT_HEADER`
+pub fn timestamp() -> String {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    since_epoch.as_micros().to_string()
+}
+
+/// Decode a point in time encoded by `timestamp`
+pub fn parse_timestamp(timestamp: &str) -> Option<SystemTime> {
+    let micros = timestamp.parse().ok()?;
+    UNIX_EPOCH.checked_add(Duration::from_micros(micros))
+}
+
+/// Generate a random token of 32 hex digits, using the randomly seeded keys of the standard library's hasher
+///
+/// These keys don't come from a cryptographically secure generator, so the token only tells concurrent test runs apart.
+/// It must not be relied on to keep out a process which tries to guess it.
+pub fn generate_token() -> String {
+    (0..2u8)
+        .map(|i| {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u8(i);
+            format!("{:016x}", hasher.finish())
+        })
+        .collect()
+}
+
+/// Error for a message which isn't a valid startup notification
+pub fn invalid() -> Error {
+    Error::other(InvalidNotification)
+}
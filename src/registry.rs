@@ -0,0 +1,52 @@
+//! Record of the services which have signaled their start
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::Notification;
+
+/// Handle to the notifications received by a server, which stays usable after the server was consumed by `wait`
+///
+/// It can be held by the test while the wait runs in the background, so later assertions can check which services had come up.
+/// Clones share the same record.
+///
+/// ```no_run
+/// use std::thread;
+/// use std::time::Duration;
+///
+/// let server = test_patience::Server::new().unwrap().expect(["db", "cache"]);
+/// let registry = server.registry();
+/// thread::spawn(move || server.wait_all(Duration::from_secs(5)));
+/// // ...
+/// assert!(registry.is_ready("db"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Registry {
+    notifications: Arc<Mutex<Vec<Notification>>>,
+}
+
+impl Registry {
+    /// Check whether the service `name` has signaled its start using `Client::notify_named`
+    pub fn is_ready(&self, name: &str) -> bool {
+        self.lock().iter().any(|notification| notification.name() == Some(name))
+    }
+
+    /// Get the names of the services which have signaled their start, in the order their notifications were received
+    ///
+    /// Only notifications sent using `Client::notify_named` carry a name.
+    pub fn ready_services(&self) -> Vec<String> {
+        self.lock().iter().filter_map(Notification::name).map(str::to_string).collect()
+    }
+
+    /// Get all notifications received so far, including those without a name
+    pub fn notifications(&self) -> Vec<Notification> {
+        self.lock().clone()
+    }
+
+    pub(crate) fn record(&self, notification: &Notification) {
+        self.lock().push(notification.clone());
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Notification>> {
+        self.notifications.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,1398 @@
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+#[cfg(target_os = "linux")]
+use std::os::linux::net::SocketAddrExt;
+#[cfg(target_os = "linux")]
+use std::os::unix::net::SocketAddr as UnixSocketAddr;
+use std::fs;
+use std::io::{Result, Error, ErrorKind};
+use std::io::prelude::*;
+use std::net::{SocketAddr, TcpListener, UdpSocket};
+use std::ops::{Bound, RangeBounds};
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Instant, Duration};
+
+#[cfg(feature = "serde")]
+use crate::Handshake;
+use crate::{Notification, Registry, ShutdownHandle, StartupFailure, WaitTimeout};
+use crate::client::{PORT_VAR, RUN_VAR, TIMESTAMP_VAR, TOKEN_VAR};
+use crate::error::{Duplicate, HeartbeatStopped};
+use crate::probe::Backoff;
+use crate::probe::backoff::Delays;
+use crate::process::{self, Capture, EarlyExit};
+use crate::protocol::{self, Kind, Message, RELEASE, RUN_HEADER, TOKEN_HEADER};
+use crate::shutdown;
+use crate::transport::{self, Incoming, Listener, Peers};
+use crate::transport::heartbeat::HeartbeatStream;
+
+mod builder;
+mod handle;
+#[cfg(all(feature = "mio", unix))]
+mod poller;
+
+pub use self::builder::ServerBuilder;
+pub use self::handle::ServerHandle;
+#[cfg(all(feature = "mio", unix))]
+pub use self::poller::Poller;
+
+/// Interval in which the server checks for new notifications by default
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+/// Minimum interval in which a server waiting for its sockets to become readable checks whether its heartbeats have stopped
+#[cfg(unix)]
+const WAKE_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Interval in which `spawn_and_wait` checks whether the application has exited
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// Interval in which `wait_with` invokes its callback
+const CALLBACK_INTERVAL: Duration = Duration::from_secs(1);
+/// Time given to notifications sent right before the application exited to arrive
+const EXIT_GRACE_PERIOD: Duration = Duration::from_millis(100);
+
+/// Entry point for the test, waiting for the application to start
+///
+/// Dropping the server closes its listener, so applications notifying it afterwards get an error, and marks it as shut down (see `ShutdownHandle`).
+pub struct Server {
+    listener: Listener,
+    /// Heartbeat connections of applications which are still starting
+    heartbeats: Vec<HeartbeatStream>,
+    reset_on_progress: bool,
+    heartbeat_timeout: Option<Duration>,
+    /// Services `wait_all` waits for
+    expected: Vec<Expected>,
+    /// Services declared using a `ServerHandle`, which haven't been added to `expected` yet
+    registered: Arc<Mutex<Vec<Expected>>>,
+    /// Groups of services `wait_group` waits for, with the names of their members
+    groups: Vec<(String, Vec<String>)>,
+    registry: Registry,
+    /// Number of output lines of the application `spawn_and_wait` includes in timeout errors
+    captured_lines: usize,
+    /// Number of attempts and initial backoff of `spawn_and_wait`
+    restart: (u32, Duration),
+    token: String,
+    require_token: bool,
+    /// Random ID of this server, which tells its notifications apart from those of applications started for earlier servers on the same port
+    run_id: String,
+    /// Delays between the checks for new notifications
+    poll_backoff: Backoff,
+    /// State of the calls of `try_wait` since the last notification
+    polling: Option<Wait>,
+    /// Name of the environment variable passing the port number to the application
+    port_var: String,
+    shutdown: ShutdownHandle,
+    /// Number of connections accepted and datagrams received, reported in timeout errors
+    connections: usize,
+    /// Maximum size of the messages received over sockets
+    max_message_size: usize,
+    /// Whether invalid messages are logged and skipped instead of ending the wait
+    ignore_invalid: bool,
+    /// Whether the application is asked to timestamp its notifications
+    measure_overhead: bool,
+    duplicates: Duplicates,
+}
+
+impl Server {
+    fn with_listener(listener: Listener) -> Server {
+        Server {
+            listener,
+            heartbeats: Vec::new(),
+            reset_on_progress: false,
+            heartbeat_timeout: None,
+            expected: Vec::new(),
+            registered: Arc::default(),
+            groups: Vec::new(),
+            registry: Registry::default(),
+            captured_lines: 0,
+            restart: (1, Duration::from_secs(0)),
+            token: protocol::generate_token(),
+            require_token: false,
+            run_id: protocol::generate_token(),
+            poll_backoff: Backoff::fixed(POLL_INTERVAL),
+            polling: None,
+            port_var: PORT_VAR.to_string(),
+            shutdown: ShutdownHandle::default(),
+            connections: 0,
+            max_message_size: protocol::MAX_MESSAGE_SIZE,
+            ignore_invalid: false,
+            measure_overhead: false,
+            duplicates: Duplicates::default(),
+        }
+    }
+
+    /// Start new TCP server, waiting for the application's startup notification
+    ///
+    /// The server listens on `127.0.0.1`, or on `::1` on hosts without IPv4, which `Client::notify` tries as well.
+    pub fn new() -> Result<Server> {
+        Ok(Server::with_listener(Listener::tcp(transport::bind_loopback(0, TcpListener::bind)?, Peers::default())?))
+    }
+
+    /// Start new TCP server on the fixed `port` of the loopback interface like `new`, waiting for the application's startup notification
+    ///
+    /// This is needed for applications which can only read a hardcoded port from their configuration file.
+    /// Returns an error of kind `AddrInUse` naming the port if it's already taken, e.g. by a test running in parallel.
+    pub fn bind(port: u16) -> Result<Server> {
+        let listener = transport::bind_loopback(port, TcpListener::bind).map_err(|e| match e.kind() {
+            ErrorKind::AddrInUse => Error::new(ErrorKind::AddrInUse, format!("port {} is already in use", port)),
+            _ => e,
+        })?;
+        Ok(Server::with_listener(Listener::tcp(listener, Peers::default())?))
+    }
+
+    /// Start new TCP server on the first free port of the loopback interface within `ports`, waiting for the application's startup notification
+    ///
+    /// This is needed on CI machines whose firewall only allows a specific range of ports for test tooling.
+    /// Returns an error of kind `AddrInUse` listing the busy ports if all of them are taken, or of kind `InvalidInput` if the range is empty.
+    ///
+    /// ```no_run
+    /// let server = test_patience::Server::bind_in_range(40000..=40100).unwrap();
+    /// # #[allow(unused_variables)]
+    /// let port = server.port().unwrap();
+    /// ```
+    pub fn bind_in_range<R: RangeBounds<u16>>(ports: R) -> Result<Server> {
+        let start = match ports.start_bound() {
+            Bound::Included(&start) => Some(start),
+            Bound::Excluded(&start) => start.checked_add(1),
+            Bound::Unbounded => Some(1),
+        };
+        let end = match ports.end_bound() {
+            Bound::Included(&end) => Some(end),
+            Bound::Excluded(&end) => end.checked_sub(1),
+            Bound::Unbounded => Some(u16::MAX),
+        };
+        let (start, end) = match (start, end) {
+            (Some(start), Some(end)) if start <= end => (start, end),
+            _ => return Err(Error::new(ErrorKind::InvalidInput, "port range is empty")),
+        };
+        let mut busy = Vec::new();
+        for port in start..=end {
+            match transport::bind_loopback(port, TcpListener::bind) {
+                Ok(listener) => return Ok(Server::with_listener(Listener::tcp(listener, Peers::default())?)),
+                Err(ref e) if e.kind() == ErrorKind::AddrInUse => busy.push(port),
+                Err(e) => return Err(e),
+            }
+        }
+        let message = format!("all ports from {} to {} are already in use (busy: {})", start, end, port_ranges(&busy));
+        Err(Error::new(ErrorKind::AddrInUse, message))
+    }
+
+    /// Configure a TCP server before starting it, see `ServerBuilder`
+    pub fn builder() -> ServerBuilder {
+        ServerBuilder::new()
+    }
+
+    /// Start new UDP server, waiting for the application's startup notification datagram
+    ///
+    /// The application has to use `Client::notify_udp`, which sends a single datagram and doesn't need to handle connection errors.
+    pub fn new_udp() -> Result<Server> {
+        Ok(Server::with_listener(Listener::Udp(transport::bind_loopback(0, UdpSocket::bind)?)))
+    }
+
+    /// Start new server listening on the Unix domain socket at `path`, waiting for the application's startup notification
+    ///
+    /// This avoids TCP loopback connections, which occasionally collide with firewall rules on CI machines.
+    /// The path has to be sent to the application instead of a port number.
+    /// The socket file is removed when the server is dropped.
+    #[cfg(unix)]
+    pub fn new_unix<P: AsRef<Path>>(path: P) -> Result<Server> {
+        let path = path.as_ref().to_path_buf();
+        Ok(Server::with_listener(Listener::Unix(UnixListener::bind(&path)?, Some(path), Vec::new())))
+    }
+
+    /// Start new server listening on the abstract Unix domain socket `name`, waiting for the application's startup notification
+    ///
+    /// Abstract sockets live in the Linux abstract namespace instead of the filesystem,
+    /// so no cleanup is needed and tests running in parallel containers with separate network namespaces can't collide on socket paths.
+    /// The name (without leading null byte) has to be sent to the application.
+    #[cfg(target_os = "linux")]
+    pub fn new_abstract<N: AsRef<[u8]>>(name: N) -> Result<Server> {
+        let addr = UnixSocketAddr::from_abstract_name(name)?;
+        Ok(Server::with_listener(Listener::Unix(UnixListener::bind_addr(&addr)?, None, Vec::new())))
+    }
+
+    /// Start new server watching the piped stdout of `child` for a line equal to `marker`
+    ///
+    /// This is useful for applications that cannot open sockets (e.g. sandboxed tools).
+    /// The child has to be spawned with `Stdio::piped()` stdout, which is taken from it and drained in a background thread.
+    /// `wait` fails early if the application closes its stdout without printing the marker line.
+    ///
+    /// ```no_run
+    /// use std::process::{Command, Stdio};
+    /// use std::time::Duration;
+    ///
+    /// let mut child = Command::new("path/to/application")
+    ///     .stdout(Stdio::piped())
+    ///     .spawn()
+    ///     .unwrap();
+    ///
+    /// let server = test_patience::Server::watch_stdout(&mut child, "READY").unwrap();
+    /// server.wait(Duration::from_secs(5)).unwrap();
+    /// ```
+    pub fn watch_stdout(child: &mut Child, marker: &str) -> Result<Server> {
+        let stdout = child.stdout.take()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "stdout of the child process is not piped"))?;
+        Ok(Server::with_listener(Listener::stdout(stdout, marker)))
+    }
+
+    /// Start new server watching for the sentinel file at `path` to be created
+    ///
+    /// This is the only option for some legacy applications that can't be modified beyond a shell wrapper.
+    /// The path has to be sent to the application instead of a port number.
+    /// The file's content is returned as the payload of the notification, see `Notification::payload`.
+    /// A stale file left over from a previous run is removed,
+    /// and the sentinel file is removed again when the server is dropped.
+    pub fn watch_file<P: AsRef<Path>>(path: P) -> Result<Server> {
+        let path = path.as_ref().to_path_buf();
+        match fs::remove_file(&path) {
+            Err(ref e) if e.kind() == ErrorKind::NotFound => {}
+            result => result?,
+        }
+        Ok(Server::with_listener(Listener::File(path)))
+    }
+
+    /// Start new server waiting for `SIGUSR1` to be sent to the test process
+    ///
+    /// This is useful for daemons that already support signaling their parent when they are ready.
+    /// The id of the test process (see `pid`) has to be sent to the application.
+    ///
+    /// The signal handler is process-wide, so only one signal server can exist at a time. Creating another one fails with an error of kind `AddrInUse`.
+    /// The handler stays installed after the server is dropped, so late signals don't terminate the test process.
+    #[cfg(unix)]
+    pub fn new_signal() -> Result<Server> {
+        Ok(Server::with_listener(Listener::Signal(transport::signal::Registration::new()?)))
+    }
+
+    /// Get the id of the test process, which the application has to signal if the server was created using `new_signal`
+    #[cfg(unix)]
+    pub fn pid(&self) -> u32 {
+        std::process::id()
+    }
+
+    /// Start new server reading the startup notification from an anonymous pipe
+    ///
+    /// The write end of the pipe has to be passed to the application using `inherit_pipe`.
+    /// This avoids ports entirely and works in network-less sandboxes.
+    /// `wait` fails early if the application closes the pipe without signaling its start, e.g. because it crashed.
+    ///
+    /// ```no_run
+    /// use std::process::Command;
+    /// use std::time::Duration;
+    ///
+    /// let server = test_patience::Server::new_pipe().unwrap();
+    ///
+    /// let mut command = Command::new("path/to/application");
+    /// server.inherit_pipe(&mut command).unwrap();
+    /// # #[allow(unused_variables)]
+    /// let process = command.spawn().unwrap();
+    ///
+    /// server.wait(Duration::from_secs(5)).unwrap();
+    /// ```
+    pub fn new_pipe() -> Result<Server> {
+        Ok(Server::with_listener(Listener::Pipe(transport::pipe::Pipe::new()?)))
+    }
+
+    /// Let the application spawned by `command` inherit the write end of the pipe
+    ///
+    /// The file descriptor (handle on Windows) is passed in the `TEST_PATIENCE_PIPE` environment variable.
+    /// The test closes its copy of the write end when `wait` is called, so the application has to be spawned before that.
+    /// Returns an error if the server was not created using `new_pipe`.
+    pub fn inherit_pipe(&self, command: &mut Command) -> Result<()> {
+        match self.listener {
+            Listener::Pipe(ref pipe) => pipe.inherit(command),
+            _ => Err(Error::new(ErrorKind::InvalidInput, "server does not read from a pipe")),
+        }
+    }
+
+    /// Start new server listening on a vsock port, waiting for the startup notification of an application running inside a VM
+    ///
+    /// This lets the guest notify the host test process without any port forwarding.
+    /// The port number (see `vsock_port`) has to be sent to the application, which connects to the host using `Client::notify_vsock`.
+    #[cfg(target_os = "linux")]
+    pub fn new_vsock() -> Result<Server> {
+        Ok(Server::with_listener(Listener::Vsock(transport::vsock::VsockListener::bind()?, Vec::new())))
+    }
+
+    /// Get the vsock port number of the server
+    ///
+    /// Returns an error if the server was not created using `new_vsock`.
+    #[cfg(target_os = "linux")]
+    pub fn vsock_port(&self) -> Result<u32> {
+        match self.listener {
+            Listener::Vsock(ref listener, _) => listener.local_port(),
+            _ => Err(Error::new(ErrorKind::InvalidInput, "server does not listen on a vsock port")),
+        }
+    }
+
+    /// Spawn the application using `command`, telling it how to notify this server
+    ///
+    /// The port number is passed in the `TEST_PATIENCE_PORT` environment variable, the token in `TEST_PATIENCE_TOKEN` and the run ID in `TEST_PATIENCE_RUN`.
+    /// For servers created using `new_pipe`, the write end of the pipe is inherited instead of passing a port number (see `inherit_pipe`).
+    /// Applications notifying other servers have to be told where to notify by other means.
+    ///
+    /// The application is started in a new process group (a job object on Windows),
+    /// so killing it with `ChildGuard` or `spawn_and_wait` also kills the processes it started, e.g. the server launched by a wrapper script.
+    ///
+    /// ```no_run
+    /// use std::process::Command;
+    /// use std::time::Duration;
+    ///
+    /// let server = test_patience::Server::new().unwrap();
+    /// # #[allow(unused_variables)]
+    /// let child = server.spawn(&mut Command::new("path/to/application")).unwrap();
+    /// server.wait(Duration::from_secs(5)).unwrap();
+    /// ```
+    pub fn spawn(&self, command: &mut Command) -> Result<Child> {
+        self.configure(command)?;
+        process::new_group(command);
+        let mut child = command.spawn()?;
+        if let Err(e) = process::join_group(&child) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(e);
+        }
+        Ok(child)
+    }
+
+    /// Spawn the application using `command` like `spawn` and wait until it has signaled its successful start or the `timeout` period has expired
+    ///
+    /// Returns the child together with the received notification.
+    /// If the application exits before signaling its start, an `EarlyExit` error carrying its exit status is returned right away instead of waiting for the timeout.
+    /// Applications which detach from the test, like daemons forking into the background, have to be started using `spawn` and `wait` instead.
+    /// If waiting fails, e.g. because of a timeout, the application is killed before the error is returned, so it doesn't leak into subsequent tests.
+    /// With `capture_output`, timeout and `EarlyExit` errors include the last lines printed by the application.
+    /// With `restart_on_failure`, the application is relaunched until it starts successfully or the attempts are exhausted.
+    ///
+    /// ```no_run
+    /// use std::process::Command;
+    /// use std::time::Duration;
+    ///
+    /// let server = test_patience::Server::new().unwrap();
+    /// # #[allow(unused_variables)]
+    /// let (child, notification) = server.spawn_and_wait(&mut Command::new("path/to/application"), Duration::from_secs(5)).unwrap();
+    /// ```
+    pub fn spawn_and_wait(mut self, command: &mut Command, timeout: Duration) -> Result<(Child, Notification)> {
+        let (attempts, mut backoff) = self.restart;
+        let mut attempt = 1;
+        loop {
+            match self.spawn_once(command, timeout) {
+                Err(_) if attempt < attempts => {
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Relaunch applications spawned by `spawn_and_wait` which fail to start, up to a total of `attempts` launches
+    ///
+    /// This is useful for third-party services which occasionally crash on startup.
+    /// Every attempt has the full timeout of `spawn_and_wait`, and the failed application is killed before the next attempt.
+    /// The attempts are separated by `backoff`, which doubles after every attempt.
+    /// If all attempts fail, the error of the last one is returned.
+    pub fn restart_on_failure(mut self, attempts: u32, backoff: Duration) -> Server {
+        self.restart = (attempts, backoff);
+        self
+    }
+
+    /// Spawn the application once and wait for its start, killing it if waiting fails
+    fn spawn_once(&mut self, command: &mut Command, timeout: Duration) -> Result<(Child, Notification)> {
+        let captured_lines = self.captured_lines;
+        if captured_lines > 0 {
+            command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        }
+        let mut child = self.spawn(command)?;
+        let capture = if captured_lines > 0 { Some(Capture::start(&mut child, captured_lines)) } else { None };
+        let mut wait = self.start(timeout)?;
+        match self.wait_for_child(&mut child, &mut wait) {
+            Ok(notification) => Ok((child, notification)),
+            Err(mut e) => {
+                if let Some(ref capture) = capture {
+                    if let Some(exit) = e.get_mut().and_then(|e| e.downcast_mut::<EarlyExit>()) {
+                        exit.set_output(capture.lines());
+                    } else if let Some(timeout) = e.get_mut().and_then(|e| e.downcast_mut::<WaitTimeout>()) {
+                        timeout.set_output(capture.lines());
+                    } else if e.kind() == ErrorKind::TimedOut {
+                        e = with_output(e, &capture.lines());
+                    }
+                }
+                process::kill(&mut child);
+                let _ = child.wait();
+                Err(e)
+            }
+        }
+    }
+
+    /// Wait for the startup notification until `child` exits or the timeout of `wait` expires
+    fn wait_for_child(&mut self, child: &mut Child, wait: &mut Wait) -> Result<Notification> {
+        loop {
+            if let Some(notification) = self.next(wait, Some(Instant::now() + EXIT_POLL_INTERVAL))? {
+                return Ok(notification);
+            }
+            if let Some(status) = child.try_wait()? {
+                // the application may have notified right before exiting
+                return match self.next(wait, Some(Instant::now() + EXIT_GRACE_PERIOD))? {
+                    Some(notification) => Ok(notification),
+                    None => Err(Error::other(EarlyExit::new(status))),
+                };
+            }
+            if wait.expired() {
+                return Err(self.timed_out(wait));
+            }
+        }
+    }
+
+    /// Capture the stdout and stderr of applications spawned by `spawn_and_wait`, including their last `lines` in timeout errors
+    ///
+    /// When the startup notification never arrives, the output of the application usually tells why it failed to start.
+    /// The output is piped and drained in background threads, so it doesn't appear in the output of the test anymore.
+    pub fn capture_output(mut self, lines: usize) -> Server {
+        self.captured_lines = lines;
+        self
+    }
+
+    /// Set the environment variables of `command`, telling the application how to notify this server
+    pub(crate) fn configure(&self, command: &mut Command) -> Result<()> {
+        if let Listener::Pipe(ref pipe) = self.listener {
+            pipe.inherit(command)?;
+        }
+        command.envs(self.env());
+        Ok(())
+    }
+
+    /// Get the environment variables telling the application how to notify this server
+    ///
+    /// These are the `TEST_PATIENCE_PORT` variable (see `port_var`) of TCP and UDP servers and the `TEST_PATIENCE_TOKEN` and `TEST_PATIENCE_RUN` (see `run_id`) variables,
+    /// plus `TEST_PATIENCE_TIMESTAMP` if the server measures its overhead (see `measure_overhead`).
+    /// Passing all of them keeps tests correct when further variables are added.
+    /// For servers using a pipe, `inherit_pipe` has to be used as well.
+    ///
+    /// ```no_run
+    /// use std::process::Command;
+    /// use std::time::Duration;
+    ///
+    /// let server = test_patience::Server::new().unwrap();
+    /// # #[allow(unused_variables)]
+    /// let child = Command::new("path/to/application").envs(server.env()).spawn().unwrap();
+    /// server.wait(Duration::from_secs(5)).unwrap();
+    /// ```
+    pub fn env(&self) -> Vec<(String, String)> {
+        let mut env = Vec::new();
+        if let Listener::Tcp(..) | Listener::Udp(_) = self.listener {
+            if let Ok(port) = self.port() {
+                env.push((self.port_var.clone(), format!("{}", port)));
+            }
+        }
+        env.push((TOKEN_VAR.to_string(), self.token.clone()));
+        env.push((RUN_VAR.to_string(), self.run_id.clone()));
+        if self.measure_overhead {
+            env.push((TIMESTAMP_VAR.to_string(), "1".to_string()));
+        }
+        env
+    }
+
+    /// Pass the port number to the application in the environment variable `name` instead of `TEST_PATIENCE_PORT`
+    ///
+    /// This keeps nested layers of tests using test-patience apart, e.g. a test starting an application which in turn uses test-patience to start its own helpers.
+    /// The application has to use `Client::notify_from_env_var` with the same name.
+    pub fn port_var(mut self, name: &str) -> Server {
+        self.port_var = name.to_string();
+        self
+    }
+
+    /// Get the port number of the TCP or UDP Server
+    ///
+    /// This port number has to sent to the application.
+    /// Returns an error if the server doesn't listen on a port.
+    pub fn port(&self) -> Result<u16> {
+        Ok(self.local_addr()?.port())
+    }
+
+    /// Get the address the TCP or UDP server listens on
+    ///
+    /// For a server bound to an unspecified address like `0.0.0.0` (see `ServerBuilder::bind_addr`), this is the unspecified address,
+    /// and applications running inside a container have to connect to an address of the host reachable from the container instead, e.g. the IP of the Docker bridge.
+    /// Returns an error if the server doesn't listen on a port.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        match self.listener {
+            Listener::Tcp(ref listener, ..) => listener.local_addr(),
+            Listener::Udp(ref socket) => socket.local_addr(),
+            #[cfg(unix)]
+            Listener::Unix(..) => Err(Error::new(ErrorKind::InvalidInput, "server does not listen on a port")),
+            Listener::Stdout(_) | Listener::File(_) => Err(Error::new(ErrorKind::InvalidInput, "server does not listen on a port")),
+            #[cfg(unix)]
+            Listener::Signal(_) => Err(Error::new(ErrorKind::InvalidInput, "server does not listen on a port")),
+            Listener::Pipe(_) => Err(Error::new(ErrorKind::InvalidInput, "server does not listen on a port")),
+            #[cfg(target_os = "linux")]
+            Listener::Vsock(..) => Err(Error::new(ErrorKind::InvalidInput, "server does not listen on a TCP or UDP port")),
+        }
+    }
+
+    /// Measure the `timeout` of `wait` from the last progress update instead of the call of `wait`
+    ///
+    /// Services with a long and variable warmup (e.g. rebuilding an index or running migrations) can send periodic updates using `Client::progress`,
+    /// so the timeout only has to cover the longest gap between two updates instead of the whole startup.
+    pub fn reset_timeout_on_progress(mut self, reset: bool) -> Server {
+        self.reset_on_progress = reset;
+        self
+    }
+
+    /// Reject messages larger than `size` bytes, which are received over sockets, instead of buffering them (16 MiB by default)
+    ///
+    /// Waiting fails with an error of kind `InvalidData` as soon as a connection exceeds the size, so a misbehaving peer can't make the test allocate arbitrary amounts of memory.
+    /// The asynchronous server always uses the default size.
+    pub fn max_message_size(mut self, size: usize) -> Server {
+        self.max_message_size = size;
+        self
+    }
+
+    /// Get the random token generated for this server
+    ///
+    /// It has to be sent to the application along with the port number, preferably using the `TEST_PATIENCE_TOKEN` environment variable.
+    /// The token isn't generated by a cryptographically secure generator, so it only keeps concurrent test runs apart and provides no security.
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// Get the port, address and token the application needs to notify this TCP server, serialized as a single value
+    ///
+    /// For servers bound to an unspecified address like `0.0.0.0` (see `ServerBuilder::bind_addr`), that address is included as it is,
+    /// so applications running on another host have to replace it.
+    /// Returns an error of kind `InvalidInput` for servers which don't listen on a TCP port.
+    #[cfg(feature = "serde")]
+    pub fn handshake(&self) -> Result<Handshake> {
+        match self.listener {
+            Listener::Tcp(ref listener, ..) => Ok(Handshake::new(listener.local_addr()?, &self.token, &self.run_id, self.measure_overhead)),
+            _ => Err(Error::new(ErrorKind::InvalidInput, "server does not listen on a TCP port")),
+        }
+    }
+
+    /// Get the random run ID of this server
+    ///
+    /// It's passed to the application in the `TEST_PATIENCE_RUN` environment variable (see `env`) and sent along with every notification by the `Client`.
+    /// Notifications carrying another run ID are ignored, as they were sent by an application leaked by an earlier test, which was started for another server on the same port.
+    /// Notifications without a run ID are accepted.
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// Ask the application to timestamp its notifications, so `Notification::overhead` reports the overhead of the synchronization
+    ///
+    /// The request is passed in the `TEST_PATIENCE_TIMESTAMP` environment variable (see `env`), or in the `Handshake`.
+    /// It's off by default, as timestamped notifications aren't understood by servers of the released 0.1 versions,
+    /// which only accept a plain `done` from applications started without a token.
+    pub fn measure_overhead(mut self, measure: bool) -> Server {
+        self.measure_overhead = measure;
+        self
+    }
+
+    /// Get a handle shutting down this server from another thread, cancelling a wait running there
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Get a cloneable handle of this server, which can be used by other threads while this one waits
+    ///
+    /// See `ServerHandle`.
+    pub fn handle(&self) -> ServerHandle {
+        ServerHandle::new(self.local_addr().ok(), self.shutdown.clone(), self.registered.clone())
+    }
+
+    /// Close the listener of the server right away instead of when it's dropped
+    ///
+    /// This frees the port or removes the socket file, e.g. before starting another server on a fixed port (see `bind`).
+    pub fn close(self) {
+        drop(self);
+    }
+
+    /// Log invalid messages to stderr and keep waiting, instead of ending the wait with an error
+    ///
+    /// Port scanners and health checkers connect to random ports and send data which isn't a startup notification, or reset their connections.
+    /// With this option, the wait continues until the actual notification arrives or the timeout expires.
+    /// Unlike `require_token`, valid notifications without the token of the server are still accepted.
+    /// Failures reported by the application using `Client::notify_failure` still end the wait, as do closed heartbeat connections.
+    pub fn ignore_invalid(mut self, ignore: bool) -> Server {
+        self.ignore_invalid = ignore;
+        self
+    }
+
+    /// Set how repeated startup notifications of the same service are handled (all of them count by default)
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use test_patience::Duplicates;
+    ///
+    /// let server = test_patience::Server::new().unwrap().duplicates(Duplicates::Ignore);
+    /// // start three services, which may retry their notifications
+    /// server.wait_for_n(3, Duration::from_secs(5)).unwrap();
+    /// ```
+    pub fn duplicates(mut self, policy: Duplicates) -> Server {
+        self.duplicates = policy;
+        self
+    }
+
+    /// Only accept notifications carrying the token of this server
+    ///
+    /// On shared machines, another process could connect to the port of the server and send garbage, or an unrelated startup notification.
+    /// With this option, such messages are ignored instead of ending the wait.
+    /// Clients send the token automatically if it's passed in the `TEST_PATIENCE_TOKEN` environment variable.
+    /// This guards against accidents like a test suite running twice at once, not against attackers, as the token can be guessed (see `token`).
+    ///
+    /// Returns an error of kind `InvalidInput` if the server watches the stdout of a child process, a sentinel file or readiness signals,
+    /// as these can't carry a token.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use std::process;
+    ///
+    /// let server = test_patience::Server::new().unwrap().require_token(true).unwrap();
+    ///
+    /// # #[allow(unused_variables)]
+    /// let process = process::Command::new("path/to/application")
+    ///     .env("TEST_PATIENCE_PORT", format!("{}", server.port().unwrap()))
+    ///     .env("TEST_PATIENCE_TOKEN", server.token())
+    ///     .spawn()
+    ///     .unwrap();
+    ///
+    /// server.wait(Duration::from_secs(5)).unwrap();
+    /// ```
+    pub fn require_token(mut self, require: bool) -> Result<Server> {
+        match self.listener {
+            Listener::Stdout(_) | Listener::File(_) if require => {
+                return Err(Error::new(ErrorKind::InvalidInput, "server can't authenticate notifications without a message"));
+            }
+            #[cfg(unix)]
+            Listener::Signal(_) if require => {
+                return Err(Error::new(ErrorKind::InvalidInput, "server can't authenticate notifications without a message"));
+            }
+            _ => {}
+        }
+        self.require_token = require;
+        Ok(self)
+    }
+
+    /// Set the `interval` in which the server checks for new notifications, see `ServerBuilder::poll_interval`
+    ///
+    /// On Windows, the server polls its sockets in this interval as well, as it can't wait for them to become readable there.
+    pub fn poll_interval(self, interval: Duration) -> Server {
+        self.poll_backoff(Backoff::fixed(interval))
+    }
+
+    /// Replace the fixed poll interval by the delays of the `backoff` policy, which start over with every wait
+    ///
+    /// An exponential backoff starts checking for notifications often and slows down while a slow application starts,
+    /// e.g. `Backoff::exponential(Duration::from_millis(1), Duration::from_millis(500))` for services which take 30 seconds or more.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use test_patience::probe::Backoff;
+    ///
+    /// let server = test_patience::Server::watch_file("/tmp/app.ready").unwrap()
+    ///     .poll_backoff(Backoff::exponential(Duration::from_millis(1), Duration::from_millis(500)));
+    /// // start the application, which creates /tmp/app.ready
+    /// server.wait(Duration::from_secs(60)).unwrap();
+    /// ```
+    pub fn poll_backoff(mut self, backoff: Backoff) -> Server {
+        self.poll_backoff = backoff;
+        self
+    }
+
+    /// Fail as soon as the application hasn't sent a heartbeat for the `timeout` period
+    ///
+    /// This only applies to applications using `Client::heartbeat`, once their heartbeat connection has been established.
+    /// It distinguishes an application which is still starting from one that hangs, so the test fails early instead of running into the timeout of `wait`.
+    /// Heartbeat connections closed without a startup notification, e.g. because the application died, are always reported right away.
+    /// The asynchronous server doesn't monitor heartbeats.
+    pub fn heartbeat_timeout(mut self, timeout: Duration) -> Server {
+        self.heartbeat_timeout = Some(timeout);
+        self
+    }
+
+    /// Block the currently running thread until either the starting application has signaled its successful start or the `timeout` period has expired
+    ///
+    /// Returns the received notification, including the duration for which was waited, or an error in case of a timeout or invalid startup notification.
+    /// In case of a timeout, the error wraps a `WaitTimeout`, which includes the last progress update sent by the application.
+    /// An error of kind `ConnectionAborted` is returned if the heartbeats of the application stop (see `heartbeat_timeout`).
+    /// Connections are read without blocking, so a client which connects but never sends anything can't keep the wait from returning within its `timeout`.
+    pub fn wait(mut self, timeout: Duration) -> Result<Notification> {
+        self.wait_ref(timeout)
+    }
+
+    /// Check whether the application has signaled its successful start, without blocking
+    ///
+    /// Returns `Ok(None)` if no notification has arrived yet, so the test can do other setup work in the meantime and check again later.
+    /// The duration of the returned notification is measured from the first call of `try_wait` after the previous notification.
+    /// Like `wait`, this returns an error for an invalid startup notification or a failure reported by the application.
+    ///
+    /// ```no_run
+    /// use std::thread;
+    /// use std::time::Duration;
+    ///
+    /// let mut server = test_patience::Server::new().unwrap();
+    /// // start the application
+    /// let notification = loop {
+    ///     if let Some(notification) = server.try_wait().unwrap() {
+    ///         break notification;
+    ///     }
+    ///     // other setup work
+    ///     thread::sleep(Duration::from_millis(10));
+    /// };
+    /// ```
+    pub fn try_wait(&mut self) -> Result<Option<Notification>> {
+        if self.shutdown.is_shut_down() {
+            return Err(shutdown::cancelled());
+        }
+        self.listener.start()?;
+        let mut polling = self.polling.take().unwrap_or_else(|| Wait::new(Duration::MAX, self.poll_backoff));
+        let result = self.poll(&mut polling);
+        if let Ok(None) = result {
+            self.polling = Some(polling);
+        }
+        result
+    }
+
+    /// Wait like `wait`, invoking `callback` with the duration waited so far every second while still waiting
+    ///
+    /// This lets long running waits report that they are still alive, so CI log watchers don't assume the build hung.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// let server = test_patience::Server::new().unwrap();
+    /// // start the application
+    /// server.wait_with(Duration::from_secs(60), |elapsed| {
+    ///     println!("still waiting for application ({}s/60s)", elapsed.as_secs());
+    /// }).unwrap();
+    /// ```
+    pub fn wait_with<F: FnMut(Duration)>(mut self, timeout: Duration, mut callback: F) -> Result<Notification> {
+        let mut wait = self.start(timeout)?;
+        loop {
+            if let Some(notification) = self.next(&mut wait, Instant::now().checked_add(CALLBACK_INTERVAL))? {
+                return Ok(notification);
+            }
+            if wait.expired() {
+                return Err(self.timed_out(&wait));
+            }
+            callback(wait.start.elapsed());
+        }
+    }
+
+    /// Wait like `wait` without any timeout, e.g. while the application is being debugged interactively
+    ///
+    /// Long timeouts like `Duration::MAX` passed to `wait` and its variants are handled the same way.
+    pub fn wait_forever(self) -> Result<Notification> {
+        self.wait(Duration::MAX)
+    }
+
+    /// Wait like `wait` until the point in time `deadline` instead of a timeout period
+    ///
+    /// This lets several waits share a single overall time budget, without recomputing the remaining duration for each of them.
+    /// If the deadline has already passed, a timeout error is returned right away.
+    ///
+    /// ```no_run
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let deadline = Instant::now() + Duration::from_secs(10);
+    /// let db = test_patience::Server::new().unwrap();
+    /// let api = test_patience::Server::new().unwrap();
+    /// // start the applications
+    /// db.wait_deadline(deadline).unwrap();
+    /// api.wait_deadline(deadline).unwrap();
+    /// ```
+    pub fn wait_deadline(self, deadline: Instant) -> Result<Notification> {
+        self.wait(deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Wait like `wait` without consuming the server, so it can be waited for again
+    ///
+    /// This lets a single server, and the port passed to the applications, be used across several phases of a test,
+    /// e.g. waiting for an application again after restarting it.
+    /// Notifications which arrive between two calls are kept until the next one.
+    /// Servers watching the stdout of a child process or a pipe only receive a single notification,
+    /// and servers watching a sentinel file report it again as long as the file exists.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// let mut server = test_patience::Server::new().unwrap();
+    /// // start the application
+    /// server.wait_ref(Duration::from_secs(5)).unwrap();
+    /// // restart the application
+    /// server.wait_ref(Duration::from_secs(5)).unwrap();
+    /// ```
+    pub fn wait_ref(&mut self, timeout: Duration) -> Result<Notification> {
+        let mut wait = self.start(timeout)?;
+        self.next(&mut wait, None)?.ok_or_else(|| self.timed_out(&wait))
+    }
+
+    /// Collect the notifications arriving within the `window` period, e.g. after `wait_ref` has run into its timeout
+    ///
+    /// This tells a timeout which was too short, because the application notified the server shortly afterwards,
+    /// apart from an application which never notified it at all.
+    /// Blocks for the whole `window` period. The durations of the notifications are measured from the call of this method.
+    /// Like `wait`, this returns an error for an invalid startup notification or a failure reported by the application.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// let mut server = test_patience::Server::new().unwrap();
+    /// // start the application
+    /// if let Err(e) = server.wait_ref(Duration::from_secs(5)) {
+    ///     match server.drain_late(Duration::from_secs(10)).unwrap().first() {
+    ///         Some(late) => panic!("{}, but the notification arrived {:?} later", e, late.elapsed()),
+    ///         None => panic!("{}", e),
+    ///     }
+    /// }
+    /// ```
+    pub fn drain_late(&mut self, window: Duration) -> Result<Vec<Notification>> {
+        let mut wait = self.start(window)?;
+        let mut notifications = Vec::new();
+        while let Some(notification) = self.next(&mut wait, None)? {
+            notifications.push(notification);
+        }
+        Ok(notifications)
+    }
+
+    /// Block the currently running thread until `n` applications have signaled their successful start or the `timeout` period has expired
+    ///
+    /// This lets several applications report to the same server, instead of needing a server and port per application.
+    /// Returns the notifications in the order they were received, each including the duration for which was waited for it.
+    /// Every startup notification counts, so each application has to notify exactly once, unless repeated notifications of named services are ignored (see `duplicates`).
+    /// Applications which only know at runtime how many others will report (e.g. because they fork workers) can raise the count
+    /// while waiting using `Client::notify_expecting`.
+    /// In case of a timeout, the error wraps a `WaitTimeout` telling how many notifications were received.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use std::process;
+    ///
+    /// let server = test_patience::Server::new().unwrap();
+    /// let port = server.port().unwrap();
+    ///
+    /// for helper in &["path/to/db-proxy", "path/to/search", "path/to/mailer"] {
+    ///     process::Command::new(helper)
+    ///         .env("TEST_PATIENCE_PORT", format!("{}", port))
+    ///         .spawn()
+    ///         .unwrap();
+    /// }
+    ///
+    /// let notifications = server.wait_for_n(3, Duration::from_secs(5)).unwrap();
+    /// ```
+    pub fn wait_for_n(mut self, mut n: usize, timeout: Duration) -> Result<Vec<Notification>> {
+        let mut wait = self.start(timeout)?;
+        let mut notifications = Vec::with_capacity(n);
+        while notifications.len() < n {
+            match self.next(&mut wait, None)? {
+                Some(notification) => {
+                    n += notification.expecting();
+                    notifications.push(notification);
+                }
+                None => {
+                    let timeout = self.wait_timeout(&wait).notifications(notifications.len(), n - notifications.len());
+                    return Err(Error::new(ErrorKind::TimedOut, timeout));
+                }
+            }
+        }
+        Ok(notifications)
+    }
+
+    /// Get a handle to the notifications received by this server, which can be queried while `wait` or one of its variants runs in another thread
+    pub fn registry(&self) -> Registry {
+        self.registry.clone()
+    }
+
+    /// Declare the names of services `wait_all` waits for
+    ///
+    /// The services have to identify themselves using `Client::notify_named`.
+    /// Further services can be declared by calling this method again or using `expect_within`.
+    pub fn expect<I, S>(mut self, names: I) -> Server
+        where I: IntoIterator<Item = S>, S: Into<String>
+    {
+        self.expected.extend(names.into_iter().map(|name| Expected { name: name.into(), timeout: None }));
+        self
+    }
+
+    /// Declare the name of a service `wait_all` waits for, which has to signal its start within its own `timeout` period
+    ///
+    /// This lets a slow service (e.g. a database) take longer than a stub server, which should be up right away.
+    /// The timeout is measured from the call of `wait_all`, whose own `timeout` applies as well.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// let server = test_patience::Server::new().unwrap()
+    ///     .expect_within("db", Duration::from_secs(30))
+    ///     .expect_within("stub", Duration::from_secs(1));
+    /// server.wait_all(Duration::from_secs(30)).unwrap();
+    /// ```
+    pub fn expect_within(mut self, name: &str, timeout: Duration) -> Server {
+        self.expected.push(Expected { name: name.to_string(), timeout: Some(timeout) });
+        self
+    }
+
+    /// Block the currently running thread until every service declared using `expect` has signaled its successful start or the `timeout` period has expired
+    ///
+    /// Returns the notifications in the order they were received.
+    /// Notifications of services which weren't declared, as well as repeated notifications of the same service, are skipped.
+    /// In case of a timeout, the error wraps a `WaitTimeout` listing the names of the services which haven't signaled their start,
+    /// or only those which have exceeded their own timeout (see `expect_within`).
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// let server = test_patience::Server::new().unwrap().expect(["db", "cache", "api"]);
+    /// // start the services, which call `Client::notify_named`
+    /// server.wait_all(Duration::from_secs(5)).unwrap();
+    /// ```
+    pub fn wait_all(mut self, timeout: Duration) -> Result<Vec<Notification>> {
+        let mut wait = self.start(timeout)?;
+        self.take_registered();
+        let missing = self.expected.clone();
+        self.wait_for(&mut wait, Vec::new(), missing, true)
+    }
+
+    /// Declare the group of services `name`, consisting of the services named `members`, which can be waited for as a unit using `wait_group`
+    pub fn group<I, S>(mut self, name: &str, members: I) -> Server
+        where I: IntoIterator<Item = S>, S: Into<String>
+    {
+        self.groups.push((name.to_string(), members.into_iter().map(Into::into).collect()));
+        self
+    }
+
+    /// Block the currently running thread until every member of the `group` has signaled its successful start or the `timeout` period has expired
+    ///
+    /// Unlike `wait`, this doesn't consume the server, so several groups can be waited for one after another.
+    /// Members which have already signaled their start while waiting for another group count as well.
+    /// Returns the notifications of the members, or an error of kind `InvalidInput` if the group was not declared using `group`.
+    /// In case of a timeout, the error wraps a `WaitTimeout` naming the group and its missing members.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// let mut server = test_patience::Server::new().unwrap()
+    ///     .group("storage", ["postgres", "minio"])
+    ///     .group("frontend", ["api", "worker"]);
+    /// // start the storage services
+    /// server.wait_group("storage", Duration::from_secs(30)).unwrap();
+    /// // start the frontend services
+    /// server.wait_group("frontend", Duration::from_secs(5)).unwrap();
+    /// ```
+    pub fn wait_group(&mut self, group: &str, timeout: Duration) -> Result<Vec<Notification>> {
+        let members = self.groups.iter()
+            .find(|(name, _)| name == group)
+            .map(|(_, members)| members.clone())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, format!("unknown group {}", group)))?;
+        let mut wait = self.start(timeout)?;
+        let ready = self.registry.notifications();
+        let mut notifications = Vec::with_capacity(members.len());
+        let mut missing = Vec::new();
+        for member in members {
+            match ready.iter().find(|notification| notification.name() == Some(member.as_str())) {
+                Some(notification) => notifications.push(notification.clone()),
+                None => missing.push(Expected { name: member, timeout: None }),
+            }
+        }
+        self.wait_for(&mut wait, notifications, missing, false).map_err(|mut e| {
+            if let Some(timeout) = e.get_mut().and_then(|e| e.downcast_mut::<WaitTimeout>()) {
+                timeout.set_group(group);
+            }
+            e
+        })
+    }
+
+    /// Receive notifications until all `missing` services have signaled their start, and add them to the `notifications` received before
+    ///
+    /// If `registered` is set, services declared using a `ServerHandle` in the meantime are waited for as well.
+    fn wait_for(&mut self, wait: &mut Wait, mut notifications: Vec<Notification>, mut missing: Vec<Expected>, registered: bool) -> Result<Vec<Notification>> {
+        loop {
+            if registered {
+                missing.extend(self.take_registered());
+            }
+            if missing.is_empty() {
+                break;
+            }
+            let until = missing.iter().filter_map(|service| service.timeout).min().and_then(|timeout| wait.start.checked_add(timeout));
+            let notification = match self.next(wait, until)? {
+                Some(notification) => notification,
+                None => {
+                    let elapsed = wait.start.elapsed();
+                    let overdue = missing.iter()
+                        .filter_map(|service| match service.timeout {
+                            Some(timeout) if elapsed >= timeout => Some((service.name.clone(), timeout)),
+                            _ => None,
+                        })
+                        .collect();
+                    let names = missing.into_iter().map(|service| service.name).collect();
+                    let timeout = self.wait_timeout(wait).services(notifications.len(), names, overdue);
+                    return Err(Error::new(ErrorKind::TimedOut, timeout));
+                }
+            };
+            if let Some(i) = missing.iter().position(|service| notification.name() == Some(service.name.as_str())) {
+                missing.remove(i);
+                notifications.push(notification);
+            }
+        }
+        Ok(notifications)
+    }
+
+    /// Block the currently running thread until the first of several applications has signaled its successful start or the `timeout` period has expired
+    ///
+    /// This is useful for failover tests, which launch redundant instances but only need one of them to come up.
+    /// If services were declared using `expect`, only their notifications are considered. The returned notification identifies the service using `Notification::name`.
+    /// A failure reported by a single instance is skipped, as another instance may still come up. If none does, the last failure is returned instead of a timeout error.
+    pub fn wait_any(mut self, timeout: Duration) -> Result<Notification> {
+        let mut wait = self.start(timeout)?;
+        let mut failure = None;
+        loop {
+            match self.next(&mut wait, None) {
+                Ok(Some(notification)) => {
+                    self.take_registered();
+                    let expected = self.expected.is_empty()
+                        || self.expected.iter().any(|service| notification.name() == Some(service.name.as_str()));
+                    if expected {
+                        return Ok(notification);
+                    }
+                }
+                Ok(None) => return Err(failure.unwrap_or_else(|| self.timed_out(&wait))),
+                Err(e) if e.get_ref().is_some_and(|e| e.is::<StartupFailure>()) => failure = Some(e),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Block the currently running thread until `n` applications have arrived at the startup barrier, then release all of them together
+    ///
+    /// The applications arrive using `Client::wait_for_release`, which blocks until they are released.
+    /// This lets several applications (e.g. load generators) start at the same instant.
+    /// The test itself is the last participant, so the applications are released right after the `n`th one has arrived.
+    /// Startup notifications received while waiting at the barrier are ignored.
+    /// In case of a timeout, the waiting applications are not released but their connections are closed, so `Client::wait_for_release` fails.
+    /// The error wraps a `WaitTimeout` telling how many of them arrived.
+    /// Only TCP servers support barriers.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// let server = test_patience::Server::new().unwrap();
+    /// // start the load generators, which call `Client::wait_for_release`
+    /// server.barrier(3, Duration::from_secs(5)).unwrap();
+    /// ```
+    pub fn barrier(mut self, n: usize, timeout: Duration) -> Result<()> {
+        let mut wait = self.start(timeout)?;
+        let mut arrived = Vec::with_capacity(n);
+        while arrived.len() < n {
+            if self.shutdown.is_shut_down() {
+                return Err(shutdown::cancelled());
+            }
+            if wait.expired() {
+                let timeout = self.wait_timeout(&wait).barrier(arrived.len(), n - arrived.len());
+                return Err(Error::new(ErrorKind::TimedOut, timeout));
+            }
+            match self.listener.receive(self.max_message_size, &mut self.connections)? {
+                Some(Incoming::Arrival(ref headers, _)) if !self.is_authentic(protocol::header(headers, TOKEN_HEADER), protocol::header(headers, RUN_HEADER)) => {}
+                Some(Incoming::Arrival(_, stream)) => arrived.push(stream),
+                Some(_) => {}
+                None => self.sleep(wait.delays.next().unwrap_or_default(), wait.deadline)?,
+            }
+        }
+        for stream in &arrived {
+            stream.set_nonblocking(false)?;
+        }
+        for mut stream in arrived {
+            stream.write_all(RELEASE)?;
+        }
+        Ok(())
+    }
+
+    /// Iterate over the startup notifications as they arrive, waiting up to `timeout_per_item` for each of them
+    ///
+    /// Every notification includes the duration since the call of this method.
+    /// The iterator ends after yielding an error, e.g. because no notification arrived within the timeout period.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// let server = test_patience::Server::new().unwrap();
+    /// for notification in server.iter(Duration::from_secs(5)).take(3) {
+    ///     println!("service ready after {:?}", notification.unwrap().elapsed());
+    /// }
+    /// ```
+    pub fn iter(self, timeout_per_item: Duration) -> Notifications {
+        let wait = Wait::new(timeout_per_item, self.poll_backoff);
+        Notifications { server: self, wait, started: false, done: false }
+    }
+
+    fn start(&mut self, timeout: Duration) -> Result<Wait> {
+        self.listener.start()?;
+        Ok(Wait::new(timeout, self.poll_backoff))
+    }
+
+    /// Receive the next notification, skipping progress updates and, if required, messages without the correct token
+    ///
+    /// Returns `None` if the deadline of the `wait` or the earlier point in time `until` has passed first.
+    fn next(&mut self, wait: &mut Wait, until: Option<Instant>) -> Result<Option<Notification>> {
+        while !wait.expired() && until.is_none_or(|until| Instant::now() < until) {
+            if self.shutdown.is_shut_down() {
+                return Err(shutdown::cancelled());
+            }
+            if let Some(notification) = self.poll(wait)? {
+                return Ok(Some(notification));
+            }
+            let delay = wait.delays.next().unwrap_or_default();
+            self.sleep(delay, wait.deadline.into_iter().chain(until).min())?;
+        }
+        Ok(None)
+    }
+
+    /// Block until a socket of the listener or a heartbeat connection becomes readable, or until the `deadline`
+    ///
+    /// Sockets wake the server as soon as data arrives, so the measured durations don't depend on the poll interval.
+    /// They are still checked after the `delay` of the poll backoff, or at least every `WAKE_INTERVAL`, to notice stopped heartbeats.
+    /// Listeners without sockets, and all listeners on Windows, are polled after the `delay` instead.
+    /// Shutting the server down wakes it right away in both cases.
+    fn sleep(&self, delay: Duration, deadline: Option<Instant>) -> Result<()> {
+        let remaining = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        #[cfg(unix)]
+        {
+            let mut fds = Vec::with_capacity(1 + self.heartbeats.len());
+            if self.fds(&mut fds) {
+                fds.extend(self.shutdown.wake_fd());
+                let interval = delay.max(WAKE_INTERVAL);
+                return transport::readiness::wait(&fds, remaining.map_or(interval, |remaining| remaining.min(interval)));
+            }
+        }
+        self.shutdown.sleep(remaining.map_or(delay, |remaining| remaining.min(delay)));
+        Ok(())
+    }
+
+    /// Add the file descriptors of the listener, its connections and the heartbeat connections to `fds`, see `Listener::fds`
+    #[cfg(unix)]
+    fn fds(&self, fds: &mut Vec<RawFd>) -> bool {
+        if !self.listener.fds(fds) {
+            return false;
+        }
+        fds.extend(self.heartbeats.iter().map(|stream| stream.as_raw_fd()));
+        true
+    }
+
+    /// Handle the pending messages without blocking and return the first notification, if there is one
+    fn poll(&mut self, wait: &mut Wait) -> Result<Option<Notification>> {
+        while let Some(message) = self.receive()? {
+            let message = match Message::decode(&message) {
+                Ok(ref message) if !self.is_authentic(message.get(TOKEN_HEADER), message.get(RUN_HEADER)) => continue,
+                Ok(message) => message,
+                Err(_) if self.require_token => continue,
+                Err(e) if self.ignore_invalid => {
+                    eprintln!("test-patience: ignoring invalid message: {}", e);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            if message.kind != Kind::Progress {
+                let notification = Notification::from_message(message, wait.start.elapsed())?;
+                if let Some(name) = notification.name().filter(|name| self.registry.is_ready(name)) {
+                    match self.duplicates {
+                        Duplicates::Accept => {}
+                        Duplicates::Ignore => continue,
+                        Duplicates::Error => {
+                            return Err(Error::new(ErrorKind::InvalidData, Duplicate(name.to_string())));
+                        }
+                    }
+                }
+                self.registry.record(&notification);
+                return Ok(Some(notification));
+            }
+            if self.reset_on_progress {
+                wait.reset();
+            }
+            wait.progress = Some(String::from_utf8_lossy(&message.body).into_owned());
+        }
+        Ok(None)
+    }
+
+    /// Read from the heartbeat connections and the listener and return the first message, if there is one
+    fn receive(&mut self) -> Result<Option<Vec<u8>>> {
+        for i in 0..self.heartbeats.len() {
+            match receive_heartbeat(&mut self.heartbeats[i], self.heartbeat_timeout) {
+                Ok(None) => {}
+                result => {
+                    self.heartbeats.swap_remove(i);
+                    return result;
+                }
+            }
+        }
+        let incoming = match self.listener.receive(self.max_message_size, &mut self.connections) {
+            // the connection has been dropped already
+            Err(ref e) if self.ignore_invalid && is_invalid_connection(e) => {
+                eprintln!("test-patience: ignoring invalid connection: {}", e);
+                return Ok(None);
+            }
+            result => result?,
+        };
+        match incoming {
+            Some(Incoming::Message(message)) => return Ok(Some(message)),
+            Some(Incoming::Heartbeat(ref headers, _)) if !self.is_authentic(protocol::header(headers, TOKEN_HEADER), protocol::header(headers, RUN_HEADER)) => {}
+            Some(Incoming::Heartbeat(_, stream)) => self.heartbeats.push(stream),
+            // applications can't wait at a barrier while the server waits for their start
+            Some(Incoming::Arrival(..)) | None => {}
+        }
+        Ok(None)
+    }
+
+    /// Error for a `wait` which has run into its timeout, including the last progress update
+    fn timed_out(&self, wait: &Wait) -> Error {
+        Error::new(ErrorKind::TimedOut, self.wait_timeout(wait))
+    }
+
+    /// Context of a `wait` which has run into its timeout, see `timed_out`
+    fn wait_timeout(&self, wait: &Wait) -> WaitTimeout {
+        WaitTimeout::new(wait.timeout, wait.start.elapsed(), self.local_addr().ok(), self.connections, wait.progress.clone())
+    }
+
+    /// Add the services declared using a `ServerHandle` to the expected ones and return them
+    fn take_registered(&mut self) -> Vec<Expected> {
+        let registered: Vec<_> = self.registered.lock().unwrap_or_else(|e| e.into_inner()).drain(..).collect();
+        self.expected.extend(registered.iter().cloned());
+        registered
+    }
+
+    /// Check the `token` of a message, if required, and that its `run` ID, if any, is the one of this server
+    fn is_authentic(&self, token: Option<&str>, run: Option<&str>) -> bool {
+        (!self.require_token || token == Some(self.token.as_str())) && run.is_none_or(|run| run == self.run_id)
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.shutdown.shutdown();
+    }
+}
+
+/// Iterator over the startup notifications received by a server, created by `Server::iter`
+pub struct Notifications {
+    server: Server,
+    wait: Wait,
+    started: bool,
+    done: bool,
+}
+
+impl Notifications {
+    fn receive(&mut self) -> Result<Notification> {
+        if !self.started {
+            self.server.listener.start()?;
+            self.started = true;
+        }
+        self.wait.reset();
+        self.wait.delays = self.server.poll_backoff.delays();
+        match self.server.next(&mut self.wait, None)? {
+            Some(notification) => Ok(notification),
+            None => Err(self.server.timed_out(&self.wait)),
+        }
+    }
+}
+
+impl Iterator for Notifications {
+    type Item = Result<Notification>;
+
+    fn next(&mut self) -> Option<Result<Notification>> {
+        if self.done {
+            return None;
+        }
+        let result = self.receive();
+        self.done = result.is_err();
+        Some(result)
+    }
+}
+
+/// Policy for repeated startup notifications of the same service, e.g. because the application retried sending it, see `Server::duplicates`
+///
+/// Services are identified by the name sent using `Client::notify_named`. Notifications without a name are never duplicates,
+/// as the same application may notify the server again after it has been restarted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Duplicates {
+    /// Every notification counts, even repeated ones (the default)
+    #[default]
+    Accept,
+    /// Skip repeated notifications, so every service counts once, e.g. for `wait_for_n`
+    Ignore,
+    /// End the wait with an error of kind `InvalidData`
+    Error,
+}
+
+/// Service declared using `Server::expect` or `Server::expect_within`
+#[derive(Debug, Clone)]
+struct Expected {
+    name: String,
+    timeout: Option<Duration>,
+}
+
+/// Deadline and last progress update of a call of `wait` or one of its variants
+struct Wait {
+    start: Instant,
+    timeout: Duration,
+    /// Point in time at which the wait times out, `None` if the timeout is too long to be represented
+    deadline: Option<Instant>,
+    progress: Option<String>,
+    /// Delays between the checks for new notifications
+    delays: Delays,
+}
+
+impl Wait {
+    fn new(timeout: Duration, backoff: Backoff) -> Wait {
+        let start = Instant::now();
+        Wait { start, timeout, deadline: start.checked_add(timeout), progress: None, delays: backoff.delays() }
+    }
+
+    /// Measure the timeout from now on
+    fn reset(&mut self) {
+        self.deadline = Instant::now().checked_add(self.timeout);
+    }
+
+    fn expired(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+}
+
+/// Check whether the error `e` of reading from the listener was caused by a single misbehaving connection
+fn is_invalid_connection(e: &Error) -> bool {
+    matches!(e.kind(), ErrorKind::InvalidData | ErrorKind::ConnectionReset)
+}
+
+/// Add the last `lines` of output of the application to the message of the error `e`
+fn with_output(e: Error, lines: &[String]) -> Error {
+    if lines.is_empty() {
+        return e;
+    }
+    Error::new(e.kind(), format!("{}\nlast output of the application:\n{}", e, lines.join("\n")))
+}
+
+/// Format the sorted `ports` compactly, joining consecutive ports to ranges like `40000-40002, 40005`
+fn port_ranges(ports: &[u16]) -> String {
+    let mut ranges: Vec<(u16, u16)> = Vec::new();
+    for &port in ports {
+        match ranges.last_mut() {
+            Some(&mut (_, ref mut end)) if end.checked_add(1) == Some(port) => *end = port,
+            _ => ranges.push((port, port)),
+        }
+    }
+    let ranges: Vec<_> = ranges.into_iter()
+        .map(|(start, end)| if start == end { format!("{}", start) } else { format!("{}-{}", start, end) })
+        .collect();
+    ranges.join(", ")
+}
+
+/// Read the pending heartbeats and return the final message, if it was sent
+fn receive_heartbeat(stream: &mut HeartbeatStream, timeout: Option<Duration>) -> Result<Option<Vec<u8>>> {
+    let message = stream.poll()?;
+    match timeout {
+        Some(timeout) if message.is_none() && stream.last().elapsed() > timeout => {
+            Err(Error::new(ErrorKind::ConnectionAborted, HeartbeatStopped))
+        }
+        _ => Ok(message),
+    }
+}
@@ -0,0 +1,205 @@
+//! Configuration of a TCP server before it's started
+
+use std::io::{Result, Error, ErrorKind};
+use std::net::{IpAddr, SocketAddr, TcpListener};
+use std::time::Duration;
+
+use crate::probe::Backoff;
+use crate::protocol;
+use crate::transport::{self, Listener, Peers};
+use super::{Duplicates, Server, POLL_INTERVAL};
+
+/// Builder for a TCP `Server`, created by `Server::builder`
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// let server = test_patience::Server::builder()
+///     .bind_addr("0.0.0.0:0".parse().unwrap())
+///     .allow_peer("172.17.0.2".parse().unwrap())
+///     .token("secret")
+///     .require_token(true)
+///     .poll_interval(Duration::from_millis(10))
+///     .build()
+///     .unwrap();
+/// # #[allow(unused_variables)]
+/// let port = server.port().unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ServerBuilder {
+    bind_addr: Option<SocketAddr>,
+    token: Option<String>,
+    require_token: bool,
+    poll_backoff: Backoff,
+    heartbeat_timeout: Option<Duration>,
+    reset_on_progress: bool,
+    port_var: Option<String>,
+    peers: Peers,
+    max_message_size: usize,
+    ignore_invalid: bool,
+    measure_overhead: bool,
+    duplicates: Duplicates,
+}
+
+impl ServerBuilder {
+    /// Create a builder for a server listening on a random port of the loopback interface, like `Server::new`
+    pub fn new() -> ServerBuilder {
+        ServerBuilder {
+            bind_addr: None,
+            token: None,
+            require_token: false,
+            poll_backoff: Backoff::fixed(POLL_INTERVAL),
+            heartbeat_timeout: None,
+            reset_on_progress: false,
+            port_var: None,
+            peers: Peers::default(),
+            max_message_size: protocol::MAX_MESSAGE_SIZE,
+            ignore_invalid: false,
+            measure_overhead: false,
+            duplicates: Duplicates::default(),
+        }
+    }
+
+    /// Listen on `addr` instead of a random port of the loopback interface
+    ///
+    /// This is needed for applications which don't run on the same host, e.g. inside a Docker container,
+    /// for which 127.0.0.1 is the container itself instead of the host running the test.
+    /// Binding `0.0.0.0` or the IP of the Docker bridge lets them notify the test, see `Server::local_addr`.
+    /// Their addresses have to be allowed using `allow_peer` or `allow_any_peer`, as only connections from loopback addresses are accepted by default.
+    /// IPv6 addresses like `[::1]:0` are supported as well.
+    /// A port of 0 lets the operating system choose a free port.
+    pub fn bind_addr(mut self, addr: SocketAddr) -> ServerBuilder {
+        self.bind_addr = Some(addr);
+        self
+    }
+
+    /// Accept connections from `ip` in addition to loopback addresses
+    ///
+    /// Connections from other peers are closed right away, so a stray port scanner on the network of a server bound to `0.0.0.0` can't send notifications.
+    pub fn allow_peer(mut self, ip: IpAddr) -> ServerBuilder {
+        match self.peers {
+            Peers::Allowlist(ref mut allowed) => allowed.push(ip),
+            Peers::Any => {}
+        }
+        self
+    }
+
+    /// Accept connections from any address, e.g. from containers whose addresses aren't known in advance
+    pub fn allow_any_peer(mut self) -> ServerBuilder {
+        self.peers = Peers::Any;
+        self
+    }
+
+    /// Use `token` instead of a random token, e.g. because it's hardcoded in the configuration of the application
+    ///
+    /// The token must not be empty or contain whitespace.
+    /// Like the random token, it only keeps concurrent test runs apart and provides no security, see `Server::token`.
+    pub fn token(mut self, token: &str) -> ServerBuilder {
+        self.token = Some(token.to_string());
+        self
+    }
+
+    /// Only accept notifications carrying the token of the server, see `Server::require_token`
+    pub fn require_token(mut self, require: bool) -> ServerBuilder {
+        self.require_token = require;
+        self
+    }
+
+    /// Set the `interval` in which the server checks for new notifications (1 millisecond by default)
+    ///
+    /// A longer interval uses less CPU time while waiting for slow applications, at the cost of noticing their start a little later.
+    /// On Unix, sockets wake the server as soon as a notification arrives instead, and the interval (of at least 10 milliseconds there)
+    /// only determines how soon stopped heartbeats are noticed.
+    /// It still applies to listeners without sockets, like sentinel files, and to all listeners on Windows.
+    pub fn poll_interval(self, interval: Duration) -> ServerBuilder {
+        self.poll_backoff(Backoff::fixed(interval))
+    }
+
+    /// Replace the fixed poll interval by the delays of the `backoff` policy, see `Server::poll_backoff`
+    pub fn poll_backoff(mut self, backoff: Backoff) -> ServerBuilder {
+        self.poll_backoff = backoff;
+        self
+    }
+
+    /// Fail as soon as the application hasn't sent a heartbeat for the `timeout` period, see `Server::heartbeat_timeout`
+    pub fn heartbeat_timeout(mut self, timeout: Duration) -> ServerBuilder {
+        self.heartbeat_timeout = Some(timeout);
+        self
+    }
+
+    /// Measure the timeout of `wait` from the last progress update, see `Server::reset_timeout_on_progress`
+    pub fn reset_timeout_on_progress(mut self, reset: bool) -> ServerBuilder {
+        self.reset_on_progress = reset;
+        self
+    }
+
+    /// Pass the port number in the environment variable `name`, see `Server::port_var`
+    pub fn port_var(mut self, name: &str) -> ServerBuilder {
+        self.port_var = Some(name.to_string());
+        self
+    }
+
+    /// Reject messages larger than `size` bytes, see `Server::max_message_size`
+    pub fn max_message_size(mut self, size: usize) -> ServerBuilder {
+        self.max_message_size = size;
+        self
+    }
+
+    /// Log invalid messages and keep waiting, see `Server::ignore_invalid`
+    pub fn ignore_invalid(mut self, ignore: bool) -> ServerBuilder {
+        self.ignore_invalid = ignore;
+        self
+    }
+
+    /// Ask the application to timestamp its notifications, see `Server::measure_overhead`
+    pub fn measure_overhead(mut self, measure: bool) -> ServerBuilder {
+        self.measure_overhead = measure;
+        self
+    }
+
+    /// Set how repeated startup notifications of the same service are handled, see `Server::duplicates`
+    pub fn duplicates(mut self, policy: Duplicates) -> ServerBuilder {
+        self.duplicates = policy;
+        self
+    }
+
+    /// Start the server
+    ///
+    /// Returns an error of kind `InvalidInput` if the token is invalid, or of kind `AddrInUse` naming the address if it's already taken.
+    pub fn build(self) -> Result<Server> {
+        if let Some(ref token) = self.token {
+            if token.is_empty() || token.chars().any(|c| c.is_whitespace() || c.is_control()) {
+                return Err(Error::new(ErrorKind::InvalidInput, "token must not be empty or contain whitespace"));
+            }
+        }
+        let listener = match self.bind_addr {
+            Some(addr) => TcpListener::bind(addr).map_err(|e| match e.kind() {
+                ErrorKind::AddrInUse => Error::new(ErrorKind::AddrInUse, format!("address {} is already in use", addr)),
+                _ => e,
+            })?,
+            None => transport::bind_loopback(0, TcpListener::bind)?,
+        };
+        let mut server = Server::with_listener(Listener::tcp(listener, self.peers)?);
+        if let Some(token) = self.token {
+            server.token = token;
+        }
+        server.require_token = self.require_token;
+        server.poll_backoff = self.poll_backoff;
+        server.heartbeat_timeout = self.heartbeat_timeout;
+        server.reset_on_progress = self.reset_on_progress;
+        server.max_message_size = self.max_message_size;
+        server.ignore_invalid = self.ignore_invalid;
+        server.measure_overhead = self.measure_overhead;
+        server.duplicates = self.duplicates;
+        if let Some(port_var) = self.port_var {
+            server.port_var = port_var;
+        }
+        Ok(server)
+    }
+}
+
+impl Default for ServerBuilder {
+    fn default() -> ServerBuilder {
+        ServerBuilder::new()
+    }
+}
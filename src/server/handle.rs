@@ -0,0 +1,75 @@
+//! Handle of a server, shared with other threads while the server waits
+
+use std::io::{Result, Error, ErrorKind};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::ShutdownHandle;
+use super::Expected;
+
+/// Cloneable handle of a server, created by `Server::handle`, which can be used by other threads while the server waits
+///
+/// It tells the port of the server, cancels its wait and declares further services for `wait_all`,
+/// so the server doesn't have to be wrapped in a `Mutex`.
+/// Clones refer to the same server.
+///
+/// ```no_run
+/// use std::thread;
+/// use std::time::Duration;
+///
+/// let server = test_patience::Server::new().unwrap().expect(["api"]);
+/// let handle = server.handle();
+/// let waiting = thread::spawn(move || server.wait_all(Duration::from_secs(30)));
+/// // start the api using handle.port(), which turns out to need a worker
+/// handle.expect("worker");
+/// waiting.join().unwrap().unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ServerHandle {
+    local_addr: Option<SocketAddr>,
+    shutdown: ShutdownHandle,
+    registered: Arc<Mutex<Vec<Expected>>>,
+}
+
+impl ServerHandle {
+    pub(super) fn new(local_addr: Option<SocketAddr>, shutdown: ShutdownHandle, registered: Arc<Mutex<Vec<Expected>>>) -> ServerHandle {
+        ServerHandle { local_addr, shutdown, registered }
+    }
+
+    /// Get the port of the server, see `Server::port`
+    pub fn port(&self) -> Result<u16> {
+        self.local_addr().map(|addr| addr.port())
+    }
+
+    /// Get the address the server is listening on, see `Server::local_addr`
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.local_addr.ok_or_else(|| Error::new(ErrorKind::InvalidInput, "server does not listen on a port"))
+    }
+
+    /// Shut the server down, cancelling its pending and later waits, see `ShutdownHandle`
+    pub fn shutdown(&self) {
+        self.shutdown.shutdown();
+    }
+
+    /// Check whether the server has been shut down
+    pub fn is_shut_down(&self) -> bool {
+        self.shutdown.is_shut_down()
+    }
+
+    /// Declare a further service `wait_all` waits for, like `Server::expect`
+    ///
+    /// A running `wait_all` picks the service up with the next notification it receives, so it has to be declared before all previously declared services have started.
+    pub fn expect(&self, name: &str) {
+        self.register(Expected { name: name.to_string(), timeout: None });
+    }
+
+    /// Declare a further service `wait_all` waits for, which has to signal its start within its own `timeout` period, like `Server::expect_within`
+    pub fn expect_within(&self, name: &str, timeout: Duration) {
+        self.register(Expected { name: name.to_string(), timeout: Some(timeout) });
+    }
+
+    fn register(&self, service: Expected) {
+        self.registered.lock().unwrap_or_else(|e| e.into_inner()).push(service);
+    }
+}
@@ -0,0 +1,131 @@
+//! Waiting for many servers from a single thread, with the sockets of all of them registered with one `mio` poller
+
+use std::io::{Result, ErrorKind};
+use std::time::{Duration, Instant};
+
+use mio::{Events, Interest, Poll, Token};
+use mio::unix::SourceFd;
+
+use crate::Notification;
+use crate::shutdown;
+use super::{Server, Wait, WAKE_INTERVAL};
+
+/// Set of servers which are waited for together by a single thread, instead of one thread per server
+///
+/// All of their sockets are registered with one `mio` poller, so the thread only wakes up once one of them becomes readable,
+/// and only reads from the servers whose sockets did.
+/// Servers without sockets, like those watching sentinel files, are polled in their poll interval (or after the delays of their poll backoff) instead.
+/// Shutting down one of the servers, see `ShutdownHandle`, ends the wait right away.
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// let servers: Vec<_> = (0..30).map(|_| test_patience::Server::new().unwrap()).collect();
+/// // start one application per server
+/// let poller = servers.into_iter().fold(test_patience::Poller::new().unwrap(), |poller, server| poller.server(server));
+/// poller.wait_all(Duration::from_secs(30)).unwrap();
+/// ```
+pub struct Poller {
+    poll: Poll,
+    servers: Vec<Server>,
+}
+
+impl Poller {
+    /// Create a poller without any servers
+    pub fn new() -> Result<Poller> {
+        Ok(Poller { poll: Poll::new()?, servers: Vec::new() })
+    }
+
+    /// Add the `server` to the servers which are waited for
+    pub fn server(mut self, server: Server) -> Poller {
+        self.servers.push(server);
+        self
+    }
+
+    /// Block the currently running thread until every server has received a startup notification, or the `timeout` period has expired
+    ///
+    /// Returns the first notification of each server, in the order in which the servers were added.
+    /// Like `Server::wait`, this fails as soon as one of the servers receives an invalid notification or a failure, or has been shut down.
+    /// In case of a timeout, the error wraps the `WaitTimeout` of the first server which hasn't received a notification.
+    pub fn wait_all(mut self, timeout: Duration) -> Result<Vec<Notification>> {
+        let mut waits = Vec::with_capacity(self.servers.len());
+        for server in &mut self.servers {
+            waits.push(server.start(timeout)?);
+        }
+        let mut notifications: Vec<Option<Notification>> = self.servers.iter().map(|_| None).collect();
+        let mut readable = vec![true; self.servers.len()];
+        let mut events = Events::with_capacity(self.servers.len().max(16));
+        let mut checked = Instant::now();
+        loop {
+            for (i, server) in self.servers.iter_mut().enumerate() {
+                if notifications[i].is_some() {
+                    continue;
+                }
+                if server.shutdown.is_shut_down() {
+                    return Err(shutdown::cancelled());
+                }
+                if readable[i] {
+                    notifications[i] = server.poll(&mut waits[i])?;
+                }
+            }
+            let first = match notifications.iter().position(Option::is_none) {
+                Some(first) => first,
+                None => return Ok(notifications.into_iter().flatten().collect()),
+            };
+            if waits[first].expired() {
+                return Err(self.servers[first].timed_out(&waits[first]));
+            }
+            let polled = self.register(&notifications)?;
+            let interval = waits.iter_mut().zip(&polled)
+                .filter(|&(_, &polled)| polled)
+                .fold(WAKE_INTERVAL, |interval, (wait, _)| interval.min(wait.delays.next().unwrap_or_default()));
+            self.sleep(&mut events, &waits[first], interval)?;
+            // all servers are read in the wake interval, so stopped heartbeats are noticed even while other servers keep the thread busy
+            let all = events.is_empty() || checked.elapsed() >= WAKE_INTERVAL;
+            if all {
+                checked = Instant::now();
+            }
+            for (readable, polled) in readable.iter_mut().zip(polled) {
+                *readable = all || polled;
+            }
+            for event in &events {
+                readable[event.token().0] = true;
+            }
+        }
+    }
+
+    /// Register the sockets of the servers which are still waiting for a notification and return which servers have to be polled instead
+    ///
+    /// Sockets accepted since the last call are added, those which are registered already are skipped. Closed ones are removed by the kernel.
+    fn register(&self, notifications: &[Option<Notification>]) -> Result<Vec<bool>> {
+        let mut polled = vec![false; self.servers.len()];
+        let mut fds = Vec::new();
+        for (i, server) in self.servers.iter().enumerate().filter(|&(i, _)| notifications[i].is_none()) {
+            fds.clear();
+            if !server.fds(&mut fds) {
+                polled[i] = true;
+            }
+            // shutting a server down wakes the thread right away
+            fds.extend(server.shutdown.wake_fd());
+            for fd in &fds {
+                match self.poll.registry().register(&mut SourceFd(fd), Token(i), Interest::READABLE) {
+                    Err(ref e) if e.kind() == ErrorKind::AlreadyExists => {}
+                    result => result?,
+                }
+            }
+        }
+        Ok(polled)
+    }
+
+    /// Block until a registered socket becomes readable, the `interval` has passed, or the `wait` has expired
+    fn sleep(&mut self, events: &mut Events, wait: &Wait, interval: Duration) -> Result<()> {
+        let timeout = wait.deadline.map_or(interval, |deadline| deadline.saturating_duration_since(Instant::now()).min(interval));
+        match self.poll.poll(events, Some(timeout)) {
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => {
+                events.clear();
+                Ok(())
+            }
+            result => result,
+        }
+    }
+}
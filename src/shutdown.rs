@@ -0,0 +1,113 @@
+//! Cancellation of waits running in other threads
+
+use std::error;
+use std::fmt;
+use std::io::{Error, ErrorKind};
+#[cfg(unix)]
+use std::io::prelude::*;
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Handle shutting down a server, which can be held by the test while a wait runs in another thread
+///
+/// Shutting the server down wakes the pending wait of the server right away, and fails all later ones, with an error of kind `Interrupted`
+/// wrapping a marker which `Error::from` turns into `Error::Cancelled`.
+/// This lets a test which bails out early unblock the thread waiting for the application, instead of leaving it hanging until its timeout.
+/// Clones shut down the same server.
+///
+/// ```no_run
+/// use std::thread;
+/// use std::time::Duration;
+///
+/// let server = test_patience::Server::new().unwrap();
+/// let shutdown = server.shutdown_handle();
+/// let waiting = thread::spawn(move || server.wait(Duration::from_secs(60)));
+/// // the test bails out early
+/// shutdown.shutdown();
+/// assert!(waiting.join().unwrap().is_err());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownHandle {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    shut_down: AtomicBool,
+    /// Lock of `condvar`, which wakes waits sleeping between two polls
+    lock: Mutex<()>,
+    condvar: Condvar,
+    /// Socket pair, whose reading end becomes readable once the server has been shut down, waking waits blocking on their sockets
+    #[cfg(unix)]
+    wake: Option<(UnixStream, UnixStream)>,
+}
+
+impl Default for Inner {
+    fn default() -> Inner {
+        Inner {
+            shut_down: AtomicBool::new(false),
+            lock: Mutex::new(()),
+            condvar: Condvar::new(),
+            // without it, waits blocking on their sockets notice the shutdown in their poll interval
+            #[cfg(unix)]
+            wake: UnixStream::pair().ok(),
+        }
+    }
+}
+
+impl ShutdownHandle {
+    /// Shut the server down, cancelling its pending and later waits
+    pub fn shutdown(&self) {
+        self.inner.shut_down.store(true, Ordering::SeqCst);
+        #[cfg(unix)]
+        {
+            if let Some((_, ref writer)) = self.inner.wake {
+                let _ = writer.set_nonblocking(true);
+                let _ = (&*writer).write(&[0]);
+            }
+        }
+        let _lock = self.inner.lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.inner.condvar.notify_all();
+    }
+
+    /// Check whether the server has been shut down, either using `shutdown` or because it was closed or dropped
+    pub fn is_shut_down(&self) -> bool {
+        self.inner.shut_down.load(Ordering::SeqCst)
+    }
+
+    /// Sleep for the `timeout`, or until the server is shut down
+    pub(crate) fn sleep(&self, timeout: Duration) {
+        let lock = self.inner.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = self.inner.condvar.wait_timeout_while(lock, timeout, |_| !self.is_shut_down());
+    }
+
+    /// Get the file descriptor which becomes readable once the server has been shut down, if it could be created
+    ///
+    /// It stays readable from then on, as the server can't be restarted.
+    #[cfg(unix)]
+    pub(crate) fn wake_fd(&self) -> Option<RawFd> {
+        self.inner.wake.as_ref().map(|(reader, _)| reader.as_raw_fd())
+    }
+}
+
+/// Error for a wait cancelled by shutting down its server
+pub(crate) fn cancelled() -> Error {
+    Error::new(ErrorKind::Interrupted, Cancelled)
+}
+
+/// Marker wrapped in the errors of cancelled waits, which tells them apart from other interruptions
+#[derive(Debug)]
+pub(crate) struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "wait was cancelled by shutting down the server")
+    }
+}
+
+impl error::Error for Cancelled {}
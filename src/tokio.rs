@@ -0,0 +1,83 @@
+//! Asynchronous server and client for applications running on a [tokio](https://tokio.rs) runtime
+//!
+//! This module re-exports the runtime-agnostic [`asynchronous`](../asynchronous/index.html) implementation,
+//! which can be awaited from tokio tasks without blocking a worker thread of the runtime.
+//! Applications can signal their successful start from inside their async startup path using [`Client::notify_async`](../struct.Client.html#method.notify_async).
+//!
+//! # Examples
+//!
+//! Test
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use std::process;
+//!
+//! # async fn run() {
+//! let server = test_patience::tokio::Server::new().await.unwrap();
+//! let port = server.port().unwrap();
+//!
+//! # #[allow(unused_variables)]
+//! let process = process::Command::new("path/to/application")
+//!     .env("TEST_PATIENCE_PORT", format!("{}", port))
+//!     .spawn()
+//!     .unwrap();
+//!
+//! server.wait(Duration::from_secs(5)).await.unwrap();
+//! # }
+//! ```
+//!
+//! Test using [`spawn_and_wait`](fn.spawn_and_wait.html)
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use tokio::process::Command;
+//!
+//! # async fn run() {
+//! let server = test_patience::tokio::Server::new().await.unwrap();
+//!
+//! # #[allow(unused_variables)]
+//! let (child, notification) = tokio::time::timeout(
+//!     Duration::from_secs(5),
+//!     test_patience::tokio::spawn_and_wait(server, &mut Command::new("path/to/application")),
+//! ).await.unwrap().unwrap();
+//! # }
+//! ```
+
+use std::io::{Result, Error};
+
+use ::tokio::process::{Child, Command};
+use futures_lite::FutureExt;
+
+use crate::{EarlyExit, Notification};
+
+pub use crate::asynchronous::Server;
+
+/// Spawn the application with the environment variables of `Server::env` set and wait until it has signaled its successful start
+///
+/// The returned future resolves to the spawned child together with the received notification,
+/// or to an error in case of an invalid startup notification or an `EarlyExit` error if the application exits before signaling its start.
+/// On error the application is killed.
+/// Like [`Server::notified`](../asynchronous/struct.Server.html#method.notified) it has no timeout of its own.
+pub async fn spawn_and_wait(server: Server, command: &mut Command) -> Result<(Child, Notification)> {
+    // the application only finds servers listening on a TCP port
+    server.port()?;
+    let mut child = command.envs(server.env()).spawn()?;
+
+    let notification = {
+        let exited = async {
+            let status = child.wait().await?;
+            Err(Error::other(EarlyExit::new(status)))
+        };
+        server.notified().or(exited).await
+    };
+
+    match notification {
+        Ok(notification) => Ok((child, notification)),
+        Err(e) => {
+            // don't leave the application running if it can't be handed back to the caller
+            let _ = child.start_kill();
+            let _ = child.wait().await;
+            Err(e)
+        }
+    }
+}
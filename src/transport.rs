@@ -0,0 +1,351 @@
+//! Listeners the server can receive startup notifications on
+
+use std::io::{BufReader, Result, Error, ErrorKind};
+use std::io::prelude::*;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, UdpSocket};
+#[cfg(target_os = "linux")]
+use std::fs::File;
+use std::process::ChildStdout;
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::fs;
+
+use crate::protocol::{self, Headers, Kind, Message, BARRIER_LINE, HEARTBEAT_LINE};
+use self::connection::Connection;
+use self::heartbeat::HeartbeatStream;
+
+pub mod connection;
+pub mod heartbeat;
+pub mod pipe;
+#[cfg(unix)]
+pub mod readiness;
+#[cfg(unix)]
+pub mod signal;
+#[cfg(target_os = "linux")]
+pub mod vsock;
+
+/// Maximum size of a UDP datagram payload
+const MAX_DATAGRAM_SIZE: usize = 65_507;
+
+/// Addresses of `port` on the loopback interface, IPv4 first
+pub fn loopback(port: u16) -> [SocketAddr; 2] {
+    [SocketAddr::from((Ipv4Addr::LOCALHOST, port)), SocketAddr::from((Ipv6Addr::LOCALHOST, port))]
+}
+
+/// Bind a socket to `port` of the IPv4 loopback interface using `bind`, falling back to the IPv6 one on hosts without IPv4
+///
+/// Other errors, like the port being in use, are returned right away,
+/// so a server doesn't end up on `::1` while clients connecting to `127.0.0.1` reach another one.
+pub fn bind_loopback<T, F: FnMut(SocketAddr) -> Result<T>>(port: u16, mut bind: F) -> Result<T> {
+    let [ipv4, ipv6] = loopback(port);
+    match bind(ipv4) {
+        Err(ref e) if e.kind() == ErrorKind::AddrNotAvailable => bind(ipv6),
+        result => result,
+    }
+}
+
+/// Peers a TCP listener accepts connections from, only loopback addresses by default
+#[derive(Debug, Clone)]
+pub enum Peers {
+    /// Loopback addresses and the listed ones
+    Allowlist(Vec<IpAddr>),
+    /// Any address
+    Any,
+}
+
+impl Peers {
+    /// Check whether connections from `ip` are accepted
+    ///
+    /// IPv4 addresses mapped to IPv6 ones by dual-stack sockets are compared as IPv4 addresses.
+    pub fn allows(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        match *self {
+            Peers::Allowlist(ref allowed) => ip.is_loopback() || allowed.iter().any(|allowed| allowed.to_canonical() == ip),
+            Peers::Any => true,
+        }
+    }
+}
+
+impl Default for Peers {
+    fn default() -> Peers {
+        Peers::Allowlist(Vec::new())
+    }
+}
+
+/// Raise the backlog of `listener` to the maximum, so bursts of clients connecting before the server accepts them aren't dropped
+///
+/// The standard library listens with a backlog of 128 connections. Listening again only changes the backlog.
+#[cfg(unix)]
+fn raise_backlog(listener: &TcpListener) -> Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    if unsafe { libc::listen(listener.as_raw_fd(), libc::SOMAXCONN) } != 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Windows ignores calls of `listen` for sockets which are already listening, so the backlog stays as it is
+#[cfg(windows)]
+fn raise_backlog(_: &TcpListener) -> Result<()> {
+    Ok(())
+}
+
+/// Connection or message received by a listener
+pub enum Incoming {
+    Message(Vec<u8>),
+    /// Heartbeat connection with the headers of its preamble, which is kept open while the application is starting
+    Heartbeat(Headers, HeartbeatStream),
+    /// Connection of an application waiting at a startup barrier, with the headers of its preamble
+    Arrival(Headers, TcpStream),
+}
+
+/// Listener, together with the accepted connections of stream sockets which haven't been closed yet
+pub enum Listener {
+    /// TCP listener, with the messages, heartbeat connections, arrivals and errors read from its connections which haven't been returned yet
+    Tcp(TcpListener, Vec<Connection<TcpStream>>, Peers, VecDeque<Result<Incoming>>),
+    /// UDP socket, receiving one datagram per notification
+    Udp(UdpSocket),
+    /// Unix domain socket, with the path of its socket file unless it's bound in the abstract namespace
+    #[cfg(unix)]
+    Unix(UnixListener, Option<PathBuf>, Vec<Connection<UnixStream>>),
+    /// Piped stdout of a child process, scanned for a marker line by a background thread
+    Stdout(Receiver<Result<()>>),
+    /// Sentinel file, which is polled until it exists
+    File(PathBuf),
+    /// `SIGUSR1` sent to the test process
+    #[cfg(unix)]
+    Signal(signal::Registration),
+    /// Anonymous pipe, whose write end is inherited by the application
+    Pipe(pipe::Pipe),
+    /// vsock stream socket, accepting connections from VM guests
+    #[cfg(target_os = "linux")]
+    Vsock(vsock::VsockListener, Vec<Connection<File>>),
+}
+
+impl Listener {
+    /// Wrap the TCP `listener`, accepting connections from `peers`, after raising its backlog
+    pub fn tcp(listener: TcpListener, peers: Peers) -> Result<Listener> {
+        raise_backlog(&listener)?;
+        Ok(Listener::Tcp(listener, Vec::new(), peers, VecDeque::new()))
+    }
+
+    /// Prepare the listener for `receive`
+    ///
+    /// Sockets are switched to non-blocking mode, and the test closes its copy of the pipe's write end.
+    pub fn start(&mut self) -> Result<()> {
+        match *self {
+            Listener::Tcp(ref listener, ..) => listener.set_nonblocking(true),
+            Listener::Udp(ref socket) => socket.set_nonblocking(true),
+            #[cfg(unix)]
+            Listener::Unix(ref listener, ..) => listener.set_nonblocking(true),
+            Listener::Pipe(ref mut pipe) => {
+                pipe.start();
+                Ok(())
+            }
+            #[cfg(target_os = "linux")]
+            Listener::Vsock(ref listener, _) => listener.set_nonblocking(true),
+            _ => Ok(()),
+        }
+    }
+
+    /// Add the file descriptors of the listener and its open connections to `fds`, which become readable once there's something to `receive`
+    ///
+    /// Returns `false` for listeners without sockets, which have to be polled instead.
+    #[cfg(unix)]
+    pub fn fds(&self, fds: &mut Vec<RawFd>) -> bool {
+        match *self {
+            Listener::Tcp(ref listener, ref connections, ..) => {
+                fds.push(listener.as_raw_fd());
+                fds.extend(connections.iter().map(|connection| connection.as_raw_fd()));
+            }
+            Listener::Udp(ref socket) => fds.push(socket.as_raw_fd()),
+            Listener::Unix(ref listener, _, ref connections) => {
+                fds.push(listener.as_raw_fd());
+                fds.extend(connections.iter().map(|connection| connection.as_raw_fd()));
+            }
+            #[cfg(target_os = "linux")]
+            Listener::Vsock(ref listener, ref connections) => {
+                fds.push(listener.as_raw_fd());
+                fds.extend(connections.iter().map(|connection| connection.as_raw_fd()));
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Scan the `stdout` of a child process for the `marker` line in a background thread
+    pub fn stdout(stdout: ChildStdout, marker: &str) -> Listener {
+        let (sender, receiver) = mpsc::channel();
+        let marker = marker.to_string();
+        thread::spawn(move || {
+            let mut lines = BufReader::new(stdout).lines();
+            let found = loop {
+                match lines.next() {
+                    Some(Ok(ref line)) if *line == marker => break Ok(()),
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => break Err(e),
+                    None => break Err(Error::new(ErrorKind::UnexpectedEof, "application closed its stdout without printing the marker line")),
+                }
+            };
+            let _ = sender.send(found);
+            // keep draining stdout, so the application doesn't block on a full pipe
+            for _ in lines {}
+        });
+        Listener::Stdout(receiver)
+    }
+
+    /// Accept the pending connections and return the first complete message, if there is one
+    ///
+    /// Accepted connections are read without blocking, so applications which connect but never close their connection can't stall the server.
+    /// Framed messages are returned as soon as they are complete, unframed ones once their connection is closed.
+    /// A TCP listener reads all of its connections once and queues what they received, which is returned by the following calls.
+    /// TCP connections starting with the heartbeat preamble are returned as heartbeat connections,
+    /// and those starting with the barrier preamble as arrivals.
+    /// For UDP sockets, a pending datagram is received instead.
+    /// For the stdout of a child process, the marker line is translated into a regular startup notification.
+    /// For sentinel files, the existence of the file is translated into a regular startup notification carrying the file's content as payload.
+    /// The same applies to readiness signals.
+    /// Messages exceeding `max_len` bytes are rejected with an error of kind `InvalidData`, see `protocol::check_size`.
+    /// The number of accepted connections and received datagrams is added to `accepted`.
+    /// The listener has to be started.
+    pub fn receive(&mut self, max_len: usize, accepted: &mut usize) -> Result<Option<Incoming>> {
+        let received = match *self {
+            Listener::Tcp(ref listener, ref mut connections, ref peers, ref mut pending) => {
+                if let Some(incoming) = pending.pop_front() {
+                    return incoming.map(Some);
+                }
+                *accepted += connection::accept_all(connections, max_len, || loop {
+                    let (stream, peer) = listener.accept()?;
+                    // dropping the stream closes connections of other peers right away
+                    if peers.allows(peer.ip()) {
+                        stream.set_nonblocking(true)?;
+                        return Ok(stream);
+                    }
+                })?;
+                read_tcp(connections, pending);
+                return pending.pop_front().transpose();
+            }
+            Listener::Udp(ref socket) => {
+                let mut buf = [0; MAX_DATAGRAM_SIZE];
+                let datagram = socket.recv(&mut buf).map(|len| buf[..len].to_vec());
+                if datagram.is_ok() {
+                    *accepted += 1;
+                }
+                match datagram {
+                    Ok(ref datagram) if datagram.len() > max_len => Err(protocol::too_large(max_len)),
+                    datagram => datagram,
+                }
+            }
+            #[cfg(unix)]
+            Listener::Unix(ref listener, _, ref mut connections) => {
+                *accepted += connection::accept_all(connections, max_len, || {
+                    let (stream, _) = listener.accept()?;
+                    stream.set_nonblocking(true)?;
+                    Ok(stream)
+                })?;
+                connection::read_all(connections)?.ok_or_else(|| ErrorKind::WouldBlock.into())
+            }
+            Listener::Stdout(ref receiver) => match receiver.try_recv() {
+                Ok(found) => found.map(|()| b"done".to_vec()),
+                Err(TryRecvError::Empty) => Err(ErrorKind::WouldBlock.into()),
+                Err(TryRecvError::Disconnected) => Err(Error::other("marker line was already received")),
+            },
+            Listener::Pipe(ref pipe) => pipe.try_recv(),
+            #[cfg(target_os = "linux")]
+            Listener::Vsock(ref listener, ref mut connections) => {
+                *accepted += connection::accept_all(connections, max_len, || listener.accept())?;
+                connection::read_all(connections)?.ok_or_else(|| ErrorKind::WouldBlock.into())
+            }
+            Listener::File(ref path) => match fs::read(path) {
+                Ok(ref payload) if payload.is_empty() => Ok(b"done".to_vec()),
+                Ok(payload) => Ok(Message::new(Kind::Ready).body(payload).encode()),
+                Err(ref e) if e.kind() == ErrorKind::NotFound => Err(ErrorKind::WouldBlock.into()),
+                Err(e) => Err(e),
+            },
+            #[cfg(unix)]
+            Listener::Signal(ref registration) => if registration.received() {
+                Ok(b"done".to_vec())
+            } else {
+                Err(ErrorKind::WouldBlock.into())
+            },
+        };
+        match received {
+            Ok(message) => Ok(Some(Incoming::Message(message))),
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e)
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        match *self {
+            #[cfg(unix)]
+            Listener::Unix(_, Some(ref path), _) => {
+                let _ = fs::remove_file(path);
+            }
+            Listener::File(ref path) => {
+                let _ = fs::remove_file(path);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Read from all open TCP connections once, queueing their complete messages, heartbeat connections and arrivals in `pending`
+///
+/// Reading every connection once per call, instead of starting over after each message, keeps a burst of hundreds of clients from causing a quadratic number of reads.
+/// Connections which fail to be read are dropped, and the error is queued in the order in which it occurred.
+fn read_tcp(connections: &mut Vec<Connection<TcpStream>>, pending: &mut VecDeque<Result<Incoming>>) {
+    let mut i = 0;
+    while i < connections.len() {
+        let closed = match connection::read(connections, i) {
+            Ok(closed) => closed,
+            Err(e) => {
+                pending.push_back(Err(e));
+                continue;
+            }
+        };
+        if connections[i].buf().starts_with(HEARTBEAT_LINE) {
+            if let Some((headers, len)) = heartbeat::preamble(connections[i].buf(), closed) {
+                let stream = HeartbeatStream::new(connections.swap_remove(i), len);
+                pending.push_back(Ok(Incoming::Heartbeat(headers, stream)));
+                continue;
+            }
+        } else if connections[i].buf().starts_with(BARRIER_LINE) {
+            match protocol::decode_headers(&connections[i].buf()[BARRIER_LINE.len()..]) {
+                Ok(Some((headers, _))) => {
+                    let stream = connections.swap_remove(i).into_stream();
+                    pending.push_back(Ok(Incoming::Arrival(headers, stream)));
+                    continue;
+                }
+                Ok(None) if !closed => {}
+                // incomplete or invalid preamble
+                _ => {
+                    connections.swap_remove(i);
+                    continue;
+                }
+            }
+        } else {
+            let open = connections.len();
+            while let Some(message) = connection::take_message(connections, i, closed) {
+                pending.push_back(Ok(Incoming::Message(message)));
+                if connections.len() < open {
+                    break;
+                }
+            }
+            // the connection has been closed and all of its messages have been taken
+            if connections.len() < open {
+                continue;
+            }
+        }
+        i += 1;
+    }
+}
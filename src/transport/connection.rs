@@ -0,0 +1,123 @@
+//! Accepted stream connections, which are read without blocking
+
+use std::io::{Result, ErrorKind};
+use std::io::prelude::*;
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use crate::protocol;
+
+/// Non-blocking stream, buffering the data sent by the application until it closes the connection
+pub struct Connection<S> {
+    stream: S,
+    buf: Vec<u8>,
+    framed: bool,
+    /// Maximum size of a message
+    max_len: usize,
+}
+
+impl<S: Read> Connection<S> {
+    /// Wrap the `stream`, which has to be in non-blocking mode, receiving messages of up to `max_len` bytes
+    pub fn new(stream: S, max_len: usize) -> Connection<S> {
+        Connection { stream, buf: Vec::new(), framed: false, max_len }
+    }
+
+    /// Read the data available without blocking
+    ///
+    /// Returns `true` once the application has closed the connection,
+    /// or an error of kind `InvalidData` as soon as the message being received exceeds the maximum size.
+    pub fn read(&mut self) -> Result<bool> {
+        let mut chunk = [0; 1024];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Ok(true),
+                Ok(len) => {
+                    self.buf.extend_from_slice(&chunk[..len]);
+                    protocol::check_size(&self.buf, self.max_len)?;
+                }
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => return Ok(false),
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Get the data received so far
+    pub fn buf(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Get mutable access to the data received so far
+    pub fn buf_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.buf
+    }
+
+    pub fn into_stream(self) -> S {
+        self.stream
+    }
+
+    /// Take the next complete message out of the data received so far, see `protocol::take_message`
+    pub fn take_message(&mut self, closed: bool) -> Option<Vec<u8>> {
+        protocol::take_message(&mut self.buf, &mut self.framed, closed)
+    }
+}
+
+#[cfg(unix)]
+impl<S: AsRawFd> AsRawFd for Connection<S> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+}
+
+/// Accept connections receiving messages of up to `max_len` bytes using `accept` until there are no more pending ones and return how many were accepted
+pub fn accept_all<S, F>(connections: &mut Vec<Connection<S>>, max_len: usize, mut accept: F) -> Result<usize>
+    where S: Read, F: FnMut() -> Result<S>
+{
+    let mut accepted = 0;
+    loop {
+        match accept() {
+            Ok(stream) => {
+                connections.push(Connection::new(stream, max_len));
+                accepted += 1;
+            }
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => return Ok(accepted),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Read from all open `connections` and return the first complete message
+///
+/// Connections are dropped once they have been closed by the application and all of their messages have been taken.
+#[cfg(unix)]
+pub fn read_all<S: Read>(connections: &mut Vec<Connection<S>>) -> Result<Option<Vec<u8>>> {
+    let mut i = 0;
+    while i < connections.len() {
+        let closed = read(connections, i)?;
+        if let Some(message) = take_message(connections, i, closed) {
+            return Ok(Some(message));
+        }
+        if !closed {
+            i += 1;
+        }
+    }
+    Ok(None)
+}
+
+/// Read from the connection at index `i`, dropping it if reading fails, see `Connection::read`
+pub fn read<S: Read>(connections: &mut Vec<Connection<S>>, i: usize) -> Result<bool> {
+    let result = connections[i].read();
+    if result.is_err() {
+        connections.swap_remove(i);
+    }
+    result
+}
+
+/// Take the next complete message of the connection at index `i`, dropping the connection if it's `closed` and has no further messages
+pub fn take_message<S: Read>(connections: &mut Vec<Connection<S>>, i: usize, closed: bool) -> Option<Vec<u8>> {
+    let message = connections[i].take_message(closed);
+    if closed && connections[i].buf().is_empty() {
+        connections.swap_remove(i);
+    }
+    message
+}
@@ -0,0 +1,102 @@
+//! Heartbeat connection, kept open by the application while it's starting
+
+use std::io::{Result, Error, ErrorKind};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::Instant;
+
+use crate::error::HeartbeatClosed;
+use crate::protocol::{self, Headers, HEARTBEAT_LINE, PING};
+use super::connection::Connection;
+
+/// Receiving end of a heartbeat connection, after the preamble has been read
+pub struct HeartbeatStream {
+    connection: Connection<TcpStream>,
+    last: Instant,
+}
+
+impl HeartbeatStream {
+    /// Continue reading from `connection`, whose buffer starts with a preamble of `len` bytes
+    pub fn new(mut connection: Connection<TcpStream>, len: usize) -> HeartbeatStream {
+        connection.buf_mut().drain(..len);
+        let mut heartbeat = HeartbeatStream { connection, last: Instant::now() };
+        heartbeat.consume_pings();
+        heartbeat
+    }
+
+    /// Get the point in time at which the last heartbeat was received
+    pub fn last(&self) -> Instant {
+        self.last
+    }
+
+    /// Read the pending heartbeats and return the final message, once the application has sent it and closed the connection
+    pub fn poll(&mut self) -> Result<Option<Vec<u8>>> {
+        let closed = self.connection.read()?;
+        self.consume_pings();
+        if !closed {
+            return Ok(None);
+        }
+        let buf = self.connection.buf_mut();
+        if buf.is_empty() {
+            return Err(closed_early());
+        }
+        Ok(Some(buf.split_off(0)))
+    }
+
+    fn consume_pings(&mut self) {
+        if strip_pings(self.connection.buf_mut()) {
+            self.last = Instant::now();
+        }
+    }
+}
+
+#[cfg(unix)]
+impl AsRawFd for HeartbeatStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.connection.as_raw_fd()
+    }
+}
+
+/// Split the data of a heartbeat connection, which has already been closed by the application, into the headers of its preamble and the final message
+///
+/// The final message is empty if the application closed the connection without signaling its start.
+#[cfg(feature = "async")]
+pub fn split(mut buf: Vec<u8>) -> (Headers, Vec<u8>) {
+    let (headers, len) = preamble(&buf, true).unwrap_or_else(|| (Vec::new(), buf.len()));
+    buf.drain(..len);
+    strip_pings(&mut buf);
+    (headers, buf)
+}
+
+/// Error for a heartbeat connection closed without a startup notification
+pub fn closed_early() -> Error {
+    Error::new(ErrorKind::ConnectionAborted, HeartbeatClosed)
+}
+
+/// Remove the heartbeats at the start of `buf` and return whether there were any
+fn strip_pings(buf: &mut Vec<u8>) -> bool {
+    let mut pings = 0;
+    while buf[pings * PING.len()..].starts_with(PING) {
+        pings += 1;
+    }
+    buf.drain(..pings * PING.len());
+    pings > 0
+}
+
+/// Parse the preamble at the start of `buf`, which begins with the first line of a heartbeat connection
+///
+/// Returns the headers of the preamble together with its length, or `None` if it's still incomplete and the connection is not `closed` yet.
+/// Without a header block, the first line is the whole preamble.
+pub fn preamble(buf: &[u8], closed: bool) -> Option<(Headers, usize)> {
+    let rest = &buf[HEARTBEAT_LINE.len()..];
+    if rest.starts_with(PING) {
+        return Some((Vec::new(), HEARTBEAT_LINE.len()));
+    }
+    match protocol::decode_headers(rest) {
+        Ok(Some((headers, len))) => Some((headers, HEARTBEAT_LINE.len() + len)),
+        Ok(None) if !closed => None,
+        // the final message follows the first line directly
+        _ => Some((Vec::new(), HEARTBEAT_LINE.len())),
+    }
+}
@@ -0,0 +1,122 @@
+//! Anonymous pipe, whose write end is inherited by the application
+
+use std::env;
+use std::fs::File;
+use std::io::{self, PipeReader, PipeWriter, Result, Error, ErrorKind};
+use std::io::prelude::*;
+use std::process::Command;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+/// Environment variable used to pass the inherited file descriptor or handle to the application
+pub const PIPE_VAR: &str = "TEST_PATIENCE_PIPE";
+
+pub struct Pipe {
+    message: Option<Receiver<Result<Vec<u8>>>>,
+    reader: Option<PipeReader>,
+    writer: Option<PipeWriter>,
+}
+
+impl Pipe {
+    pub fn new() -> Result<Pipe> {
+        let (reader, writer) = io::pipe()?;
+        Ok(Pipe { message: None, reader: Some(reader), writer: Some(writer) })
+    }
+
+    /// Let the application spawned by `command` inherit the write end of the pipe
+    pub fn inherit(&self, command: &mut Command) -> Result<()> {
+        let writer = self.writer.as_ref()
+            .ok_or_else(|| Error::other("pipe was already closed"))?;
+        let value = inherit(writer, command)?;
+        command.env(PIPE_VAR, value);
+        Ok(())
+    }
+
+    /// Close the write end held by the test and start reading in a background thread
+    ///
+    /// Once the application has closed its write end, too, the message is complete.
+    pub fn start(&mut self) {
+        self.writer = None;
+        if let Some(mut reader) = self.reader.take() {
+            let (sender, message) = mpsc::channel();
+            thread::spawn(move || {
+                let mut buf = Vec::new();
+                let message = reader.read_to_end(&mut buf).and_then(|_| if buf.is_empty() {
+                    Err(Error::new(ErrorKind::UnexpectedEof, "application closed the pipe without signaling its start"))
+                } else {
+                    Ok(buf)
+                });
+                let _ = sender.send(message);
+            });
+            self.message = Some(message);
+        }
+    }
+
+    /// Get the message, if the application has closed its write end
+    ///
+    /// Returns an error of kind `WouldBlock` if the message isn't complete yet.
+    pub fn try_recv(&self) -> Result<Vec<u8>> {
+        let message = self.message.as_ref()
+            .ok_or_else(|| Error::other("pipe was not started"))?;
+        match message.try_recv() {
+            Ok(message) => message,
+            Err(TryRecvError::Empty) => Err(ErrorKind::WouldBlock.into()),
+            Err(TryRecvError::Disconnected) => Err(Error::other("message was already read from the pipe")),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn inherit(writer: &PipeWriter, command: &mut Command) -> Result<String> {
+    use std::os::unix::io::AsRawFd;
+    use std::os::unix::process::CommandExt;
+
+    let fd = writer.as_raw_fd();
+    unsafe {
+        command.pre_exec(move || {
+            if libc::fcntl(fd, libc::F_SETFD, 0) == -1 {
+                return Err(Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    Ok(format!("{}", fd))
+}
+
+#[cfg(windows)]
+fn inherit(writer: &PipeWriter, _: &mut Command) -> Result<String> {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::Foundation::{SetHandleInformation, HANDLE_FLAG_INHERIT};
+
+    let handle = writer.as_raw_handle();
+    if unsafe { SetHandleInformation(handle, HANDLE_FLAG_INHERIT, HANDLE_FLAG_INHERIT) } == 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(format!("{}", handle as usize))
+}
+
+/// Write `message` to the pipe inherited from the test
+pub fn notify(message: &[u8]) -> Result<()> {
+    let value = env::var(PIPE_VAR)
+        .map_err(|_| Error::new(ErrorKind::NotFound, "no pipe was inherited from the test"))?;
+    let mut file = open(&value)?;
+    file.write_all(message)
+}
+
+#[cfg(unix)]
+fn open(value: &str) -> Result<File> {
+    use std::os::unix::io::{FromRawFd, RawFd};
+
+    let fd = value.parse::<RawFd>()
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "invalid inherited file descriptor"))?;
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+#[cfg(windows)]
+fn open(value: &str) -> Result<File> {
+    use std::os::windows::io::{FromRawHandle, RawHandle};
+
+    let handle = value.parse::<usize>()
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "invalid inherited handle"))?;
+    Ok(unsafe { File::from_raw_handle(handle as RawHandle) })
+}
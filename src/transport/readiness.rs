@@ -0,0 +1,23 @@
+//! Waiting for sockets to become readable, so the server wakes up as soon as a notification arrives instead of sleeping
+
+use std::io::{Result, Error, ErrorKind};
+use std::os::raw::c_int;
+use std::os::unix::io::RawFd;
+use std::time::Duration;
+
+/// Block until one of the `fds` is readable, or the `timeout` has passed
+///
+/// `poll` only supports timeouts in whole milliseconds, so the timeout is rounded up, and a wait never ends before its deadline.
+/// Readiness wakes it right away though, so notifications are noticed without delay.
+/// An interrupted wait returns early as well.
+pub fn wait(fds: &[RawFd], timeout: Duration) -> Result<()> {
+    let mut fds: Vec<_> = fds.iter().map(|&fd| libc::pollfd { fd, events: libc::POLLIN, revents: 0 }).collect();
+    let millis = timeout.as_micros().div_ceil(1000).min(c_int::MAX as u128) as c_int;
+    if unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, millis) } == -1 {
+        let e = Error::last_os_error();
+        if e.kind() != ErrorKind::Interrupted {
+            return Err(e);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,74 @@
+//! `SIGUSR1` handler counting readiness signals sent to the test process
+
+use std::convert::TryFrom;
+use std::io::{Result, Error, ErrorKind};
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+static RECEIVED: AtomicUsize = AtomicUsize::new(0);
+static IN_USE: AtomicBool = AtomicBool::new(false);
+/// Set once the handler has been installed successfully, so a failed installation is retried by the next registration
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn handle(_: libc::c_int) {
+    RECEIVED.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Exclusive registration for readiness signals
+///
+/// The handler stays installed after the registration is dropped, so late signals don't terminate the test process.
+pub struct Registration {
+    seen: usize,
+}
+
+impl Registration {
+    pub fn new() -> Result<Registration> {
+        if IN_USE.swap(true, Ordering::SeqCst) {
+            return Err(Error::new(ErrorKind::AddrInUse, "another server is already waiting for a readiness signal"));
+        }
+        // only one registration exists at a time, so installing can't race
+        if !INSTALLED.load(Ordering::SeqCst) {
+            if let Err(e) = install() {
+                IN_USE.store(false, Ordering::SeqCst);
+                return Err(e);
+            }
+            INSTALLED.store(true, Ordering::SeqCst);
+        }
+        Ok(Registration { seen: RECEIVED.load(Ordering::SeqCst) })
+    }
+
+    /// Check whether a signal has arrived since the registration was created
+    pub fn received(&self) -> bool {
+        RECEIVED.load(Ordering::SeqCst) != self.seen
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        IN_USE.store(false, Ordering::SeqCst);
+    }
+}
+
+fn install() -> Result<()> {
+    unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = handle as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        if libc::sigaction(libc::SIGUSR1, &action, ptr::null_mut()) != 0 {
+            return Err(Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Send `SIGUSR1` to the process `pid`
+pub fn notify(pid: u32) -> Result<()> {
+    let pid = libc::pid_t::try_from(pid)
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "invalid process id"))?;
+    if unsafe { libc::kill(pid, libc::SIGUSR1) } != 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(())
+}
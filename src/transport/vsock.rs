@@ -0,0 +1,93 @@
+//! Minimal `AF_VSOCK` stream sockets for communicating between a VM guest and its host
+
+use std::fs::File;
+use std::io::{Result, Error};
+use std::mem;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+/// Listening vsock stream socket
+pub struct VsockListener {
+    fd: OwnedFd,
+}
+
+impl VsockListener {
+    /// Listen on any CID of the host and a port chosen by the kernel
+    pub fn bind() -> Result<VsockListener> {
+        let fd = socket()?;
+        let addr = sockaddr(libc::VMADDR_CID_ANY, libc::VMADDR_PORT_ANY);
+        unsafe {
+            if libc::bind(fd.as_raw_fd(), &addr as *const _ as *const libc::sockaddr, mem::size_of_val(&addr) as libc::socklen_t) != 0 {
+                return Err(Error::last_os_error());
+            }
+            if libc::listen(fd.as_raw_fd(), 128) != 0 {
+                return Err(Error::last_os_error());
+            }
+        }
+        Ok(VsockListener { fd })
+    }
+
+    pub fn local_port(&self) -> Result<u32> {
+        let mut addr: libc::sockaddr_vm = unsafe { mem::zeroed() };
+        let mut len = mem::size_of_val(&addr) as libc::socklen_t;
+        if unsafe { libc::getsockname(self.fd.as_raw_fd(), &mut addr as *mut _ as *mut libc::sockaddr, &mut len) } != 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(addr.svm_port)
+    }
+
+    pub fn set_nonblocking(&self, nonblocking: bool) -> Result<()> {
+        let fd = self.fd.as_raw_fd();
+        unsafe {
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+            if flags == -1 {
+                return Err(Error::last_os_error());
+            }
+            let flags = if nonblocking { flags | libc::O_NONBLOCK } else { flags & !libc::O_NONBLOCK };
+            if libc::fcntl(fd, libc::F_SETFL, flags) == -1 {
+                return Err(Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    /// Accept a connection, which is returned in non-blocking mode
+    pub fn accept(&self) -> Result<File> {
+        let fd = unsafe { libc::accept4(self.fd.as_raw_fd(), std::ptr::null_mut(), std::ptr::null_mut(), libc::SOCK_CLOEXEC | libc::SOCK_NONBLOCK) };
+        if fd == -1 {
+            return Err(Error::last_os_error());
+        }
+        Ok(File::from(unsafe { OwnedFd::from_raw_fd(fd) }))
+    }
+}
+
+impl AsRawFd for VsockListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+/// Connect to `port` of the VM or host `cid`
+pub fn connect(cid: u32, port: u32) -> Result<File> {
+    let fd = socket()?;
+    let addr = sockaddr(cid, port);
+    if unsafe { libc::connect(fd.as_raw_fd(), &addr as *const _ as *const libc::sockaddr, mem::size_of_val(&addr) as libc::socklen_t) } != 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(File::from(fd))
+}
+
+fn socket() -> Result<OwnedFd> {
+    let fd = unsafe { libc::socket(libc::AF_VSOCK, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0) };
+    if fd == -1 {
+        return Err(Error::last_os_error());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+fn sockaddr(cid: u32, port: u32) -> libc::sockaddr_vm {
+    let mut addr: libc::sockaddr_vm = unsafe { mem::zeroed() };
+    addr.svm_family = libc::AF_VSOCK as libc::sa_family_t;
+    addr.svm_cid = cid;
+    addr.svm_port = port;
+    addr
+}
@@ -0,0 +1,367 @@
+#![cfg(feature = "async")]
+
+extern crate test_patience;
+
+use std::time::Duration;
+use std::thread;
+use std::io::{self, Write};
+use std::net::TcpStream;
+
+use futures_lite::{future, StreamExt};
+
+fn mock_client(port: u16, sleep: Duration) {
+    thread::sleep(sleep);
+    let _ = test_patience::Client::notify(port); // ignore errors when testing timeout
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wait_for_client() {
+        future::block_on(async {
+            let server = test_patience::asynchronous::Server::new().await.expect("failed to create test-patience server");
+            let port = server.port().expect("failed to get test-patience server port");
+
+            thread::spawn(move || {
+                mock_client(port, Duration::from_millis(500));
+            });
+
+            let wait_duration = server.wait(Duration::from_secs(5)).await.expect("failed to wait").elapsed();
+
+            assert!(wait_duration >= Duration::from_millis(500));
+            assert!(wait_duration < Duration::from_secs(5));
+        });
+    }
+
+    #[test]
+    fn wait_for_client_timeout() {
+        future::block_on(async {
+            let server = test_patience::asynchronous::Server::new().await.expect("failed to create test-patience server");
+            let port = server.port().expect("failed to get test-patience server port");
+
+            thread::spawn(move || {
+                mock_client(port, Duration::from_secs(2));
+            });
+
+            let result = server.wait(Duration::from_secs(1)).await;
+            assert!(result.is_err());
+            let error = result.unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+            match test_patience::Error::from(error) {
+                test_patience::Error::Timeout(timeout) => {
+                    assert_eq!(timeout.timeout(), Duration::from_secs(1));
+                    assert_eq!(timeout.local_addr().map(|addr| addr.port()), Some(port));
+                }
+                e => panic!("unexpected error: {}", e),
+            }
+        });
+    }
+
+    #[test]
+    fn wait_for_client_with_token() {
+        future::block_on(async {
+            let server = test_patience::asynchronous::Server::new().await.expect("failed to create test-patience server")
+                .require_token(true);
+            let port = server.port().expect("failed to get test-patience server port");
+            let token = server.token().to_string();
+
+            thread::spawn(move || {
+                test_patience::Client::notify_with_token(port, "wrong").expect("failed to notify");
+                mock_client(port, Duration::from_millis(100));
+                thread::sleep(Duration::from_millis(100));
+                test_patience::Client::notify_with_token(port, &token).expect("failed to notify");
+            });
+
+            let wait_duration = server.wait(Duration::from_secs(5)).await.expect("failed to wait").elapsed();
+
+            assert!(wait_duration >= Duration::from_millis(200));
+        });
+    }
+
+    #[test]
+    fn wait_for_client_silent_connections() {
+        future::block_on(async {
+            let server = test_patience::asynchronous::Server::new().await.expect("failed to create test-patience server");
+            let port = server.port().expect("failed to get test-patience server port");
+
+            let _silent = TcpStream::connect(("127.0.0.1", port)).expect("failed to connect");
+            let mut stalled = TcpStream::connect(("127.0.0.1", port)).expect("failed to connect");
+            stalled.write_all(b"do").expect("failed to write");
+            thread::spawn(move || {
+                mock_client(port, Duration::from_millis(200));
+            });
+
+            let wait_duration = server.wait(Duration::from_secs(5)).await.expect("failed to wait").elapsed();
+
+            assert!(wait_duration >= Duration::from_millis(200));
+            assert!(wait_duration < Duration::from_secs(1));
+        });
+    }
+
+    #[test]
+    fn reject_other_run_id() {
+        future::block_on(async {
+            let server = test_patience::asynchronous::Server::new().await.expect("failed to create test-patience server");
+            let port = server.port().expect("failed to get test-patience server port");
+            let run_id = server.run_id().to_string();
+
+            thread::spawn(move || {
+                let mut stale = TcpStream::connect(("127.0.0.1", port)).expect("failed to connect");
+                stale.write_all(b"done\nrun: 0123456789abcdef\n\n").expect("failed to write");
+                drop(stale);
+                thread::sleep(Duration::from_millis(200));
+                let mut current = TcpStream::connect(("127.0.0.1", port)).expect("failed to connect");
+                current.write_all(format!("done\nrun: {}\n\n", run_id).as_bytes()).expect("failed to write");
+            });
+
+            let wait_duration = server.wait(Duration::from_secs(5)).await.expect("failed to wait").elapsed();
+
+            assert!(wait_duration >= Duration::from_millis(200));
+        });
+    }
+
+    #[test]
+    fn notify_async() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        let waiter = thread::spawn(move || server.wait(Duration::from_secs(5)));
+
+        future::block_on(test_patience::Client::notify_async(port)).expect("failed to notify");
+
+        waiter.join().unwrap().expect("failed to wait").elapsed();
+    }
+
+    #[test]
+    fn incoming() {
+        future::block_on(async {
+            let server = test_patience::asynchronous::Server::new().await.expect("failed to create test-patience server");
+            let port = server.port().expect("failed to get test-patience server port");
+
+            thread::spawn(move || {
+                mock_client(port, Duration::from_millis(200));
+                mock_client(port, Duration::from_millis(200));
+            });
+
+            let mut incoming = server.incoming();
+            let first = incoming.next().await.unwrap().expect("failed to receive notification");
+            let second = incoming.next().await.unwrap().expect("failed to receive notification");
+
+            assert!(first.elapsed() >= Duration::from_millis(200));
+            assert!(second.elapsed() >= Duration::from_millis(400));
+        });
+    }
+
+    #[test]
+    fn incoming_named() {
+        future::block_on(async {
+            let server = test_patience::asynchronous::Server::new().await.expect("failed to create test-patience server");
+            let port = server.port().expect("failed to get test-patience server port");
+
+            thread::spawn(move || {
+                test_patience::Client::notify_named(port, "db-proxy").expect("failed to notify");
+                thread::sleep(Duration::from_millis(100));
+                test_patience::Client::notify_named(port, "search").expect("failed to notify");
+            });
+
+            let mut incoming = server.incoming();
+            let first = incoming.next().await.unwrap().expect("failed to receive notification");
+            let second = incoming.next().await.unwrap().expect("failed to receive notification");
+            drop(incoming);
+
+            assert_eq!(first.name(), Some("db-proxy"));
+            assert_eq!(second.name(), Some("search"));
+            assert_eq!(server.reported(), vec!["db-proxy".to_string(), "search".to_string()]);
+        });
+    }
+
+    #[test]
+    fn incoming_with_idle_connection() {
+        future::block_on(async {
+            let server = test_patience::asynchronous::Server::new().await.expect("failed to create test-patience server");
+            let port = server.port().expect("failed to get test-patience server port");
+
+            let idle = TcpStream::connect(("127.0.0.1", port)).expect("failed to connect");
+            thread::spawn(move || {
+                mock_client(port, Duration::from_millis(200));
+            });
+
+            let mut incoming = server.incoming();
+            let notification = incoming.next().await.unwrap().expect("failed to receive notification");
+            drop(idle);
+
+            assert!(notification.elapsed() >= Duration::from_millis(200));
+        });
+    }
+
+    #[test]
+    fn wait_for_session() {
+        future::block_on(async {
+            let server = test_patience::asynchronous::Server::new().await.expect("failed to create test-patience server");
+            let port = server.port().expect("failed to get test-patience server port");
+
+            thread::spawn(move || {
+                let mut session = test_patience::Client::connect(port).expect("failed to connect");
+                session.progress("running migrations").expect("failed to send progress");
+                thread::sleep(Duration::from_millis(200));
+                session.notify().expect("failed to notify");
+            });
+
+            let wait_duration = server.wait(Duration::from_secs(5)).await.expect("failed to wait").elapsed();
+
+            assert!(wait_duration >= Duration::from_millis(200));
+            assert!(wait_duration < Duration::from_secs(5));
+        });
+    }
+
+    #[test]
+    fn wait_for_frame_on_open_connection() {
+        future::block_on(async {
+            let server = test_patience::asynchronous::Server::new().await.expect("failed to create test-patience server");
+            let port = server.port().expect("failed to get test-patience server port");
+
+            let mut stream = TcpStream::connect(("127.0.0.1", port)).expect("failed to connect");
+            stream.write_all(b"\0\0\0\x04done").expect("failed to write");
+
+            let notification = server.wait(Duration::from_secs(2)).await;
+            drop(stream);
+
+            notification.expect("failed to wait");
+        });
+    }
+
+    #[test]
+    fn wait_for_fragmented_frame() {
+        future::block_on(async {
+            let server = test_patience::asynchronous::Server::new().await.expect("failed to create test-patience server");
+            let port = server.port().expect("failed to get test-patience server port");
+
+            thread::spawn(move || {
+                let mut stream = TcpStream::connect(("127.0.0.1", port)).expect("failed to connect");
+                stream.set_nodelay(true).expect("failed to disable nagle");
+                for chunk in [&b"\0"[..], b"\0\0", b"\x10done\nname:", b" api\n\n"] {
+                    stream.write_all(chunk).expect("failed to write");
+                    thread::sleep(Duration::from_millis(50));
+                }
+                thread::sleep(Duration::from_secs(5));
+            });
+
+            let notification = server.wait(Duration::from_secs(2)).await.expect("failed to wait");
+            assert_eq!(notification.name(), Some("api"));
+        });
+    }
+
+    #[test]
+    fn wait_for_heartbeat_client() {
+        future::block_on(async {
+            let server = test_patience::asynchronous::Server::new().await.expect("failed to create test-patience server");
+            let port = server.port().expect("failed to get test-patience server port");
+
+            thread::spawn(move || {
+                let heartbeat = test_patience::Client::heartbeat(port, Duration::from_millis(50)).expect("failed to open heartbeat connection");
+                thread::sleep(Duration::from_millis(200));
+                heartbeat.notify().expect("failed to notify");
+            });
+
+            let wait_duration = server.wait(Duration::from_secs(5)).await.expect("failed to wait").elapsed();
+
+            assert!(wait_duration >= Duration::from_millis(200));
+            assert!(wait_duration < Duration::from_secs(5));
+        });
+    }
+
+    #[test]
+    fn wait_for_heartbeat_client_died() {
+        future::block_on(async {
+            let server = test_patience::asynchronous::Server::new().await.expect("failed to create test-patience server");
+            let port = server.port().expect("failed to get test-patience server port");
+
+            thread::spawn(move || {
+                let heartbeat = test_patience::Client::heartbeat(port, Duration::from_millis(50)).expect("failed to open heartbeat connection");
+                thread::sleep(Duration::from_millis(200));
+                drop(heartbeat);
+            });
+
+            let result = server.wait(Duration::from_secs(5)).await;
+            assert!(result.is_err());
+            assert_eq!(result.unwrap_err().kind(), io::ErrorKind::ConnectionAborted);
+        });
+    }
+
+    #[test]
+    fn wait_cancellable() {
+        future::block_on(async {
+            let server = test_patience::asynchronous::Server::new().await.expect("failed to create test-patience server");
+            let port = server.port().expect("failed to get test-patience server port");
+
+            thread::spawn(move || {
+                mock_client(port, Duration::from_secs(2));
+            });
+
+            let cancel = async {
+                async_io::Timer::after(Duration::from_millis(200)).await;
+            };
+            let result = server.wait_cancellable(Duration::from_secs(5), cancel).await;
+            assert!(result.is_err());
+            assert_eq!(result.unwrap_err().kind(), io::ErrorKind::Interrupted);
+        });
+    }
+
+    #[test]
+    fn wait_cancellable_not_cancelled() {
+        future::block_on(async {
+            let server = test_patience::asynchronous::Server::new().await.expect("failed to create test-patience server");
+            let port = server.port().expect("failed to get test-patience server port");
+
+            thread::spawn(move || {
+                mock_client(port, Duration::from_millis(200));
+            });
+
+            server.wait_cancellable(Duration::from_secs(5), future::pending()).await.expect("failed to wait").elapsed();
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn wait_for_unix_client() {
+        let path = std::env::temp_dir().join(format!("test-patience-async-{}.sock", std::process::id()));
+
+        future::block_on(async {
+            let server = test_patience::asynchronous::Server::new_unix(&path).await.expect("failed to create test-patience server");
+            assert!(server.port().is_err());
+
+            let client_path = path.clone();
+            let client = async move {
+                async_io::Timer::after(Duration::from_millis(200)).await;
+                test_patience::Client::notify_unix_async(client_path).await.expect("failed to notify");
+            };
+
+            let (wait_duration, ()) = future::zip(server.wait(Duration::from_secs(5)), client).await;
+
+            assert!(wait_duration.expect("failed to wait").elapsed() >= Duration::from_millis(200));
+        });
+
+        assert!(!path.exists());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn wait_for_abstract_client() {
+        let name = format!("test-patience-async-{}", std::process::id());
+
+        future::block_on(async {
+            let server = test_patience::asynchronous::Server::new_abstract(&name).await.expect("failed to create test-patience server");
+
+            let client = async {
+                async_io::Timer::after(Duration::from_millis(200)).await;
+                test_patience::Client::notify_abstract_async(&name).await.expect("failed to notify");
+            };
+
+            let (wait_duration, ()) = future::zip(server.wait(Duration::from_secs(5)), client).await;
+
+            assert!(wait_duration.expect("failed to wait").elapsed() >= Duration::from_millis(200));
+        });
+    }
+}
@@ -0,0 +1,72 @@
+#![cfg(feature = "async")]
+
+extern crate test_patience;
+
+use std::time::{Instant, Duration};
+use std::thread;
+use std::io;
+use std::net::TcpListener;
+
+use futures_lite::future;
+use test_patience::asynchronous::probe::{self, Backoff, Probe};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tcp_probes_concurrently() {
+        let first = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let first_addr = first.local_addr().unwrap();
+        let second_addr = {
+            let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+            listener.local_addr().unwrap()
+        };
+
+        let late = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(500));
+            TcpListener::bind(second_addr).unwrap()
+        });
+
+        let (first_result, second_result) = future::block_on(future::zip(
+            probe::tcp(first_addr).wait(Duration::from_secs(5)),
+            probe::tcp(second_addr).wait(Duration::from_secs(5)),
+        ));
+
+        assert!(first_result.expect("first probe failed") < Duration::from_millis(500));
+        assert!(second_result.expect("second probe failed") >= Duration::from_millis(500));
+        drop(late.join());
+    }
+
+    #[test]
+    fn tcp_probe_timeout() {
+        let addr = {
+            let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+            listener.local_addr().unwrap()
+        };
+
+        let result = future::block_on(probe::tcp(addr).interval(Duration::from_millis(50)).wait(Duration::from_millis(300)));
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn tcp_probe_with_backoff() {
+        let addr = {
+            let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+            listener.local_addr().unwrap()
+        };
+        let late = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            TcpListener::bind(addr).unwrap()
+        });
+
+        let start = Instant::now();
+        let backoff = Backoff::exponential(Duration::from_millis(200), Duration::from_secs(1));
+        future::block_on(probe::tcp(addr).with_backoff(backoff).wait(Duration::from_secs(5))).expect("probe failed");
+
+        // the second attempt is made after 200 ms
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        drop(late.join());
+    }
+}
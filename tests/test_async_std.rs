@@ -0,0 +1,62 @@
+#![cfg(feature = "async-std")]
+
+extern crate test_patience;
+
+use std::time::Duration;
+use std::thread;
+use std::io;
+
+fn mock_client(port: u16, sleep: Duration) {
+    thread::sleep(sleep);
+    let _ = test_patience::Client::notify(port); // ignore errors when testing timeout
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn wait_for_client() {
+        let server = test_patience::async_std::Server::new().await.expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            mock_client(port, Duration::from_millis(500));
+        });
+
+        let wait_duration = server.wait(Duration::from_secs(5)).await.expect("failed to wait").elapsed();
+
+        assert!(wait_duration >= Duration::from_millis(500));
+        assert!(wait_duration < Duration::from_secs(5));
+    }
+
+    #[async_std::test]
+    async fn wait_for_client_timeout() {
+        let server = test_patience::async_std::Server::new().await.expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            mock_client(port, Duration::from_secs(2));
+        });
+
+        let result = server.wait(Duration::from_secs(1)).await;
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[async_std::test]
+    async fn notify_async() {
+        let server = test_patience::async_std::Server::new().await.expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        async_std::task::spawn(async move {
+            async_std::task::sleep(Duration::from_millis(500)).await;
+            test_patience::Client::notify_async(port).await.expect("failed to notify");
+        });
+
+        let wait_duration = server.wait(Duration::from_secs(5)).await.expect("failed to wait").elapsed();
+
+        assert!(wait_duration >= Duration::from_millis(500));
+        assert!(wait_duration < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,208 @@
+#![cfg(feature = "cli")]
+
+extern crate test_patience;
+
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::net::TcpListener;
+use std::thread;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use test_patience::probe::Probe;
+
+/// Command running the `test-patience` binary
+fn cli() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_test-patience"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notify() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server").require_token(true).unwrap();
+        let mut command = cli();
+        command.args(["notify", "--name", "db"]);
+
+        let (mut child, notification) = server.spawn_and_wait(&mut command, Duration::from_secs(5)).expect("failed to wait");
+
+        assert_eq!(notification.name(), Some("db"));
+        assert!(child.wait().expect("failed to reap cli").success());
+    }
+
+    #[test]
+    fn notify_failure() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        let status = cli().args(["notify", "--failure=no database"]).arg(format!("--port={}", port)).status().expect("failed to run cli");
+        let result = server.wait(Duration::from_secs(5));
+
+        assert!(status.success());
+        let failure = result.unwrap_err();
+        let failure = failure.get_ref().and_then(|e| e.downcast_ref::<test_patience::StartupFailure>()).expect("expected startup failure");
+        assert_eq!(failure.reason(), "no database");
+    }
+
+    #[test]
+    fn notify_usage_error() {
+        let output = cli().args(["notify", "--port", "1", "--verbose", "yes"]).output().expect("failed to run cli");
+
+        assert_eq!(output.status.code(), Some(2));
+        assert!(String::from_utf8_lossy(&output.stderr).starts_with("test-patience: unknown option --verbose\n"));
+    }
+
+    #[test]
+    fn notify_connection_refused() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+
+        let status = cli().args(["notify", "--port"]).arg(port.to_string()).status().expect("failed to run cli");
+
+        assert_eq!(status.code(), Some(1));
+    }
+
+    #[test]
+    fn wait() {
+        let mut waiter = cli().args(["wait", "--timeout", "5s"]).stdout(Stdio::piped()).spawn().expect("failed to run cli");
+        let mut port = String::new();
+        BufReader::new(waiter.stdout.take().unwrap()).read_line(&mut port).expect("failed to read port");
+        let port = port.trim().parse::<u16>().expect("invalid port");
+
+        test_patience::Client::notify(port).expect("failed to notify");
+
+        assert!(waiter.wait().expect("failed to reap cli").success());
+    }
+
+    #[test]
+    fn wait_export() {
+        let mut waiter = cli().args(["wait", "--export"]).stdout(Stdio::piped()).spawn().expect("failed to run cli");
+        let mut line = String::new();
+        BufReader::new(waiter.stdout.take().unwrap()).read_line(&mut line).expect("failed to read port");
+        let port = line.trim().strip_prefix("export TEST_PATIENCE_PORT=").expect("unexpected output").parse::<u16>().expect("invalid port");
+
+        test_patience::Client::notify(port).expect("failed to notify");
+
+        assert!(waiter.wait().expect("failed to reap cli").success());
+    }
+
+    #[test]
+    fn wait_port_file() {
+        let path = std::env::temp_dir().join(format!("test-patience-cli-port-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut waiter = cli().args(["wait", "--port-file"]).arg(&path).spawn().expect("failed to run cli");
+
+        test_patience::probe::file(&path).wait(Duration::from_secs(5)).expect("port file not written");
+        let port = fs::read_to_string(&path).unwrap().trim().parse::<u16>().expect("invalid port");
+        test_patience::Client::notify(port).expect("failed to notify");
+
+        assert!(waiter.wait().expect("failed to reap cli").success());
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn wait_timeout() {
+        let output = cli().args(["wait", "--timeout=100ms"]).output().expect("failed to run cli");
+
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&output.stderr).starts_with("test-patience: did not receive startup notification"));
+    }
+
+    #[test]
+    fn wait_invalid_duration() {
+        let output = cli().args(["wait", "--timeout", "soon"]).output().expect("failed to run cli");
+
+        assert_eq!(output.status.code(), Some(2));
+    }
+
+    #[test]
+    fn wait_overflowing_duration() {
+        let output = cli().args(["wait", "--timeout", "18446744073709551615m"]).output().expect("failed to run cli");
+
+        assert_eq!(output.status.code(), Some(2));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("invalid duration"));
+    }
+
+    #[test]
+    fn wait_for() {
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let path = std::env::temp_dir().join(format!("test-patience-cli-wait-for-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let created = path.clone();
+        let late = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            fs::File::create(created).unwrap();
+            TcpListener::bind(addr).unwrap()
+        });
+
+        let status = cli().arg("wait-for").arg(format!("tcp://{}", addr)).arg(format!("file://{}", path.display()))
+            .args(["--interval", "10ms"])
+            .status()
+            .expect("failed to run cli");
+
+        assert!(status.success());
+        drop(late.join());
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn wait_for_timeout() {
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+
+        let output = cli().arg("wait-for").arg(format!("tcp://{}", addr)).args(["--timeout", "200ms"]).output().expect("failed to run cli");
+
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.starts_with(&format!("test-patience: probe did not succeed (TCP probe of {}: ", addr)), "{}", stderr);
+    }
+
+    #[test]
+    fn wait_for_unsupported_target() {
+        let output = cli().args(["wait-for", "ftp://127.0.0.1"]).output().expect("failed to run cli");
+
+        assert_eq!(output.status.code(), Some(2));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exec_keeps_running() {
+        let output = cli().args(["exec", "--", "bash", "-c", "printf done > /dev/tcp/127.0.0.1/$TEST_PATIENCE_PORT; echo started; exit 3"])
+            .output()
+            .expect("failed to run cli");
+
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(output.stdout, b"started\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exec_detach() {
+        let start = std::time::Instant::now();
+
+        let status = cli().args(["exec", "--detach", "--", "bash", "-c", "printf done > /dev/tcp/127.0.0.1/$TEST_PATIENCE_PORT; exec sleep 2"])
+            .stdout(Stdio::null())
+            .status()
+            .expect("failed to run cli");
+
+        assert!(status.success());
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exec_exited() {
+        let output = cli().args(["exec", "--", "bash", "-c", "exit 3"]).output().expect("failed to run cli");
+
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&output.stderr).starts_with("test-patience: application exited before signaling its start"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exec_timeout() {
+        let output = cli().args(["exec", "--timeout", "200ms", "--", "sleep", "5"]).output().expect("failed to run cli");
+
+        assert_eq!(output.status.code(), Some(1));
+    }
+}
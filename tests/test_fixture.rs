@@ -0,0 +1,80 @@
+#![cfg(unix)]
+
+extern crate test_patience;
+
+use std::net::TcpListener;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use test_patience::fixture;
+use test_patience::fixture::{Fixture, Readiness};
+use test_patience::probe;
+
+static TORN_DOWN: AtomicBool = AtomicBool::new(false);
+
+fixture! {
+    notifying {
+        cmd: "bash",
+        args: ["-c", "printf \"done\n\n$PAYLOAD\" > /dev/tcp/127.0.0.1/$TEST_PATIENCE_PORT; sleep 5"],
+        env: { "PAYLOAD" => "db" },
+    }
+
+    torn_down {
+        cmd: "sleep",
+        args: ["5"],
+        ready: Readiness::probe(probe::custom(|| true, Duration::from_millis(10)), Duration::from_secs(1)),
+        teardown: |_| TORN_DOWN.store(true, Ordering::SeqCst),
+    }
+
+    never_ready {
+        cmd: "sleep",
+        args: ["5"],
+        ready: Readiness::probe(probe::custom(|| false, Duration::from_millis(10)), Duration::from_millis(100)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn start_and_stop() {
+        let mut fixture = notifying::start();
+        assert_eq!(fixture.notification().expect("no notification").payload(), b"db");
+        assert!(fixture.child().try_wait().expect("failed to check application").is_none());
+        notifying::stop(fixture);
+    }
+
+    #[test]
+    fn teardown() {
+        let fixture = torn_down::start();
+        assert!(fixture.notification().is_none());
+        torn_down::stop(fixture);
+        assert!(TORN_DOWN.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    #[should_panic(expected = "failed to start fixture never_ready")]
+    fn not_ready() {
+        never_ready::start();
+    }
+
+    #[test]
+    fn probe_port_and_teardown_reaping_child() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind listener");
+        let probe = probe::tcp(listener.local_addr().expect("failed to get address"));
+        let reaped = Arc::new(AtomicBool::new(false));
+        let flag = reaped.clone();
+        let fixture = Fixture::start(Command::new("sleep").arg("5"), Readiness::probe(probe, Duration::from_secs(5)))
+            .expect("fixture failed to start")
+            .teardown(move |child| {
+                let _ = child.kill();
+                let _ = child.wait();
+                flag.store(true, Ordering::SeqCst);
+            });
+        assert!(fixture.stop().is_ok());
+        assert!(reaped.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,75 @@
+#![cfg(feature = "serde")]
+
+extern crate test_patience;
+
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+use test_patience::Handshake;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wait_for_handshake_client() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server")
+            .require_token(true).expect("failed to require token");
+        let handshake = server.handshake().expect("failed to get handshake");
+        assert_eq!(handshake.port, server.port().expect("failed to get test-patience server port"));
+        assert_eq!(handshake.token, server.token());
+        assert_eq!(handshake.run_id, server.run_id());
+
+        let json = handshake.to_json();
+        thread::spawn(move || {
+            let handshake = Handshake::from_json(&json).expect("failed to parse handshake");
+            test_patience::Client::notify_handshake(&handshake).expect("failed to notify");
+        });
+
+        server.wait(Duration::from_secs(5)).expect("failed to wait for client");
+    }
+
+    #[test]
+    fn handshake_round_trip() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let handshake = server.handshake().expect("failed to get handshake");
+
+        assert_eq!(Handshake::from_json(&handshake.to_json()).expect("failed to parse handshake"), handshake);
+        assert_eq!(handshake.addr(), server.local_addr().expect("failed to get test-patience server address"));
+    }
+
+    #[test]
+    fn handshake_newer_version() {
+        let error = Handshake::from_json(r#"{"port":1234,"address":"127.0.0.1","token":"secret","version":1000}"#).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "unsupported handshake version 1000");
+    }
+
+    #[test]
+    fn handshake_invalid_json() {
+        let error = Handshake::from_json(r#"{"port":1234}"#).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().starts_with("invalid handshake"));
+    }
+
+    #[test]
+    fn handshake_udp_server() {
+        let server = test_patience::Server::new_udp().expect("failed to create test-patience server");
+        let error = server.handshake().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn handshake_with_timestamp() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server")
+            .measure_overhead(true);
+        let handshake = server.handshake().expect("failed to get handshake");
+        assert!(handshake.timestamp);
+
+        thread::spawn(move || test_patience::Client::notify_handshake(&handshake).expect("failed to notify"));
+
+        let notification = server.wait(Duration::from_secs(5)).expect("failed to wait for client");
+        assert!(notification.overhead().is_some());
+    }
+}
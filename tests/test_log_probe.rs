@@ -0,0 +1,90 @@
+#![cfg(feature = "regex")]
+
+extern crate test_patience;
+
+use std::io;
+#[cfg(unix)]
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use test_patience::probe;
+
+#[cfg(unix)]
+fn spawn(script: &str) -> std::process::Child {
+    Command::new("bash").arg("-c").arg(script)
+        .stdout(Stdio::piped()).stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to spawn application")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn log_match() {
+        let mut child = spawn("echo starting; sleep 0.1; echo 'Listening on 127.0.0.1:8080' >&2; sleep 5");
+
+        let found = probe::log(&mut child, r"Listening on (?P<host>[^:]+):(\d+)").expect("failed to create probe")
+            .wait(Duration::from_secs(5))
+            .expect("failed to wait");
+
+        assert_eq!(found.line(), "Listening on 127.0.0.1:8080");
+        assert_eq!(found.name("host"), Some("127.0.0.1"));
+        assert_eq!(found.get(2), Some("8080"));
+        assert_eq!(found.get(3), None);
+        child.kill().expect("failed to kill application");
+        child.wait().expect("failed to reap application");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn log_match_without_deadline() {
+        let mut child = spawn("sleep 0.1; echo Listening; sleep 5");
+
+        let found = probe::log(&mut child, "Listening").expect("failed to create probe")
+            .wait(Duration::MAX)
+            .expect("failed to wait");
+
+        assert_eq!(found.line(), "Listening");
+        child.kill().expect("failed to kill application");
+        child.wait().expect("failed to reap application");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn log_timeout() {
+        let mut child = spawn("echo starting; sleep 5");
+
+        let result = probe::log(&mut child, "Listening").expect("failed to create probe").wait(Duration::from_millis(200));
+
+        let e = result.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(e.to_string(), "no line of output matched Listening within 200ms (last line: starting)");
+        child.kill().expect("failed to kill application");
+        child.wait().expect("failed to reap application");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn log_exited() {
+        let mut child = spawn("echo crashing; exit 1");
+
+        let result = probe::log(&mut child, "Listening").expect("failed to create probe").wait(Duration::from_secs(5));
+
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        child.wait().expect("failed to reap application");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn log_invalid_pattern() {
+        let mut child = spawn("true");
+
+        let result = probe::log(&mut child, "(");
+
+        assert_eq!(result.err().map(|e| e.kind()), Some(io::ErrorKind::InvalidInput));
+        child.wait().expect("failed to reap application");
+    }
+}
@@ -0,0 +1,32 @@
+#![cfg(all(feature = "macros", unix))]
+
+extern crate test_patience;
+
+use test_patience::{ChildGuard, Notification};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_patience::test(cmd = "bash", args = ["-c", "printf 'done\n\ndb' > /dev/tcp/127.0.0.1/$TEST_PATIENCE_PORT; sleep 5"])]
+    fn starts_application() {}
+
+    #[test_patience::test(
+        cmd = "bash",
+        args = ["-c", "printf 'done\n\ndb' > /dev/tcp/127.0.0.1/$TEST_PATIENCE_PORT; sleep 5"],
+        timeout = "10s",
+    )]
+    fn passes_child_and_notification(child: &mut ChildGuard, notification: Notification) {
+        assert!(child.try_wait().expect("failed to check application").is_none());
+        assert_eq!(notification.payload(), b"db");
+    }
+
+    #[test_patience::test(cmd = "bash", args = ["-c", "printf done > /dev/tcp/127.0.0.1/$TEST_PATIENCE_PORT; sleep 5"])]
+    fn returns_result(child: &mut ChildGuard) -> std::io::Result<()> {
+        child.try_wait().map(drop)
+    }
+
+    #[test_patience::test(cmd = "bash", args = ["-c", "exit 1"], timeout = "1s")]
+    #[should_panic(expected = "application failed to start")]
+    fn fails_to_start() {}
+}
@@ -0,0 +1,67 @@
+#![cfg(feature = "serde")]
+
+extern crate test_patience;
+
+use std::time::Duration;
+use std::thread;
+use std::io::{self, Write};
+use std::net::TcpStream;
+
+use test_patience::Metadata;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wait_for_metadata() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        let mut metadata = Metadata {
+            service: Some("database".to_string()),
+            version: Some("1.2.3".to_string()),
+            pid: Some(42),
+            ..Metadata::default()
+        };
+        metadata.properties.insert("listen".to_string(), "127.0.0.1:5432".to_string());
+
+        let sent = metadata.clone();
+        thread::spawn(move || {
+            test_patience::Client::notify_with_metadata(port, &sent).expect("failed to notify");
+        });
+
+        let notification = server.wait(Duration::from_secs(5)).expect("failed to wait");
+
+        assert_eq!(notification.metadata(), Some(&metadata));
+    }
+
+    #[test]
+    fn wait_for_plain_notification() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            test_patience::Client::notify(port).expect("failed to notify");
+        });
+
+        let notification = server.wait(Duration::from_secs(5)).expect("failed to wait");
+
+        assert_eq!(notification.metadata(), None);
+    }
+
+    #[test]
+    fn invalid_metadata() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            let mut stream = TcpStream::connect(("127.0.0.1", port)).expect("failed to connect");
+            stream.write_all(b"done\nmetadata: {\n\n").expect("failed to write");
+        });
+
+        let result = server.wait(Duration::from_secs(5));
+
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,97 @@
+extern crate test_patience;
+
+use std::io;
+#[cfg(unix)]
+use std::process::Command;
+use std::time::Duration;
+
+use test_patience::{Orchestrator, Service};
+
+#[cfg(unix)]
+fn service(name: &str, script: &str) -> Service {
+    let mut command = Command::new("bash");
+    command.arg("-c").arg(script);
+    Service::new(name, command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn start_in_dependency_order() {
+        let dir = std::env::temp_dir().join(format!("test-patience-orchestrator-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("failed to create directory");
+        let marker = dir.join("db");
+        let _ = std::fs::remove_file(&marker);
+
+        let db = format!("sleep 0.2; touch {:?}; printf done > /dev/tcp/127.0.0.1/$TEST_PATIENCE_PORT; sleep 5", marker);
+        let api = format!("test -f {:?} && printf done > /dev/tcp/127.0.0.1/$TEST_PATIENCE_PORT; sleep 5", marker);
+        let services = Orchestrator::new()
+            .service(service("api", &api).after("db"))
+            .service(service("db", &db))
+            .start()
+            .expect("failed to start services");
+
+        let names: Vec<_> = services.iter().map(|(name, _, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["db", "api"]);
+        for (_, mut child, _) in services {
+            child.kill().expect("failed to kill application");
+            child.wait().expect("failed to reap application");
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn start_failed() {
+        let result = Orchestrator::new()
+            .service(service("db", "printf done > /dev/tcp/127.0.0.1/$TEST_PATIENCE_PORT; sleep 5"))
+            .service(service("api", "sleep 5").after("db").timeout(Duration::from_millis(300)))
+            .start();
+
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert!(error.to_string().starts_with("service api failed to start"));
+    }
+
+    #[test]
+    fn dependency_cycle() {
+        let result = Orchestrator::new()
+            .service(Service::new("a", std::process::Command::new("true")).after("b"))
+            .service(Service::new("b", std::process::Command::new("true")).after("a"))
+            .start();
+
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn unknown_dependency() {
+        let result = Orchestrator::new()
+            .service(Service::new("a", std::process::Command::new("true")).after("b"))
+            .start();
+
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn start_set() {
+        let services = Orchestrator::new()
+            .service(service("db", "printf done > /dev/tcp/127.0.0.1/$TEST_PATIENCE_PORT; sleep 5"))
+            .service(service("api", "printf 'done\n\napi' > /dev/tcp/127.0.0.1/$TEST_PATIENCE_PORT; sleep 5").after("db"))
+            .start_set()
+            .expect("failed to start services");
+
+        assert_eq!(services.names().collect::<Vec<_>>(), vec!["db", "api"]);
+        assert_eq!(services.notification("api").map(|notification| notification.payload()), Some(&b"api"[..]));
+        assert!(services.get("cache").is_none());
+        let proc_dirs: Vec<_> = ["db", "api"].iter()
+            .map(|name| std::path::Path::new("/proc").join(services.get(name).unwrap().id().to_string()))
+            .collect();
+        drop(services);
+
+        assert!(proc_dirs.iter().all(|dir| !dir.exists()), "services weren't killed and reaped");
+    }
+}
@@ -0,0 +1,31 @@
+extern crate test_patience;
+
+use std::env;
+use std::thread;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the only test of this file, as it sets the environment variables of the whole process, like the application would receive them
+    #[test]
+    fn notification_overhead() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server")
+            .measure_overhead(true);
+        for (name, value) in server.env() {
+            env::set_var(name, value);
+        }
+        assert_eq!(env::var("TEST_PATIENCE_TIMESTAMP").as_deref(), Ok("1"));
+
+        thread::spawn(|| {
+            thread::sleep(Duration::from_millis(100));
+            test_patience::Client::notify_from_env().expect("failed to notify");
+        });
+
+        let notification = server.wait(Duration::from_secs(5)).expect("failed to wait");
+        let overhead = notification.overhead().expect("notification carries no timestamp");
+
+        assert!(overhead < Duration::from_millis(50), "overhead: {:?}", overhead);
+    }
+}
@@ -0,0 +1,26 @@
+extern crate test_patience;
+
+use std::thread;
+use std::time::Duration;
+
+use test_patience::StartupFailure;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the panic hook is global, so this is the only test of this file
+    #[test]
+    fn panic_hook_reports_failure() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+        test_patience::Client::install_panic_hook(port);
+
+        thread::spawn(|| panic!("failed to connect to database"));
+
+        let error = server.wait(Duration::from_secs(5)).unwrap_err();
+        let failure = error.get_ref().and_then(|e| e.downcast_ref::<StartupFailure>()).expect("no startup failure");
+        assert!(failure.reason().starts_with("application panicked at tests/test_panic_hook.rs:"), "{}", failure.reason());
+        assert!(failure.reason().ends_with(": failed to connect to database"), "{}", failure.reason());
+    }
+}
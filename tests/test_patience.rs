@@ -1,8 +1,19 @@
 extern crate test_patience;
 
-use std::time::Duration;
+use std::time::{Instant, Duration};
 use std::thread;
-use std::io;
+use std::io::{self, Write};
+use std::net::{IpAddr, TcpStream, UdpSocket};
+use std::sync::mpsc;
+#[cfg(unix)]
+use std::process;
+
+/// Get an address of this host which isn't a loopback address, if it has one
+fn non_loopback_ip() -> Option<IpAddr> {
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("10.255.255.255:1").ok()?;
+    Some(socket.local_addr().ok()?.ip()).filter(|ip| !ip.is_loopback() && !ip.is_unspecified())
+}
 
 fn mock_client(port: u16, sleep: Duration) {
     thread::sleep(sleep);
@@ -16,7 +27,9 @@ mod tests {
     #[cfg(not(windows))]
     macro_rules! assert_close {
         ($left:expr, $right:expr) => ({
-            let diff = $left - $right;
+            let (left, right) = ($left, $right);
+            // the application may have been started right before the wait, so the wait can be a bit shorter as well
+            let diff = if left > right { left - right } else { right - left };
             if diff.as_secs() > 0 || diff.subsec_nanos() > 10_000_000 {
                 panic!("assertion failed: `(time difference smaller than 10 ms)`, (diff: `{:?}`)", diff)
             }
@@ -27,7 +40,9 @@ mod tests {
     #[cfg(windows)]
     macro_rules! assert_close {
         ($left:expr, $right:expr) => ({
-            let diff = $left - $right;
+            let (left, right) = ($left, $right);
+            // the application may have been started right before the wait, so the wait can be a bit shorter as well
+            let diff = if left > right { left - right } else { right - left };
             if diff.as_secs() > 0 || diff.subsec_nanos() > 100_000_000 {
                 panic!("assertion failed: `(time difference smaller than 100 ms)`, (diff: `{:?}`)", diff)
             }
@@ -43,7 +58,7 @@ mod tests {
             mock_client(port, Duration::from_secs(0));
         });
 
-        let wait_duration = server.wait(Duration::from_secs(5)).expect("failed to wait");
+        let wait_duration = server.wait(Duration::from_secs(5)).expect("failed to wait").elapsed();
 
         assert_close!(wait_duration, Duration::from_secs(0));
     }
@@ -57,7 +72,7 @@ mod tests {
             mock_client(port, Duration::from_secs(1));
         });
 
-        let wait_duration = server.wait(Duration::from_secs(5)).expect("failed to wait");
+        let wait_duration = server.wait(Duration::from_secs(5)).expect("failed to wait").elapsed();
 
         assert_close!(wait_duration, Duration::from_secs(1));
     }
@@ -71,7 +86,7 @@ mod tests {
             mock_client(port, Duration::from_secs(2));
         });
 
-        let wait_duration = server.wait(Duration::from_secs(5)).expect("failed to wait");
+        let wait_duration = server.wait(Duration::from_secs(5)).expect("failed to wait").elapsed();
 
         assert_close!(wait_duration, Duration::from_secs(2));
     }
@@ -87,7 +102,7 @@ mod tests {
 
         thread::sleep(Duration::from_secs(1));
 
-        let wait_duration = server.wait(Duration::from_secs(5)).expect("failed to wait");
+        let wait_duration = server.wait(Duration::from_secs(5)).expect("failed to wait").elapsed();
 
         assert_close!(wait_duration, Duration::from_secs(0));
     }
@@ -105,4 +120,1804 @@ mod tests {
         assert!(result.is_err());
         assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
     }
+
+    #[test]
+    fn wait_for_client_failure() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            test_patience::Client::notify_failure(port, "could not connect to database").expect("failed to notify");
+        });
+
+        let error = server.wait(Duration::from_secs(5)).unwrap_err();
+        let failure = error.get_ref()
+            .and_then(|e| e.downcast_ref::<test_patience::StartupFailure>())
+            .expect("no startup failure");
+        assert_eq!(failure.reason(), "could not connect to database");
+    }
+
+    #[test]
+    fn wait_for_client_progress() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server")
+            .reset_timeout_on_progress(true);
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            for step in 1..3 {
+                thread::sleep(Duration::from_millis(300));
+                test_patience::Client::progress(port, &format!("step {}", step)).expect("failed to send progress");
+            }
+            mock_client(port, Duration::from_millis(300));
+        });
+
+        let wait_duration = server.wait(Duration::from_millis(500)).expect("failed to wait").elapsed();
+
+        assert!(wait_duration >= Duration::from_millis(900));
+    }
+
+    #[test]
+    fn wait_for_session() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server")
+            .reset_timeout_on_progress(true);
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            let mut session = test_patience::Client::connect(port).expect("failed to connect");
+            for step in 1..3 {
+                thread::sleep(Duration::from_millis(300));
+                session.progress(&format!("step {}", step)).expect("failed to send progress");
+            }
+            thread::sleep(Duration::from_millis(300));
+            session.notify().expect("failed to notify");
+        });
+
+        let wait_duration = server.wait(Duration::from_millis(500)).expect("failed to wait").elapsed();
+
+        assert!(wait_duration >= Duration::from_millis(900));
+    }
+
+    #[test]
+    fn wait_for_fragmented_frames() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            let mut stream = TcpStream::connect(("127.0.0.1", port)).expect("failed to connect");
+            for chunk in [&b"\0\0"[..], b"\0\x10progress\n\nste", b"p 1\0\0\0", b"\x04do", b"ne"] {
+                stream.write_all(chunk).expect("failed to write");
+                thread::sleep(Duration::from_millis(50));
+            }
+            // the notification has to be handled without the connection being closed
+            thread::sleep(Duration::from_secs(5));
+        });
+
+        let wait_duration = server.wait(Duration::from_secs(2)).expect("failed to wait").elapsed();
+
+        assert!(wait_duration >= Duration::from_millis(200));
+        assert!(wait_duration < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn wait_for_fragmented_message() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            let mut stream = TcpStream::connect(("127.0.0.1", port)).expect("failed to connect");
+            stream.set_nodelay(true).expect("failed to disable nagle");
+            for chunk in [&b"do"[..], b"ne\nna", b"me: ap", b"i\n", b"\n"] {
+                stream.write_all(chunk).expect("failed to write");
+                thread::sleep(Duration::from_millis(50));
+            }
+        });
+
+        let notification = server.wait(Duration::from_secs(2)).expect("failed to wait");
+        assert_eq!(notification.name(), Some("api"));
+    }
+
+    #[test]
+    fn wait_for_fragmented_heartbeat_preamble() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server")
+            .heartbeat_timeout(Duration::from_secs(1));
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            let mut stream = TcpStream::connect(("127.0.0.1", port)).expect("failed to connect");
+            stream.set_nodelay(true).expect("failed to disable nagle");
+            for byte in b"heartbeat\ntoken: secret\n\nping\ndone\nname: api\n\n".chunks(1) {
+                stream.write_all(byte).expect("failed to write");
+                thread::sleep(Duration::from_millis(5));
+            }
+        });
+
+        let notification = server.wait(Duration::from_secs(2)).expect("failed to wait");
+        assert_eq!(notification.name(), Some("api"));
+    }
+
+    #[test]
+    fn wait_for_incomplete_frame() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            let mut stream = TcpStream::connect(("127.0.0.1", port)).expect("failed to connect");
+            stream.write_all(b"\0\0\0\x04do").expect("failed to write");
+        });
+
+        let result = server.wait(Duration::from_secs(5));
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::Other);
+    }
+
+    #[test]
+    fn wait_for_client_progress_timeout() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            test_patience::Client::progress(port, "running migrations").expect("failed to send progress");
+            mock_client(port, Duration::from_secs(1));
+        });
+
+        let error = server.wait(Duration::from_millis(500)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert!(error.to_string().contains("running migrations"));
+    }
+
+    #[test]
+    fn wait_for_heartbeat_client() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server")
+            .heartbeat_timeout(Duration::from_millis(200));
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            let heartbeat = test_patience::Client::heartbeat(port, Duration::from_millis(50)).expect("failed to open heartbeat connection");
+            thread::sleep(Duration::from_secs(1));
+            heartbeat.notify().expect("failed to notify");
+        });
+
+        let wait_duration = server.wait(Duration::from_secs(5)).expect("failed to wait").elapsed();
+
+        assert_close!(wait_duration, Duration::from_secs(1));
+    }
+
+    #[test]
+    fn wait_for_heartbeat_client_died() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            let heartbeat = test_patience::Client::heartbeat(port, Duration::from_millis(50)).expect("failed to open heartbeat connection");
+            thread::sleep(Duration::from_millis(200));
+            drop(heartbeat);
+        });
+
+        let start = Instant::now();
+        let result = server.wait(Duration::from_secs(5));
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::ConnectionAborted);
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(matches!(test_patience::Error::from(error), test_patience::Error::HeartbeatClosed));
+    }
+
+    #[test]
+    fn wait_for_heartbeat_client_hung() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server")
+            .heartbeat_timeout(Duration::from_millis(200));
+        let port = server.port().expect("failed to get test-patience server port");
+
+        let (hung, hang) = mpsc::channel::<()>();
+        thread::spawn(move || {
+            let mut stream = TcpStream::connect(("127.0.0.1", port)).expect("failed to connect");
+            stream.write_all(b"heartbeat\nping\n").expect("failed to write");
+            let _ = hang.recv();
+        });
+
+        let start = Instant::now();
+        let result = server.wait(Duration::from_secs(5));
+        drop(hung);
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::ConnectionAborted);
+        assert!(error.to_string().contains("heartbeats"));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(matches!(test_patience::Error::from(error), test_patience::Error::HeartbeatStopped));
+    }
+
+    #[test]
+    fn wait_for_client_with_token() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server")
+            .require_token(true).expect("failed to require token");
+        let port = server.port().expect("failed to get test-patience server port");
+        let token = server.token().to_string();
+
+        thread::spawn(move || {
+            let mut stream = TcpStream::connect(("127.0.0.1", port)).expect("failed to connect");
+            stream.write_all(b"garbage").expect("failed to write");
+            drop(stream);
+            mock_client(port, Duration::from_millis(100));
+            thread::sleep(Duration::from_millis(100));
+            test_patience::Client::notify_with_token(port, &token).expect("failed to notify");
+        });
+
+        let wait_duration = server.wait(Duration::from_secs(5)).expect("failed to wait").elapsed();
+
+        assert!(wait_duration >= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn wait_for_client_with_wrong_token() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server")
+            .require_token(true).expect("failed to require token");
+        let port = server.port().expect("failed to get test-patience server port");
+        assert_eq!(server.token().len(), 32);
+
+        thread::spawn(move || {
+            test_patience::Client::notify_with_token(port, "wrong").expect("failed to notify");
+        });
+
+        let result = server.wait(Duration::from_millis(500));
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn require_token_without_message() {
+        let path = std::env::temp_dir().join(format!("test-patience-token-{}", std::process::id()));
+        let server = test_patience::Server::watch_file(&path).expect("failed to create test-patience server");
+
+        let result = server.require_token(true);
+        assert_eq!(result.err().map(|e| e.kind()), Some(io::ErrorKind::InvalidInput));
+    }
+
+    #[test]
+    fn wait_for_client_idle_connection() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        let _idle = TcpStream::connect(("127.0.0.1", port)).expect("failed to connect");
+        thread::spawn(move || {
+            mock_client(port, Duration::from_millis(200));
+        });
+
+        let wait_duration = server.wait(Duration::from_secs(5)).expect("failed to wait").elapsed();
+
+        assert!(wait_duration < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn wait_for_client_silent_connections() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        let _silent = TcpStream::connect(("127.0.0.1", port)).expect("failed to connect");
+        let mut stalled = TcpStream::connect(("127.0.0.1", port)).expect("failed to connect");
+        stalled.write_all(b"do").expect("failed to write");
+        thread::spawn(move || {
+            mock_client(port, Duration::from_millis(200));
+        });
+
+        let wait_duration = server.wait(Duration::from_secs(5)).expect("failed to wait").elapsed();
+
+        assert_close!(wait_duration, Duration::from_millis(200));
+    }
+
+    #[test]
+    fn wait_for_client_idle_connection_timeout() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        let _idle = TcpStream::connect(("127.0.0.1", port)).expect("failed to connect");
+
+        let start = Instant::now();
+        let result = server.wait(Duration::from_millis(300));
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn wait_for_udp_client() {
+        let server = test_patience::Server::new_udp().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            thread::sleep(Duration::from_secs(1));
+            test_patience::Client::notify_udp(port).expect("failed to notify");
+        });
+
+        let wait_duration = server.wait(Duration::from_secs(5)).expect("failed to wait").elapsed();
+
+        assert_close!(wait_duration, Duration::from_secs(1));
+    }
+
+    #[test]
+    fn wait_for_udp_heartbeat_datagram() {
+        let server = test_patience::Server::new_udp().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        let socket = std::net::UdpSocket::bind(("127.0.0.1", 0)).expect("failed to bind");
+        socket.send_to(b"heartbeat", ("127.0.0.1", port)).expect("failed to send");
+
+        let result = server.wait(Duration::from_secs(1));
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::Other);
+    }
+
+    #[test]
+    fn wait_for_udp_client_timeout() {
+        let server = test_patience::Server::new_udp().expect("failed to create test-patience server");
+
+        let result = server.wait(Duration::from_millis(500));
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn spawn() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server").require_token(true).unwrap();
+
+        let mut command = process::Command::new("bash");
+        command.arg("-c").arg("printf 'done\ntoken: %s\n\n' $TEST_PATIENCE_TOKEN > /dev/tcp/127.0.0.1/$TEST_PATIENCE_PORT");
+        let mut child = server.spawn(&mut command).expect("failed to spawn application");
+
+        server.wait(Duration::from_secs(5)).expect("failed to wait");
+        child.wait().expect("failed to reap application");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_notify_via() {
+        use test_patience::PatienceCommandExt;
+
+        let server = test_patience::Server::new().expect("failed to create test-patience server").require_token(true).unwrap();
+
+        let mut child = process::Command::new("bash")
+            .arg("-c")
+            .arg("printf 'done\ntoken: %s\n\n' $TEST_PATIENCE_TOKEN > /dev/tcp/127.0.0.1/$TEST_PATIENCE_PORT")
+            .notify_via(&server).expect("failed to configure application")
+            .spawn()
+            .expect("failed to spawn application");
+
+        server.wait(Duration::from_secs(5)).expect("failed to wait");
+        child.wait().expect("failed to reap application");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn spawn_and_wait() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+
+        let mut command = process::Command::new("bash");
+        command.arg("-c").arg("sleep 0.2; printf done > /dev/tcp/127.0.0.1/$TEST_PATIENCE_PORT; sleep 5");
+        let (mut child, notification) = server.spawn_and_wait(&mut command, Duration::from_secs(5)).expect("failed to wait");
+
+        assert!(notification.elapsed() >= Duration::from_millis(200));
+        child.kill().expect("failed to kill application");
+        child.wait().expect("failed to reap application");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn spawn_and_wait_timeout() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let marker = std::env::temp_dir().join(format!("test-patience-timed-out-{}", std::process::id()));
+
+        let mut command = process::Command::new("sh");
+        command.arg("-c").arg("sleep 0.5; touch \"$MARKER\"").env("MARKER", &marker);
+        let result = server.spawn_and_wait(&mut command, Duration::from_millis(200));
+
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+        thread::sleep(Duration::from_millis(500));
+        assert!(!marker.exists(), "application wasn't killed");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn spawn_and_wait_exited() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+
+        let mut command = process::Command::new("bash");
+        command.arg("-c").arg("exit 3");
+        let start = Instant::now();
+        let result = server.spawn_and_wait(&mut command, Duration::from_secs(5));
+
+        let e = result.unwrap_err();
+        let exit = e.get_ref().and_then(|e| e.downcast_ref::<test_patience::EarlyExit>()).expect("expected early exit");
+        assert_eq!(exit.code(), Some(3));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn spawn_and_wait_killed_with_output() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server").capture_output(5);
+
+        let mut command = process::Command::new("bash");
+        command.arg("-c").arg("echo crashing; sleep 0.1; kill -9 $$");
+        let result = server.spawn_and_wait(&mut command, Duration::from_secs(5));
+
+        let e = result.unwrap_err();
+        let exit = e.get_ref().and_then(|e| e.downcast_ref::<test_patience::EarlyExit>()).expect("expected early exit");
+        assert_eq!(exit.code(), None);
+        assert_eq!(exit.signal(), Some(9));
+        assert_eq!(exit.output(), ["crashing"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn spawn_and_wait_restart() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server")
+            .restart_on_failure(3, Duration::from_millis(50));
+        let marker = std::env::temp_dir().join(format!("test-patience-restart-{}", std::process::id()));
+        let _ = std::fs::remove_file(&marker);
+
+        let mut command = process::Command::new("bash");
+        command.arg("-c")
+            .arg("if [ -f \"$MARKER\" ]; then printf done > /dev/tcp/127.0.0.1/$TEST_PATIENCE_PORT; else touch \"$MARKER\"; fi")
+            .env("MARKER", &marker);
+        let (mut child, _) = server.spawn_and_wait(&mut command, Duration::from_millis(300)).expect("failed to wait");
+
+        child.wait().expect("failed to reap application");
+        let _ = std::fs::remove_file(&marker);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn spawn_and_wait_restart_exhausted() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server")
+            .restart_on_failure(2, Duration::from_millis(50));
+
+        let mut command = process::Command::new("sleep");
+        command.arg("5");
+        let start = Instant::now();
+        let result = server.spawn_and_wait(&mut command, Duration::from_millis(200));
+
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+        assert!(start.elapsed() >= Duration::from_millis(450));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn spawn_and_wait_timeout_with_output() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server").capture_output(2);
+
+        let mut command = process::Command::new("sh");
+        command.arg("-c").arg("echo starting; sleep 0.1; echo 'listening on port 80' >&2; echo 'permission denied' >&2; sleep 5");
+        let result = server.spawn_and_wait(&mut command, Duration::from_millis(300));
+
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert!(error.to_string().ends_with("last output of the application:\nlistening on port 80\npermission denied"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn wait_for_stdout_marker() {
+        let mut child = process::Command::new("sh")
+            .arg("-c")
+            .arg("echo starting; sleep 1; echo READY; echo more output; sleep 5")
+            .stdout(process::Stdio::piped())
+            .spawn()
+            .expect("failed to spawn application");
+        let server = test_patience::Server::watch_stdout(&mut child, "READY").expect("failed to create test-patience server");
+        assert!(server.port().is_err());
+
+        let wait_duration = server.wait(Duration::from_secs(5)).expect("failed to wait").elapsed();
+
+        assert_close!(wait_duration, Duration::from_secs(1));
+        child.kill().expect("failed to kill application");
+        child.wait().expect("failed to reap application");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn wait_for_stdout_marker_closed() {
+        let mut child = process::Command::new("sh")
+            .arg("-c")
+            .arg("echo starting")
+            .stdout(process::Stdio::piped())
+            .spawn()
+            .expect("failed to spawn application");
+        let server = test_patience::Server::watch_stdout(&mut child, "READY").expect("failed to create test-patience server");
+
+        let result = server.wait(Duration::from_secs(5));
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        child.wait().expect("failed to reap application");
+    }
+
+    #[test]
+    fn wait_for_sentinel_file() {
+        let path = std::env::temp_dir().join(format!("test-patience-{}.ready", std::process::id()));
+        std::fs::write(&path, b"stale").expect("failed to create stale sentinel file");
+        let server = test_patience::Server::watch_file(&path).expect("failed to create test-patience server");
+        assert!(!path.exists());
+
+        let client_path = path.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_secs(1));
+            test_patience::Client::notify_file(client_path).expect("failed to notify");
+        });
+
+        let wait_duration = server.wait(Duration::from_secs(5)).expect("failed to wait").elapsed();
+
+        assert_close!(wait_duration, Duration::from_secs(1));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn wait_for_n_clients() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        for delay in &[300, 100, 200] {
+            let delay = Duration::from_millis(*delay);
+            thread::spawn(move || mock_client(port, delay));
+        }
+
+        let notifications = server.wait_for_n(3, Duration::from_secs(5)).expect("failed to wait");
+
+        assert_eq!(notifications.len(), 3);
+        assert!(notifications[0].elapsed() >= Duration::from_millis(100));
+        assert!(notifications[2].elapsed() >= Duration::from_millis(300));
+        assert!(notifications[2].elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn wait_for_n_clients_expecting() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            test_patience::Client::notify_expecting(port, 2).expect("failed to notify");
+            for _ in 0..2 {
+                mock_client(port, Duration::from_millis(100));
+            }
+        });
+
+        let notifications = server.wait_for_n(1, Duration::from_secs(5)).expect("failed to wait");
+
+        assert_eq!(notifications.len(), 3);
+        assert_eq!(notifications[0].expecting(), 2);
+        assert!(notifications[2].elapsed() >= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn wait_for_n_clients_timeout() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || mock_client(port, Duration::from_millis(100)));
+
+        let result = server.wait_for_n(2, Duration::from_millis(500));
+
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert!(error.to_string().contains("1 of 2"));
+        match test_patience::Error::from(error) {
+            test_patience::Error::Timeout(timeout) => assert_eq!((timeout.received(), timeout.missing()), (1, 1)),
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn wait_all_named() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server")
+            .expect(["db", "cache"]);
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            test_patience::Client::notify_named(port, "cache").expect("failed to notify");
+            test_patience::Client::notify_named(port, "unrelated").expect("failed to notify");
+            test_patience::Client::notify_named(port, "cache").expect("failed to notify");
+            thread::sleep(Duration::from_millis(200));
+            test_patience::Client::notify_named(port, "db").expect("failed to notify");
+        });
+
+        let notifications = server.wait_all(Duration::from_secs(5)).expect("failed to wait");
+
+        let names: Vec<_> = notifications.iter().map(|notification| notification.name()).collect();
+        assert_eq!(names, vec![Some("cache"), Some("db")]);
+        assert!(notifications[1].elapsed() >= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn wait_all_named_timeout() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server")
+            .expect(["db", "cache", "api"]);
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            test_patience::Client::notify_named(port, "cache").expect("failed to notify");
+        });
+
+        let result = server.wait_all(Duration::from_millis(500));
+
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert!(error.to_string().ends_with("of db, api"));
+        match test_patience::Error::from(error) {
+            test_patience::Error::Timeout(timeout) => {
+                assert_eq!(timeout.received(), 1);
+                assert_eq!(timeout.missing_services(), ["db".to_string(), "api".to_string()]);
+            }
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn wait_all_within() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server")
+            .expect(["db"])
+            .expect_within("stub", Duration::from_millis(200));
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            test_patience::Client::notify_named(port, "db").expect("failed to notify");
+            thread::sleep(Duration::from_millis(500));
+            let _ = test_patience::Client::notify_named(port, "stub");
+        });
+
+        let start = Instant::now();
+        let result = server.wait_all(Duration::from_secs(5));
+
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(error.to_string(), "did not receive startup notification of stub within 200ms");
+        match test_patience::Error::from(error) {
+            test_patience::Error::Timeout(timeout) => assert_eq!(timeout.missing_services(), ["stub".to_string()]),
+            e => panic!("unexpected error: {}", e),
+        }
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn registry() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server")
+            .expect(["db", "cache"]);
+        let port = server.port().expect("failed to get test-patience server port");
+        let registry = server.registry();
+
+        let waiter = thread::spawn(move || server.wait_all(Duration::from_secs(5)));
+        test_patience::Client::notify_named(port, "db").expect("failed to notify");
+        thread::sleep(Duration::from_millis(200));
+
+        assert!(registry.is_ready("db"));
+        assert!(!registry.is_ready("cache"));
+
+        test_patience::Client::notify(port).expect("failed to notify");
+        test_patience::Client::notify_named(port, "cache").expect("failed to notify");
+        waiter.join().unwrap().expect("failed to wait");
+
+        assert_eq!(registry.ready_services(), vec!["db".to_string(), "cache".to_string()]);
+        assert_eq!(registry.notifications().len(), 3);
+    }
+
+    #[test]
+    fn wait_group() {
+        let mut server = test_patience::Server::new().expect("failed to create test-patience server")
+            .group("storage", ["postgres", "minio"])
+            .group("frontend", ["api", "worker"]);
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            for name in &["api", "postgres", "minio"] {
+                test_patience::Client::notify_named(port, name).expect("failed to notify");
+            }
+            thread::sleep(Duration::from_millis(200));
+            test_patience::Client::notify_named(port, "worker").expect("failed to notify");
+        });
+
+        let storage = server.wait_group("storage", Duration::from_secs(5)).expect("failed to wait for storage");
+        let frontend = server.wait_group("frontend", Duration::from_secs(5)).expect("failed to wait for frontend");
+
+        assert_eq!(storage.len(), 2);
+        let names: Vec<_> = frontend.iter().map(|notification| notification.name()).collect();
+        assert_eq!(names, vec![Some("api"), Some("worker")]);
+    }
+
+    #[test]
+    fn wait_group_timeout() {
+        let mut server = test_patience::Server::new().expect("failed to create test-patience server")
+            .group("storage", ["postgres", "minio"]);
+        let port = server.port().expect("failed to get test-patience server port");
+
+        test_patience::Client::notify_named(port, "postgres").expect("failed to notify");
+
+        let error = server.wait_group("storage", Duration::from_millis(300)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(error.to_string(), "did not receive startup notification of minio in group storage");
+        match test_patience::Error::from(error) {
+            test_patience::Error::Timeout(timeout) => {
+                assert_eq!(timeout.group(), Some("storage"));
+                assert_eq!((timeout.received(), timeout.missing()), (1, 1));
+            }
+            e => panic!("unexpected error: {}", e),
+        }
+
+        let error = server.wait_group("frontend", Duration::from_millis(300)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn wait_any_named() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server")
+            .expect(["primary", "secondary"]);
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            test_patience::Client::notify_named(port, "unrelated").expect("failed to notify");
+            test_patience::Client::notify_failure(port, "address in use").expect("failed to notify");
+            thread::sleep(Duration::from_millis(200));
+            test_patience::Client::notify_named(port, "secondary").expect("failed to notify");
+        });
+
+        let notification = server.wait_any(Duration::from_secs(5)).expect("failed to wait");
+
+        assert_eq!(notification.name(), Some("secondary"));
+        assert!(notification.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn wait_any_failed() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            test_patience::Client::notify_failure(port, "address in use").expect("failed to notify");
+        });
+
+        let result = server.wait_any(Duration::from_millis(300));
+
+        let error = result.unwrap_err();
+        let failure = error.get_ref()
+            .and_then(|e| e.downcast_ref::<test_patience::StartupFailure>())
+            .expect("no startup failure");
+        assert_eq!(failure.reason(), "address in use");
+    }
+
+    #[test]
+    fn barrier() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        let start = Instant::now();
+        let participants: Vec<_> = (0..3u32)
+            .map(|i| thread::spawn(move || {
+                thread::sleep(Duration::from_millis(100) * i);
+                test_patience::Client::wait_for_release(port).expect("failed to wait for release");
+                start.elapsed()
+            }))
+            .collect();
+
+        server.barrier(3, Duration::from_secs(5)).expect("failed to release barrier");
+
+        let released: Vec<_> = participants.into_iter().map(|participant| participant.join().unwrap()).collect();
+        for elapsed in &released {
+            assert!(*elapsed >= Duration::from_millis(200));
+        }
+        let first = released.iter().min().unwrap();
+        let last = released.iter().max().unwrap();
+        assert!(*last - *first < Duration::from_millis(50));
+    }
+
+    #[test]
+    fn barrier_timeout() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        let participant = thread::spawn(move || test_patience::Client::wait_for_release(port));
+
+        let result = server.barrier(2, Duration::from_millis(300));
+
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert!(error.to_string().starts_with("1 of 2"));
+        match test_patience::Error::from(error) {
+            test_patience::Error::Timeout(timeout) => assert_eq!((timeout.received(), timeout.missing()), (1, 1)),
+            e => panic!("unexpected error: {}", e),
+        }
+        assert_eq!(participant.join().unwrap().unwrap_err().kind(), io::ErrorKind::ConnectionAborted);
+    }
+
+    #[test]
+    fn iter() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            for _ in 0..2 {
+                mock_client(port, Duration::from_millis(200));
+            }
+        });
+
+        let results: Vec<_> = server.iter(Duration::from_millis(500)).collect();
+
+        assert_eq!(results.len(), 3);
+        assert!(results[0].as_ref().expect("failed to wait").elapsed() >= Duration::from_millis(200));
+        assert!(results[1].as_ref().expect("failed to wait").elapsed() >= Duration::from_millis(400));
+        assert_eq!(results[2].as_ref().unwrap_err().kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn wait_for_named_client() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            test_patience::Client::notify_named(port, "db-proxy").expect("failed to notify");
+        });
+
+        let notification = server.wait(Duration::from_secs(5)).expect("failed to wait");
+
+        assert_eq!(notification.name(), Some("db-proxy"));
+    }
+
+    #[test]
+    fn wait_for_payload() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            test_patience::Client::notify_with_payload(port, b"127.0.0.1:8080\n\0").expect("failed to notify");
+        });
+
+        let notification = server.wait(Duration::from_secs(5)).expect("failed to wait");
+
+        assert_eq!(notification.payload(), b"127.0.0.1:8080\n\0");
+    }
+
+    #[test]
+    fn wait_without_payload() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            test_patience::Client::notify(port).expect("failed to notify");
+        });
+
+        let notification = server.wait(Duration::from_secs(5)).expect("failed to wait");
+
+        assert!(notification.payload().is_empty());
+    }
+
+    #[test]
+    fn notify_named_with_line_break() {
+        let result = test_patience::Client::notify_named(1, "db\nproxy");
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn wait_for_sentinel_file_with_payload() {
+        let path = std::env::temp_dir().join(format!("test-patience-{}.payload", std::process::id()));
+        let server = test_patience::Server::watch_file(&path).expect("failed to create test-patience server");
+
+        let client_path = path.clone();
+        thread::spawn(move || {
+            test_patience::Client::notify_file_with_payload(client_path, b"127.0.0.1:8080").expect("failed to notify");
+        });
+
+        let notification = server.wait(Duration::from_secs(5)).expect("failed to wait");
+
+        assert_eq!(notification.payload(), b"127.0.0.1:8080");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn wait_for_signal() {
+        let server = test_patience::Server::new_signal().expect("failed to create test-patience server");
+        let pid = server.pid();
+        assert!(test_patience::Server::new_signal().is_err());
+
+        thread::spawn(move || {
+            thread::sleep(Duration::from_secs(1));
+            test_patience::Client::notify_signal(pid).expect("failed to notify");
+        });
+
+        let wait_duration = server.wait(Duration::from_secs(5)).expect("failed to wait").elapsed();
+
+        assert_close!(wait_duration, Duration::from_secs(1));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn wait_for_pipe() {
+        let server = test_patience::Server::new_pipe().expect("failed to create test-patience server");
+        let mut command = process::Command::new("sh");
+        command.arg("-c").arg("sleep 1; printf done >&$TEST_PATIENCE_PIPE");
+        server.inherit_pipe(&mut command).expect("failed to pass pipe");
+        let mut child = command.spawn().expect("failed to spawn application");
+
+        let wait_duration = server.wait(Duration::from_secs(5)).expect("failed to wait").elapsed();
+
+        assert_close!(wait_duration, Duration::from_secs(1));
+        child.wait().expect("failed to reap application");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn wait_for_pipe_closed() {
+        let server = test_patience::Server::new_pipe().expect("failed to create test-patience server");
+        let mut command = process::Command::new("sh");
+        command.arg("-c").arg("exit 1");
+        server.inherit_pipe(&mut command).expect("failed to pass pipe");
+        let mut child = command.spawn().expect("failed to spawn application");
+
+        let result = server.wait(Duration::from_secs(5));
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        child.wait().expect("failed to reap application");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn wait_for_vsock_timeout() {
+        let server = match test_patience::Server::new_vsock() {
+            Ok(server) => server,
+            Err(_) => return, // vsock is not available on this machine
+        };
+        assert!(server.vsock_port().is_ok());
+        assert!(server.port().is_err());
+
+        let result = server.wait(Duration::from_millis(500));
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn wait_for_unix_client() {
+        let path = std::env::temp_dir().join(format!("test-patience-{}.sock", std::process::id()));
+        let server = test_patience::Server::new_unix(&path).expect("failed to create test-patience server");
+        assert!(server.port().is_err());
+
+        let client_path = path.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_secs(1));
+            test_patience::Client::notify_unix(client_path).expect("failed to notify");
+        });
+
+        let wait_duration = server.wait(Duration::from_secs(5)).expect("failed to wait").elapsed();
+
+        assert_close!(wait_duration, Duration::from_secs(1));
+        assert!(!path.exists());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn wait_for_abstract_client() {
+        let name = format!("test-patience-{}", std::process::id());
+        let server = test_patience::Server::new_abstract(&name).expect("failed to create test-patience server");
+
+        thread::spawn(move || {
+            thread::sleep(Duration::from_secs(1));
+            test_patience::Client::notify_abstract(name).expect("failed to notify");
+        });
+
+        let wait_duration = server.wait(Duration::from_secs(5)).expect("failed to wait").elapsed();
+
+        assert_close!(wait_duration, Duration::from_secs(1));
+    }
+
+    #[test]
+    fn builder_with_token() {
+        let server = test_patience::Server::builder()
+            .token("secret")
+            .require_token(true)
+            .poll_interval(Duration::from_millis(20))
+            .build()
+            .expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+        assert_eq!(server.token(), "secret");
+
+        thread::spawn(move || {
+            test_patience::Client::notify_with_token(port, "wrong").expect("failed to notify");
+            mock_client(port, Duration::from_millis(100));
+            thread::sleep(Duration::from_millis(100));
+            test_patience::Client::notify_with_token(port, "secret").expect("failed to notify");
+        });
+
+        let wait_duration = server.wait(Duration::from_secs(5)).expect("failed to wait").elapsed();
+
+        assert!(wait_duration >= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn builder_bind_addr() {
+        let server = test_patience::Server::builder()
+            .bind_addr("0.0.0.0:0".parse().unwrap())
+            .build()
+            .expect("failed to create test-patience server");
+        let addr = server.local_addr().expect("failed to get test-patience server address");
+        assert!(addr.ip().is_unspecified());
+        let port = server.port().expect("failed to get test-patience server port");
+        assert_eq!(addr.port(), port);
+
+        thread::spawn(move || mock_client(port, Duration::from_millis(100)));
+
+        server.wait(Duration::from_secs(5)).expect("failed to wait");
+    }
+
+    #[test]
+    fn builder_invalid_token() {
+        let result = test_patience::Server::builder().token("two words").build();
+        assert_eq!(result.err().map(|e| e.kind()), Some(io::ErrorKind::InvalidInput));
+    }
+
+    #[test]
+    fn bind_fixed_port() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").and_then(|listener| listener.local_addr()).expect("failed to find free port").port();
+        let server = test_patience::Server::bind(port).expect("failed to create test-patience server");
+        assert_eq!(server.port().expect("failed to get test-patience server port"), port);
+
+        let result = test_patience::Server::bind(port);
+        let error = result.err().expect("bound port twice");
+        assert_eq!(error.kind(), io::ErrorKind::AddrInUse);
+        assert_eq!(error.to_string(), format!("port {} is already in use", port));
+
+        thread::spawn(move || mock_client(port, Duration::from_millis(100)));
+
+        server.wait(Duration::from_secs(5)).expect("failed to wait");
+    }
+
+    #[test]
+    fn bind_in_range() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").and_then(|listener| listener.local_addr()).expect("failed to find free port").port();
+        let server = test_patience::Server::bind_in_range(port..=port).expect("failed to create test-patience server");
+        assert_eq!(server.port().expect("failed to get test-patience server port"), port);
+
+        let result = test_patience::Server::bind_in_range(port..port + 1);
+        let error = result.err().expect("bound port twice");
+        assert_eq!(error.kind(), io::ErrorKind::AddrInUse);
+        assert_eq!(error.to_string(), format!("all ports from {} to {} are already in use (busy: {})", port, port, port));
+
+        thread::spawn(move || mock_client(port, Duration::from_millis(100)));
+
+        server.wait(Duration::from_secs(5)).expect("failed to wait");
+    }
+
+    #[test]
+    fn bind_in_empty_range() {
+        let result = test_patience::Server::bind_in_range(40000..40000);
+        assert_eq!(result.err().map(|e| e.kind()), Some(io::ErrorKind::InvalidInput));
+    }
+
+    #[test]
+    fn wait_for_ipv6_client() {
+        let server = match test_patience::Server::builder().bind_addr("[::1]:0".parse().unwrap()).build() {
+            Ok(server) => server,
+            // IPv6 is disabled on this host
+            Err(ref e) if e.kind() == io::ErrorKind::AddrNotAvailable => return,
+            Err(e) => panic!("failed to create test-patience server: {}", e),
+        };
+        let addr = server.local_addr().expect("failed to get test-patience server address");
+        assert!(addr.is_ipv6());
+
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            test_patience::Client::notify_addr(addr).expect("failed to notify");
+        });
+
+        server.wait(Duration::from_secs(5)).expect("failed to wait");
+    }
+
+    #[test]
+    fn wait_ref_several_times() {
+        let mut server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            mock_client(port, Duration::from_millis(100));
+            mock_client(port, Duration::from_millis(300));
+        });
+
+        let first = server.wait_ref(Duration::from_secs(5)).expect("failed to wait").elapsed();
+        let second = server.wait_ref(Duration::from_secs(5)).expect("failed to wait").elapsed();
+        assert!(first >= Duration::from_millis(100));
+        assert!(second >= Duration::from_millis(200));
+
+        let result = server.wait_ref(Duration::from_millis(100));
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn wait_deadline() {
+        let start = Instant::now();
+        let deadline = start + Duration::from_millis(500);
+        let first = test_patience::Server::new().expect("failed to create test-patience server");
+        let second = test_patience::Server::new().expect("failed to create test-patience server");
+        let first_port = first.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || mock_client(first_port, Duration::from_millis(200)));
+
+        first.wait_deadline(deadline).expect("failed to wait");
+        let result = second.wait_deadline(deadline);
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+        assert!(Instant::now() >= deadline);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn wait_forever() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || mock_client(port, Duration::from_millis(100)));
+
+        let wait_duration = server.wait_forever().expect("failed to wait").elapsed();
+
+        assert!(wait_duration >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn wait_with_max_timeout() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server")
+            .reset_timeout_on_progress(true);
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            test_patience::Client::progress(port, "warming up").expect("failed to send progress");
+            mock_client(port, Duration::from_millis(100));
+        });
+
+        server.wait(Duration::MAX).expect("failed to wait");
+    }
+
+    #[test]
+    fn try_wait() {
+        let mut server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+        assert_eq!(server.try_wait().expect("failed to check for notification"), None);
+
+        thread::spawn(move || {
+            test_patience::Client::progress(port, "warming up").expect("failed to send progress");
+            mock_client(port, Duration::from_millis(100));
+        });
+
+        let start = Instant::now();
+        let notification = loop {
+            if let Some(notification) = server.try_wait().expect("failed to check for notification") {
+                break notification;
+            }
+            assert!(start.elapsed() < Duration::from_secs(5), "no notification received");
+            thread::sleep(Duration::from_millis(10));
+        };
+        assert!(notification.elapsed() >= Duration::from_millis(100));
+        assert_eq!(server.try_wait().expect("failed to check for notification"), None);
+    }
+
+    #[test]
+    fn wait_with_callback() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || mock_client(port, Duration::from_millis(2500)));
+
+        let mut calls = Vec::new();
+        server.wait_with(Duration::from_secs(5), |elapsed| calls.push(elapsed)).expect("failed to wait");
+
+        assert_eq!(calls.len(), 2);
+        assert!(calls[0] >= Duration::from_secs(1) && calls[1] >= Duration::from_secs(2));
+    }
+
+    #[test]
+    fn wait_with_callback_timeout() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+
+        let mut calls = 0;
+        let result = server.wait_with(Duration::from_millis(1500), |_| calls += 1);
+
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn notify_from_env_var() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+        let var = format!("TEST_PATIENCE_NOTIFY_FROM_ENV_{}", std::process::id());
+        std::env::set_var(&var, format!("{}", port));
+
+        thread::spawn(move || test_patience::Client::notify_from_env_var(&var).expect("failed to notify"));
+
+        server.wait(Duration::from_secs(5)).expect("failed to wait");
+    }
+
+    #[test]
+    fn notify_from_unset_env_var() {
+        let result = test_patience::Client::notify_from_env_var("TEST_PATIENCE_NOTIFY_FROM_UNSET_ENV");
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn notify_from_invalid_env_var() {
+        let var = format!("TEST_PATIENCE_NOTIFY_FROM_INVALID_ENV_{}", std::process::id());
+        std::env::set_var(&var, "http");
+
+        let result = test_patience::Client::notify_from_env_var(&var);
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn notify_addr_host_name() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || test_patience::Client::notify_addr(("localhost", port)).expect("failed to notify"));
+
+        server.wait(Duration::from_secs(5)).expect("failed to wait");
+    }
+
+    #[test]
+    fn env() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        let env = server.env();
+        assert_eq!(env, vec![
+            ("TEST_PATIENCE_PORT".to_string(), format!("{}", port)),
+            ("TEST_PATIENCE_TOKEN".to_string(), server.token().to_string()),
+            ("TEST_PATIENCE_RUN".to_string(), server.run_id().to_string()),
+        ]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn spawn_with_port_var() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server")
+            .port_var("INNER_PATIENCE_PORT");
+        let port = server.port().expect("failed to get test-patience server port");
+        assert_eq!(server.env()[0], ("INNER_PATIENCE_PORT".to_string(), format!("{}", port)));
+
+        let mut command = process::Command::new("bash");
+        command.args(["-c", "test -z \"$TEST_PATIENCE_PORT\" && printf done > /dev/tcp/127.0.0.1/$INNER_PATIENCE_PORT; sleep 5"]);
+        command.env_remove("TEST_PATIENCE_PORT");
+        let (child, _) = server.spawn_and_wait(&mut command, Duration::from_secs(5)).expect("failed to wait");
+        drop(test_patience::ChildGuard::new(child));
+    }
+
+    #[test]
+    fn shutdown_cancels_wait() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let shutdown = server.shutdown_handle();
+        assert!(!shutdown.is_shut_down());
+
+        let waiting = thread::spawn(move || server.wait(Duration::from_secs(60)));
+        thread::sleep(Duration::from_millis(100));
+        let start = Instant::now();
+        shutdown.shutdown();
+
+        let result = waiting.join().expect("waiting thread panicked");
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::Interrupted);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn close() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+        let shutdown = server.shutdown_handle();
+
+        server.close();
+
+        assert!(shutdown.is_shut_down());
+        assert!(test_patience::Client::notify(port).is_err());
+    }
+
+    #[test]
+    fn ready_guard() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            let _guard = test_patience::Client::ready_guard(port);
+            thread::sleep(Duration::from_millis(100));
+        });
+
+        let wait_duration = server.wait(Duration::from_secs(5)).expect("failed to wait").elapsed();
+
+        assert!(wait_duration >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn ready_guard_panic() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            let _guard = test_patience::Client::ready_guard(port);
+            panic!("failed to connect to database");
+        });
+
+        let error = server.wait(Duration::from_secs(5)).unwrap_err();
+        let failure = error.get_ref().and_then(|e| e.downcast_ref::<test_patience::StartupFailure>()).expect("no startup failure");
+        assert_eq!(failure.reason(), "application panicked during its initialization");
+    }
+
+    #[test]
+    fn wait_timeout_context() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let addr = server.local_addr().expect("failed to get test-patience server address");
+
+        thread::spawn(move || {
+            // connect without ever sending a notification
+            let _stream = TcpStream::connect(addr).expect("failed to connect");
+            thread::sleep(Duration::from_secs(1));
+        });
+
+        let error = server.wait(Duration::from_millis(300)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert!(error.to_string().starts_with("did not receive startup notification within 300ms"));
+        let timeout = error.get_ref().and_then(|e| e.downcast_ref::<test_patience::WaitTimeout>()).expect("no wait timeout");
+        assert_eq!(timeout.timeout(), Duration::from_millis(300));
+        assert!(timeout.elapsed() >= Duration::from_millis(300));
+        assert_eq!(timeout.local_addr(), Some(addr));
+        assert_eq!(timeout.connections(), 1);
+        assert_eq!(timeout.progress(), None);
+    }
+
+    #[test]
+    fn server_handle() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server")
+            .expect(["api"]);
+        let port = server.port().expect("failed to get test-patience server port");
+        let handle = server.handle();
+        assert_eq!(handle.port().expect("failed to get port from handle"), port);
+
+        let waiting = thread::spawn(move || server.wait_all(Duration::from_secs(5)));
+        let clone = handle.clone();
+        clone.expect("worker");
+        test_patience::Client::notify_named(port, "api").expect("failed to notify");
+        thread::sleep(Duration::from_millis(100));
+        test_patience::Client::notify_named(port, "worker").expect("failed to notify");
+
+        let notifications = waiting.join().unwrap().expect("failed to wait for services");
+        let names: Vec<_> = notifications.iter().map(|notification| notification.name()).collect();
+        assert_eq!(names, vec![Some("api"), Some("worker")]);
+    }
+
+    #[test]
+    fn server_handle_shutdown() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let handle = server.handle();
+
+        let waiting = thread::spawn(move || server.wait(Duration::from_secs(60)));
+        thread::sleep(Duration::from_millis(100));
+        handle.shutdown();
+        assert!(handle.is_shut_down());
+
+        let error = waiting.join().unwrap().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Interrupted);
+    }
+
+    #[test]
+    fn error_timeout() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+
+        let error = test_patience::Error::from(server.wait(Duration::from_millis(100)).unwrap_err());
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        match error {
+            test_patience::Error::Timeout(timeout) => assert_eq!(timeout.timeout(), Duration::from_millis(100)),
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn error_failure() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+        test_patience::Client::notify_failure(port, "config missing").expect("failed to notify");
+
+        match test_patience::Error::from(server.wait(Duration::from_secs(5)).unwrap_err()) {
+            test_patience::Error::Failure(failure) => assert_eq!(failure.reason(), "config missing"),
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn error_cancelled() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        server.shutdown_handle().shutdown();
+
+        let error = test_patience::Error::from(server.wait(Duration::from_secs(5)).unwrap_err());
+        assert!(matches!(error, test_patience::Error::Cancelled));
+        assert_eq!(error.to_string(), "wait was cancelled by shutting down the server");
+        assert_eq!(io::Error::from(error).kind(), io::ErrorKind::Interrupted);
+    }
+
+    #[test]
+    fn error_invalid_notification() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+        TcpStream::connect(("127.0.0.1", port)).expect("failed to connect").write_all(b"started").expect("failed to write");
+
+        let error = test_patience::Error::from(server.wait(Duration::from_secs(5)).unwrap_err());
+        assert!(matches!(error, test_patience::Error::InvalidNotification));
+        assert_eq!(error.to_string(), "wrong startup notification received");
+    }
+
+    #[test]
+    fn error_io() {
+        let error = test_patience::Error::from(io::Error::new(io::ErrorKind::InvalidData, "invalid startup notification"));
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "invalid startup notification");
+    }
+
+    #[test]
+    fn reject_non_loopback_peer() {
+        let ip = match non_loopback_ip() {
+            Some(ip) => ip,
+            None => return,
+        };
+        let server = test_patience::Server::builder()
+            .bind_addr("0.0.0.0:0".parse().unwrap())
+            .build()
+            .expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        test_patience::Client::notify_addr((ip, port)).expect("failed to notify");
+
+        let error = server.wait(Duration::from_millis(300)).unwrap_err();
+        let timeout = error.get_ref().and_then(|e| e.downcast_ref::<test_patience::WaitTimeout>()).expect("no wait timeout");
+        assert_eq!(timeout.connections(), 0);
+    }
+
+    #[test]
+    fn allow_peer() {
+        let ip = match non_loopback_ip() {
+            Some(ip) => ip,
+            None => return,
+        };
+        let server = test_patience::Server::builder()
+            .bind_addr("0.0.0.0:0".parse().unwrap())
+            .allow_peer(ip)
+            .build()
+            .expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        test_patience::Client::notify_addr((ip, port)).expect("failed to notify");
+        server.wait(Duration::from_secs(5)).expect("failed to wait for client");
+    }
+
+    #[test]
+    fn allow_any_peer() {
+        let ip = match non_loopback_ip() {
+            Some(ip) => ip,
+            None => return,
+        };
+        let server = test_patience::Server::builder()
+            .bind_addr("0.0.0.0:0".parse().unwrap())
+            .allow_any_peer()
+            .build()
+            .expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        test_patience::Client::notify_addr((ip, port)).expect("failed to notify");
+        server.wait(Duration::from_secs(5)).expect("failed to wait for client");
+    }
+
+    #[test]
+    fn max_message_size() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server")
+            .max_message_size(16);
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            let mut stream = TcpStream::connect(("127.0.0.1", port)).expect("failed to connect");
+            let _ = stream.write_all(&[b'x'; 64]);
+            thread::sleep(Duration::from_secs(1));
+        });
+
+        let error = server.wait(Duration::from_secs(5)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "message exceeds the maximum size of 16 bytes");
+    }
+
+    #[test]
+    fn max_message_size_framed() {
+        let server = test_patience::Server::builder()
+            .max_message_size(16)
+            .build()
+            .expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            let mut stream = TcpStream::connect(("127.0.0.1", port)).expect("failed to connect");
+            // only the prefix announcing a large frame is sent
+            stream.write_all(&[0, 0, 1, 0]).expect("failed to write");
+            thread::sleep(Duration::from_secs(1));
+        });
+
+        let error = server.wait(Duration::from_secs(5)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn max_message_size_not_exceeded() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server")
+            .max_message_size(4);
+        let port = server.port().expect("failed to get test-patience server port");
+
+        test_patience::Client::notify(port).expect("failed to notify");
+        server.wait(Duration::from_secs(5)).expect("failed to wait for client");
+    }
+
+    #[test]
+    fn ignore_invalid() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server")
+            .ignore_invalid(true)
+            .max_message_size(64);
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            let mut garbage = TcpStream::connect(("127.0.0.1", port)).expect("failed to connect");
+            garbage.write_all(b"GET / HTTP/1.1\r\n\r\n").expect("failed to write");
+            drop(garbage);
+            let mut large = TcpStream::connect(("127.0.0.1", port)).expect("failed to connect");
+            let _ = large.write_all(&[b'x'; 128]);
+            thread::sleep(Duration::from_millis(200));
+            test_patience::Client::notify(port).expect("failed to notify");
+        });
+
+        let notification = server.wait(Duration::from_secs(5)).expect("failed to wait for client");
+        assert!(notification.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn ignore_invalid_failure() {
+        let server = test_patience::Server::builder()
+            .ignore_invalid(true)
+            .build()
+            .expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        test_patience::Client::notify_failure(port, "config missing").expect("failed to notify");
+
+        let error = server.wait(Duration::from_secs(5)).unwrap_err();
+        assert!(error.get_ref().is_some_and(|e| e.is::<test_patience::StartupFailure>()));
+    }
+
+    #[test]
+    fn drain_late() {
+        let mut server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            mock_client(port, Duration::from_millis(300));
+        });
+
+        let error = server.wait_ref(Duration::from_millis(100)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+
+        let start = Instant::now();
+        let late = server.drain_late(Duration::from_millis(500)).expect("failed to drain late notifications");
+        assert_eq!(late.len(), 1);
+        assert!(late[0].elapsed() >= Duration::from_millis(150));
+        assert!(start.elapsed() >= Duration::from_millis(500));
+    }
+
+    #[test]
+    fn drain_late_nothing() {
+        let mut server = test_patience::Server::new().expect("failed to create test-patience server");
+
+        let late = server.drain_late(Duration::from_millis(100)).expect("failed to drain late notifications");
+        assert!(late.is_empty());
+    }
+
+    #[test]
+    fn reject_other_run_id() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+        let run_id = server.run_id().to_string();
+        assert_eq!(run_id.len(), 32);
+
+        thread::spawn(move || {
+            let mut stale = TcpStream::connect(("127.0.0.1", port)).expect("failed to connect");
+            stale.write_all(b"done\nrun: 0123456789abcdef\n\n").expect("failed to write");
+            drop(stale);
+            thread::sleep(Duration::from_millis(200));
+            let mut current = TcpStream::connect(("127.0.0.1", port)).expect("failed to connect");
+            current.write_all(format!("done\nrun: {}\n\n", run_id).as_bytes()).expect("failed to write");
+        });
+
+        let notification = server.wait(Duration::from_secs(5)).expect("failed to wait for client");
+        assert!(notification.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn spawn_with_run_id() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+
+        let mut command = process::Command::new("bash");
+        command.arg("-c").arg("printf 'done\nrun: %s\n\n' $TEST_PATIENCE_RUN > /dev/tcp/127.0.0.1/$TEST_PATIENCE_PORT");
+        let (mut child, _) = server.spawn_and_wait(&mut command, Duration::from_secs(5)).expect("failed to wait for child");
+        child.wait().expect("failed to wait for child to exit");
+    }
+
+    #[test]
+    fn duplicates_accept() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        test_patience::Client::notify_named(port, "api").expect("failed to notify");
+        test_patience::Client::notify_named(port, "api").expect("failed to notify");
+
+        let notifications = server.wait_for_n(2, Duration::from_secs(5)).expect("failed to wait for clients");
+        assert_eq!(notifications.len(), 2);
+    }
+
+    #[test]
+    fn duplicates_ignore() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server")
+            .duplicates(test_patience::Duplicates::Ignore);
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            test_patience::Client::notify_named(port, "api").expect("failed to notify");
+            test_patience::Client::notify_named(port, "api").expect("failed to notify");
+            thread::sleep(Duration::from_millis(100));
+            test_patience::Client::notify_named(port, "worker").expect("failed to notify");
+        });
+
+        let notifications = server.wait_for_n(2, Duration::from_secs(5)).expect("failed to wait for clients");
+        let names: Vec<_> = notifications.iter().map(|notification| notification.name()).collect();
+        assert_eq!(names, vec![Some("api"), Some("worker")]);
+    }
+
+    #[test]
+    fn duplicates_error() {
+        let server = test_patience::Server::builder()
+            .duplicates(test_patience::Duplicates::Error)
+            .build()
+            .expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        test_patience::Client::notify_named(port, "api").expect("failed to notify");
+        thread::sleep(Duration::from_millis(50));
+        test_patience::Client::notify_named(port, "api").expect("failed to notify");
+
+        let error = server.wait_for_n(2, Duration::from_secs(5)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "received repeated startup notification of api");
+        match test_patience::Error::from(error) {
+            test_patience::Error::Duplicate(name) => assert_eq!(name, "api"),
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn wait_for_burst_of_clients() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        let clients: Vec<_> = (0..200)
+            .map(|i| thread::spawn(move || test_patience::Client::notify_named(port, &format!("service-{}", i))))
+            .collect();
+        for client in clients {
+            client.join().unwrap().expect("failed to notify");
+        }
+
+        let notifications = server.wait_for_n(200, Duration::from_secs(10)).expect("failed to wait for clients");
+        let mut names: Vec<_> = notifications.iter().filter_map(|notification| notification.name()).collect();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), 200);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn wait_wakes_on_notification() {
+        let server = test_patience::Server::builder()
+            .poll_interval(Duration::from_millis(500))
+            .build()
+            .expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || mock_client(port, Duration::from_millis(100)));
+
+        let wait_duration = server.wait(Duration::from_secs(5)).expect("failed to wait").elapsed();
+
+        assert_close!(wait_duration, Duration::from_millis(100));
+    }
+
+    #[test]
+    fn poll_backoff() {
+        let path = std::env::temp_dir().join(format!("test-patience-{}.backoff", std::process::id()));
+        let server = test_patience::Server::watch_file(&path).expect("failed to create test-patience server")
+            .poll_backoff(test_patience::probe::Backoff::exponential(Duration::from_millis(10), Duration::from_millis(100)));
+
+        let client_path = path.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(300));
+            test_patience::Client::notify_file(client_path).expect("failed to notify");
+        });
+
+        let wait_duration = server.wait(Duration::from_secs(5)).expect("failed to wait").elapsed();
+
+        // the checks slow down to one every 100 ms
+        assert!(wait_duration >= Duration::from_millis(300));
+        assert!(wait_duration < Duration::from_millis(450));
+    }
+
+    #[test]
+    fn notification_overhead_not_requested() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+        assert!(server.env().iter().all(|(name, _)| name != "TEST_PATIENCE_TIMESTAMP"));
+
+        thread::spawn(move || mock_client(port, Duration::from_millis(100)));
+
+        let notification = server.wait(Duration::from_secs(5)).expect("failed to wait");
+
+        assert_eq!(notification.overhead(), None);
+    }
+
+    #[test]
+    fn notification_overhead_without_timestamp() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            let mut stream = TcpStream::connect(("127.0.0.1", port)).expect("failed to connect");
+            stream.write_all(b"done").expect("failed to send notification");
+        });
+
+        let notification = server.wait(Duration::from_secs(5)).expect("failed to wait");
+
+        assert_eq!(notification.overhead(), None);
+    }
+
+    #[test]
+    fn wait_for_n_concurrent_clients() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        for i in 0..200 {
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(100));
+                test_patience::Client::notify_named(port, &format!("service-{}", i)).expect("failed to notify");
+            });
+        }
+
+        let notifications = server.wait_for_n(200, Duration::from_secs(10)).expect("failed to wait for clients");
+
+        assert_eq!(notifications.len(), 200);
+        assert!(notifications[199].elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn shutdown_wakes_wait() {
+        let server = test_patience::Server::builder()
+            .poll_interval(Duration::from_secs(10))
+            .build()
+            .expect("failed to create test-patience server");
+        let shutdown = server.shutdown_handle();
+
+        let waiting = thread::spawn(move || server.wait(Duration::from_secs(60)));
+        thread::sleep(Duration::from_millis(100));
+        let start = Instant::now();
+        shutdown.shutdown();
+
+        let result = waiting.join().expect("waiting thread panicked");
+        assert!(matches!(result.map_err(test_patience::Error::from), Err(test_patience::Error::Cancelled)));
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn shutdown_wakes_polling_wait() {
+        let path = std::env::temp_dir().join(format!("test-patience-{}.shutdown", std::process::id()));
+        let server = test_patience::Server::watch_file(&path).expect("failed to create test-patience server")
+            .poll_interval(Duration::from_secs(10));
+        let shutdown = server.shutdown_handle();
+
+        let waiting = thread::spawn(move || server.wait(Duration::from_secs(60)));
+        thread::sleep(Duration::from_millis(100));
+        let start = Instant::now();
+        shutdown.shutdown();
+
+        let result = waiting.join().expect("waiting thread panicked");
+        assert!(matches!(result.map_err(test_patience::Error::from), Err(test_patience::Error::Cancelled)));
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
 }
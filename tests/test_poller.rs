@@ -0,0 +1,106 @@
+#![cfg(all(feature = "mio", unix))]
+
+extern crate test_patience;
+
+use std::io;
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use test_patience::Poller;
+
+fn mock_client(port: u16, sleep: Duration) {
+    thread::sleep(sleep);
+    let _ = test_patience::Client::notify(port); // ignore errors when testing timeout
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wait_all_servers() {
+        let mut poller = Poller::new().expect("failed to create poller");
+        for i in 0..20 {
+            let server = test_patience::Server::new().expect("failed to create test-patience server");
+            let port = server.port().expect("failed to get test-patience server port");
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(10 * (20 - i)));
+                test_patience::Client::notify_named(port, &format!("service-{}", i)).expect("failed to notify");
+            });
+            poller = poller.server(server);
+        }
+
+        let notifications = poller.wait_all(Duration::from_secs(5)).expect("failed to wait");
+
+        assert_eq!(notifications.len(), 20);
+        for (i, notification) in notifications.iter().enumerate() {
+            assert_eq!(notification.name(), Some(format!("service-{}", i).as_str()));
+        }
+    }
+
+    #[test]
+    fn wait_all_servers_timeout() {
+        let first = test_patience::Server::new().expect("failed to create test-patience server");
+        let second = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = first.port().expect("failed to get test-patience server port");
+        let addr = second.local_addr().expect("failed to get test-patience server address");
+        thread::spawn(move || mock_client(port, Duration::from_millis(10)));
+
+        let poller = Poller::new().expect("failed to create poller").server(first).server(second);
+        let error = poller.wait_all(Duration::from_millis(200)).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        match test_patience::Error::from(error) {
+            test_patience::Error::Timeout(timeout) => assert_eq!(timeout.local_addr(), Some(addr)),
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn wait_all_servers_without_sockets() {
+        let path = std::env::temp_dir().join(format!("test-patience-{}.poller", std::process::id()));
+        let file = test_patience::Server::watch_file(&path).expect("failed to create test-patience server");
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+        thread::spawn(move || {
+            mock_client(port, Duration::from_millis(10));
+            thread::sleep(Duration::from_millis(50));
+            fs::write(&path, "").expect("failed to create sentinel file");
+        });
+
+        let poller = Poller::new().expect("failed to create poller").server(file).server(server);
+        let notifications = poller.wait_all(Duration::from_secs(5)).expect("failed to wait");
+
+        assert_eq!(notifications.len(), 2);
+        assert!(notifications[0].elapsed() >= Duration::from_millis(60));
+    }
+
+    #[test]
+    fn wait_all_servers_shutdown() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        server.shutdown_handle().shutdown();
+
+        let error = Poller::new().expect("failed to create poller").server(server).wait_all(Duration::from_secs(5)).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::Interrupted);
+    }
+
+    #[test]
+    fn wait_all_servers_shutdown_wakes() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let shutdown = server.shutdown_handle();
+        let poller = Poller::new().expect("failed to create poller")
+            .server(test_patience::Server::new().expect("failed to create test-patience server"))
+            .server(server);
+
+        let waiting = thread::spawn(move || poller.wait_all(Duration::from_secs(60)));
+        thread::sleep(Duration::from_millis(100));
+        let start = Instant::now();
+        shutdown.shutdown();
+
+        let error = waiting.join().expect("waiting thread panicked").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Interrupted);
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,267 @@
+extern crate test_patience;
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener};
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Instant, Duration};
+
+#[cfg(unix)]
+use std::process::Command;
+
+use test_patience::probe::{self, Backoff, Probe};
+
+/// Get a local address on which nothing listens
+fn unused_addr() -> SocketAddr {
+    let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+    listener.local_addr().unwrap()
+}
+
+/// Serve the `responses` one after another to the HTTP requests sent to the returned address
+fn mock_http_server(responses: &'static [&'static str]) -> SocketAddr {
+    let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for response in responses.iter().cycle() {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request);
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+    addr
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tcp_probe() {
+        let addr = unused_addr();
+        let late = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            TcpListener::bind(addr).unwrap()
+        });
+
+        let elapsed = probe::tcp(addr).wait(Duration::from_secs(5)).expect("probe failed");
+
+        assert!(elapsed >= Duration::from_millis(200));
+        drop(late.join());
+    }
+
+    #[test]
+    fn tcp_probe_timeout() {
+        let result = probe::tcp(unused_addr()).interval(Duration::from_millis(50)).wait(Duration::from_millis(200));
+
+        let e = result.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+        assert!(e.to_string().starts_with("probe did not succeed ("), "{}", e);
+    }
+
+    #[test]
+    fn http_probe() {
+        let addr = mock_http_server(&[
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Length: 8\r\n\r\nstarting",
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok",
+        ]);
+
+        probe::http(&format!("http://{}/healthz", addr)).body_contains("ok").wait(Duration::from_secs(5)).expect("probe failed");
+    }
+
+    #[test]
+    fn http_probe_unexpected_status() {
+        let addr = mock_http_server(&["HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n"]);
+
+        let result = probe::http(&format!("http://{}/healthz", addr)).wait(Duration::from_millis(200));
+
+        let e = result.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(e.to_string(), "probe did not succeed (received status 503)");
+    }
+
+    #[test]
+    fn http_probe_invalid_url() {
+        let result = probe::http("https://127.0.0.1/healthz").wait(Duration::from_millis(100));
+
+        let e = result.unwrap_err();
+        assert!(e.to_string().contains("only http:// URLs can be probed"), "{}", e);
+    }
+
+    #[test]
+    fn custom_probe() {
+        let checks = Arc::new(AtomicUsize::new(0));
+        let counter = checks.clone();
+
+        probe::custom(move || counter.fetch_add(1, Ordering::SeqCst) == 3, Duration::from_millis(10))
+            .wait(Duration::from_secs(5))
+            .expect("probe failed");
+
+        assert_eq!(checks.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn custom_probe_timeout() {
+        let result = probe::custom(|| false, Duration::from_millis(10)).wait(Duration::from_millis(100));
+
+        let e = result.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(e.to_string(), "probe did not succeed (condition does not hold)");
+    }
+
+    #[test]
+    fn file_probe() {
+        let path = std::env::temp_dir().join(format!("test-patience-file-probe-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let created = path.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            fs::File::create(created).unwrap();
+        });
+
+        let elapsed = probe::file(&path).wait(Duration::from_secs(5)).expect("probe failed");
+
+        assert!(elapsed >= Duration::from_millis(100));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn file_probe_non_empty() {
+        let path = std::env::temp_dir().join(format!("test-patience-non-empty-probe-{}", std::process::id()));
+        fs::File::create(&path).unwrap();
+
+        let result = probe::file(&path).non_empty().wait(Duration::from_millis(100));
+        let e = result.unwrap_err();
+        assert_eq!(e.to_string(), format!("probe did not succeed ({} is empty)", path.display()));
+
+        fs::write(&path, "42").unwrap();
+        probe::file(&path).non_empty().wait(Duration::from_secs(5)).expect("probe failed");
+        let _ = fs::remove_file(&path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_probe() {
+        let path = std::env::temp_dir().join(format!("test-patience-command-probe-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut command = Command::new("bash");
+        command.arg("-c").arg("test -f \"$MARKER\" || { touch \"$MARKER\"; exit 1; }").env("MARKER", &path);
+
+        probe::command(command).interval(Duration::from_millis(10)).wait(Duration::from_secs(5)).expect("probe failed");
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_probe_capture_output() {
+        let mut command = Command::new("bash");
+        command.arg("-c").arg("echo no response; exit 2");
+
+        let result = probe::command(command).capture_output().wait(Duration::from_millis(100));
+
+        let e = result.unwrap_err();
+        assert_eq!(e.to_string(), "probe did not succeed (command failed with exit status: 2\nno response)");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_probe() {
+        let path = std::env::temp_dir().join(format!("test-patience-unix-probe-{}.sock", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let bound = path.clone();
+        let late = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            UnixListener::bind(bound).unwrap()
+        });
+
+        probe::unix(&path).wait(Duration::from_secs(5)).expect("probe failed");
+
+        drop(late.join());
+        let _ = fs::remove_file(&path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_probe_not_listening() {
+        let path = std::env::temp_dir().join(format!("test-patience-stale-probe-{}.sock", std::process::id()));
+        let _ = fs::remove_file(&path);
+        drop(UnixListener::bind(&path).unwrap());
+
+        let result = probe::unix(&path).wait(Duration::from_millis(100));
+
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn all_probe() {
+        let addr = mock_http_server(&["HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n"]);
+        let path = std::env::temp_dir().join(format!("test-patience-all-probe-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let result = probe::all(vec![Box::new(probe::tcp(addr)), Box::new(probe::file(&path))]).wait(Duration::from_millis(100));
+        let e = result.unwrap_err();
+        assert!(e.to_string().starts_with(&format!("probe did not succeed (probe of {}: ", path.display())), "{}", e);
+
+        fs::File::create(&path).unwrap();
+        probe::all(vec![Box::new(probe::tcp(addr)), Box::new(probe::file(&path))]).wait(Duration::from_secs(5)).expect("probe failed");
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn any_probe() {
+        let addr = mock_http_server(&["HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n"]);
+
+        probe::any(vec![Box::new(probe::tcp(unused_addr())), Box::new(probe::tcp(addr))]).wait(Duration::from_secs(5)).expect("probe failed");
+
+        let unused = unused_addr();
+        let result = probe::any(vec![Box::new(probe::custom(|| false, Duration::from_millis(10))), Box::new(probe::tcp(unused))])
+            .wait(Duration::from_millis(100));
+        let e = result.unwrap_err();
+        let expected = format!("probe did not succeed (custom probe: condition does not hold; TCP probe of {}: ", unused);
+        assert!(e.to_string().starts_with(&expected), "{}", e);
+    }
+
+    #[cfg(feature = "docker")]
+    #[test]
+    fn docker_probe_unknown_container() {
+        let result = probe::docker("test-patience-unknown-container").wait(Duration::from_millis(100));
+
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn probe_with_exponential_backoff() {
+        let checks = Arc::new(AtomicUsize::new(0));
+        let counter = checks.clone();
+
+        let _ = probe::custom(move || { counter.fetch_add(1, Ordering::SeqCst); false }, Duration::from_millis(1))
+            .with_backoff(Backoff::exponential(Duration::from_millis(10), Duration::from_secs(1)))
+            .wait(Duration::from_millis(200));
+
+        // checks after 0, 10, 30, 70, 150 and 200 ms
+        assert!(checks.load(Ordering::SeqCst) <= 6);
+    }
+
+    #[test]
+    fn probe_with_jitter() {
+        let checks = Arc::new(AtomicUsize::new(0));
+        let counter = checks.clone();
+        let start = Instant::now();
+
+        let _ = probe::custom(move || { counter.fetch_add(1, Ordering::SeqCst); false }, Duration::from_millis(1))
+            .with_backoff(Backoff::fixed(Duration::from_millis(40)).jitter())
+            .wait(Duration::from_millis(200));
+
+        // delays between 20 and 40 ms
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        let checks = checks.load(Ordering::SeqCst);
+        assert!((5..=12).contains(&checks), "{} checks", checks);
+    }
+}
@@ -0,0 +1,81 @@
+extern crate test_patience;
+
+#[cfg(target_os = "linux")]
+use std::fs;
+#[cfg(target_os = "linux")]
+use std::io::{BufRead, BufReader};
+#[cfg(target_os = "linux")]
+use std::path::Path;
+#[cfg(unix)]
+use std::process::Command;
+#[cfg(target_os = "linux")]
+use std::process::Stdio;
+#[cfg(target_os = "linux")]
+use std::thread;
+#[cfg(target_os = "linux")]
+use std::time::{Duration, Instant};
+
+#[cfg(unix)]
+use test_patience::ChildGuard;
+
+/// Check whether the process `id` is running and not just a zombie waiting to be reaped by init
+#[cfg(target_os = "linux")]
+fn is_running(id: &str) -> bool {
+    match fs::read_to_string(Path::new("/proc").join(id).join("stat")) {
+        Ok(stat) => stat.rsplit(") ").next().is_some_and(|fields| !fields.starts_with('Z')),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn child_guard_kills_child() {
+        let child = Command::new("sleep").arg("5").spawn().expect("failed to spawn application");
+        let proc_dir = Path::new("/proc").join(child.id().to_string());
+
+        let guard = ChildGuard::new(child);
+        assert!(proc_dir.exists());
+        drop(guard);
+
+        assert!(!proc_dir.exists(), "application wasn't killed and reaped");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn child_guard_into_inner() {
+        let guard = ChildGuard::from(Command::new("sleep").arg("5").spawn().expect("failed to spawn application"));
+        let id = guard.id();
+
+        let mut child = guard.into_inner();
+
+        assert_eq!(child.id(), id);
+        assert!(child.try_wait().expect("failed to check application").is_none());
+        child.kill().expect("failed to kill application");
+        child.wait().expect("failed to reap application");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn child_guard_kills_process_group() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let mut command = Command::new("bash");
+        command.arg("-c").arg("sleep 5 & echo $!; wait").stdout(Stdio::piped());
+        let mut child = server.spawn(&mut command).expect("failed to spawn application");
+        let mut grandchild = String::new();
+        BufReader::new(child.stdout.take().unwrap()).read_line(&mut grandchild).expect("failed to read process id");
+        let grandchild = grandchild.trim().to_string();
+
+        assert!(is_running(&grandchild));
+        drop(ChildGuard::new(child));
+
+        let start = Instant::now();
+        while is_running(&grandchild) && start.elapsed() < Duration::from_secs(1) {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(!is_running(&grandchild), "process started by the application wasn't killed");
+    }
+}
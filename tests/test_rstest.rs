@@ -0,0 +1,54 @@
+#![cfg(unix)]
+
+extern crate test_patience;
+
+use std::thread;
+use std::time::Duration;
+
+use rstest::{fixture, rstest};
+use test_patience::fixture::{PatienceFixture, Readiness, RunningApp};
+use test_patience::probe;
+
+#[fixture]
+fn app() -> RunningApp {
+    PatienceFixture::new("bash")
+        .args(["-c", "printf \"done\n\n$PAYLOAD\" > /dev/tcp/127.0.0.1/$TEST_PATIENCE_PORT; sleep 5"])
+        .env("PAYLOAD", "db")
+        .start()
+}
+
+#[fixture]
+fn probed() -> RunningApp {
+    PatienceFixture::new("sleep")
+        .arg("5")
+        .ready(Readiness::probe(probe::custom(|| true, Duration::from_millis(10)), Duration::from_secs(1)))
+        .start()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[rstest]
+    fn notified(mut app: RunningApp) {
+        assert_eq!(app.notification().expect("no notification").payload(), b"db");
+        assert!(app.try_wait().expect("failed to check application").is_none());
+    }
+
+    #[rstest]
+    fn moved_into_thread(probed: RunningApp) {
+        let id = probed.id();
+        let app = thread::spawn(move || probed).join().expect("thread panicked");
+        assert_eq!(app.id(), id);
+        assert!(app.stop().is_ok());
+    }
+
+    #[test]
+    fn not_ready() {
+        let result = PatienceFixture::new("sleep")
+            .arg("5")
+            .ready(Readiness::probe(probe::custom(|| false, Duration::from_millis(10)), Duration::from_millis(100)))
+            .try_start();
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::TimedOut);
+    }
+}
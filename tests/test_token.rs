@@ -0,0 +1,65 @@
+extern crate test_patience;
+
+use std::env;
+use std::time::Duration;
+use std::thread;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the only test in this file, as it sets the environment variable of the whole process
+    #[test]
+    fn token_from_environment() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server")
+            .require_token(true).expect("failed to require token");
+        let port = server.port().expect("failed to get test-patience server port");
+        env::set_var("TEST_PATIENCE_TOKEN", server.token());
+        thread::spawn(move || {
+            test_patience::Client::notify(port).expect("failed to notify");
+        });
+        server.wait(Duration::from_secs(5)).expect("failed to wait for TCP notification");
+
+        let server = test_patience::Server::new().expect("failed to create test-patience server")
+            .require_token(true).expect("failed to require token");
+        let port = server.port().expect("failed to get test-patience server port");
+        env::set_var("TEST_PATIENCE_TOKEN", server.token());
+        thread::spawn(move || {
+            let heartbeat = test_patience::Client::heartbeat(port, Duration::from_millis(50)).expect("failed to open heartbeat connection");
+            thread::sleep(Duration::from_millis(200));
+            heartbeat.notify().expect("failed to notify");
+        });
+        server.wait(Duration::from_secs(5)).expect("failed to wait for heartbeat notification");
+
+        let server = test_patience::Server::new_udp().expect("failed to create test-patience server")
+            .require_token(true).expect("failed to require token");
+        let port = server.port().expect("failed to get test-patience server port");
+        env::set_var("TEST_PATIENCE_TOKEN", server.token());
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            test_patience::Client::notify_udp(port).expect("failed to notify");
+        });
+        server.wait(Duration::from_secs(5)).expect("failed to wait for UDP notification");
+
+        #[cfg(unix)]
+        {
+            let path = env::temp_dir().join(format!("test-patience-token-{}.sock", std::process::id()));
+            let server = test_patience::Server::new_unix(&path).expect("failed to create test-patience server")
+                .require_token(true).expect("failed to require token");
+            env::set_var("TEST_PATIENCE_TOKEN", server.token());
+            thread::spawn(move || {
+                test_patience::Client::notify_unix(&path).expect("failed to notify");
+            });
+            server.wait(Duration::from_secs(5)).expect("failed to wait for Unix socket notification");
+        }
+
+        let server = test_patience::Server::new().expect("failed to create test-patience server")
+            .require_token(true).expect("failed to require token");
+        let port = server.port().expect("failed to get test-patience server port");
+        env::set_var("TEST_PATIENCE_TOKEN", "wrong");
+        thread::spawn(move || {
+            test_patience::Client::notify(port).expect("failed to notify");
+        });
+        assert!(server.wait(Duration::from_millis(500)).is_err());
+    }
+}
@@ -0,0 +1,159 @@
+#![cfg(feature = "tokio")]
+
+extern crate test_patience;
+
+use std::time::Duration;
+use std::thread;
+use std::io;
+
+fn mock_client(port: u16, sleep: Duration) {
+    thread::sleep(sleep);
+    let _ = test_patience::Client::notify(port); // ignore errors when testing timeout
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn wait_for_client() {
+        let server = test_patience::tokio::Server::new().await.expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            mock_client(port, Duration::from_millis(500));
+        });
+
+        let wait_duration = server.wait(Duration::from_secs(5)).await.expect("failed to wait").elapsed();
+
+        assert!(wait_duration >= Duration::from_millis(500));
+        assert!(wait_duration < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn wait_for_client_timeout() {
+        let server = test_patience::tokio::Server::new().await.expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            mock_client(port, Duration::from_secs(2));
+        });
+
+        let result = server.wait(Duration::from_secs(1)).await;
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[tokio::test]
+    async fn notify_async() {
+        let server = test_patience::tokio::Server::new().await.expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            test_patience::Client::notify_async(port).await.expect("failed to notify");
+        });
+
+        let wait_duration = server.wait(Duration::from_secs(5)).await.expect("failed to wait").elapsed();
+
+        assert!(wait_duration >= Duration::from_millis(500));
+        assert!(wait_duration < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn notify_async_blocking_server() {
+        let server = test_patience::Server::new().expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        let waiter = thread::spawn(move || server.wait(Duration::from_secs(5)));
+
+        test_patience::Client::notify_async(port).await.expect("failed to notify");
+
+        waiter.join().unwrap().expect("failed to wait").elapsed();
+    }
+
+    #[tokio::test]
+    async fn notified_with_timeout() {
+        let server = test_patience::tokio::Server::new().await.expect("failed to create test-patience server");
+        let port = server.port().expect("failed to get test-patience server port");
+
+        thread::spawn(move || {
+            mock_client(port, Duration::from_millis(500));
+        });
+
+        let wait_duration = tokio::time::timeout(Duration::from_secs(5), server.notified()).await
+            .expect("timed out")
+            .expect("failed to wait").elapsed();
+
+        assert!(wait_duration >= Duration::from_millis(500));
+        assert!(wait_duration < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn notified_select() {
+        let server = test_patience::tokio::Server::new().await.expect("failed to create test-patience server");
+
+        let notified = server.notified();
+        tokio::pin!(notified);
+
+        tokio::select! {
+            _ = &mut notified => panic!("received unexpected startup notification"),
+            _ = tokio::time::sleep(Duration::from_millis(100)) => {}
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn spawn_and_wait() {
+        let server = test_patience::tokio::Server::new().await.expect("failed to create test-patience server");
+
+        let mut command = tokio::process::Command::new("bash");
+        command.arg("-c").arg("sleep 0.5; printf done > /dev/tcp/127.0.0.1/$TEST_PATIENCE_PORT; sleep 5");
+        let (mut child, notification) = test_patience::tokio::spawn_and_wait(server, &mut command).await.expect("failed to wait");
+
+        assert!(notification.elapsed() >= Duration::from_millis(500));
+        assert!(notification.elapsed() < Duration::from_secs(5));
+        child.kill().await.expect("failed to kill application");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn spawn_and_wait_with_token_and_run_id() {
+        let server = test_patience::tokio::Server::new().await.expect("failed to create test-patience server")
+            .require_token(true);
+
+        let mut command = tokio::process::Command::new("bash");
+        command.arg("-c").arg("printf 'done\ntoken: %s\nrun: %s\n\n' $TEST_PATIENCE_TOKEN $TEST_PATIENCE_RUN > /dev/tcp/127.0.0.1/$TEST_PATIENCE_PORT; sleep 5");
+        let (mut child, _) = test_patience::tokio::spawn_and_wait(server, &mut command).await.expect("failed to wait");
+
+        child.kill().await.expect("failed to kill application");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn spawn_and_wait_exited() {
+        let server = test_patience::tokio::Server::new().await.expect("failed to create test-patience server");
+
+        let mut command = tokio::process::Command::new("sh");
+        command.arg("-c").arg("exit 3");
+        let result = test_patience::tokio::spawn_and_wait(server, &mut command).await;
+
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("exited"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn spawn_and_wait_invalid_notification() {
+        let server = test_patience::tokio::Server::new().await.expect("failed to create test-patience server");
+        let marker = std::env::temp_dir().join(format!("test-patience-still-running-{}", std::process::id()));
+
+        let mut command = tokio::process::Command::new("bash");
+        command.arg("-c").arg("printf invalid > /dev/tcp/127.0.0.1/$TEST_PATIENCE_PORT; sleep 0.5; touch \"$MARKER\"").env("MARKER", &marker);
+        let result = test_patience::tokio::spawn_and_wait(server, &mut command).await;
+
+        assert!(result.is_err());
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert!(!marker.exists(), "application wasn't killed");
+    }
+}